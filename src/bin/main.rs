@@ -8,37 +8,148 @@
 //! make
 //! ```
 //! where `rows` ∈ [1, 999] and `cols` ∈ [1, 18278].
+//!
+//! Options:
+//! - `--journal PATH`: autosave accepted edits to `PATH` (default
+//!   `spreadsheet.journal`); quitting with `q` or end of input deletes it
+//! - `--no-journal`: do not autosave
+//! - `--recover`: restore the sheet saved in the journal before starting
+//! - `--discard-journal`: delete the sheet saved in the journal and start
+//!   empty; without it (or `--recover`) the program refuses to start over a
+//!   journal that still holds edits
+//! - `--history PATH`: keep line-editor history in `PATH` (default
+//!   `~/.spreadsheet_history`); `--no-history` turns it off
+//! - `--rpc`: speak newline-delimited JSON on stdin/stdout instead of the
+//!   interactive grid (see [`lab1::rpc`]); `rows`/`cols` default to 100 and
+//!   nothing is journaled
+//! - `--timeout SECS`: abandon (and roll back) any edit whose evaluation takes
+//!   longer than `SECS`; Ctrl-C abandons the edit in progress at any time
+//! - `--tui`: full-screen editor with a cell cursor and formula bar (needs the
+//!   `tui` feature; see [`lab1::tui`]); edits are journaled as usual
+//!
 //! Once running, you can enter:
-//! - `A1=5`‐style formulas
+//! - `A1=5`‐style formulas, including conditions such as `C1=IF(A1>B1, A1, B1)`,
+//!   criteria aggregates such as `D1=COUNTIF(A1:A9, ">10")`, lookups such
+//!   as `E1=VLOOKUP(A1, H1:I9, 2, FALSE)`, computed references such as
+//!   `F1=INDIRECT("B"&A1)`, math such as `G1=ROUND(SQRT(A1), 2)`, text
+//!   such as `H1='Total` or `I1=UPPER(H1) & ": " & TEXT(A1, "0.00")` and
+//!   dates such as `J1=DATE("2024-05-01")` or `K1=NETWORKDAYS(J1, TODAY())`
 //! - `w`, `a`, `s`, `d`, or `scroll_to X,Y` to pan the viewport
 //! - `disable_output` / `enable_output` to toggle re-rendering
+//! - `set_view ROWS COLS`, `width B 20`, `scroll_step N` to size the grid
+//! - `precision N` / `precision auto` to choose the decimals shown
+//! - `print A1` / `print A1:C3` and `export csv|json|values` to dump values
+//! - `show B3`, `deps B3`, `precs A1:B3` and `why B3` to inspect cells
+//! - `graph dot|json [B3]` to dump the dependency graph
+//! - `iterate on [MAX [TOLERANCE]]` / `iterate off` to allow circular references
+//! - `timeout SECS` / `timeout off` to set the per-edit time budget
+//! - `numbers int` / `numbers float` to compute whole numbers exactly or as floats, and
+//!   `numbers decimal SCALE [half-even|half-up]` for fixed-point decimals
 //! - `q` (or `Q`) to quit
 //!
-//! Each command is timed and you’ll see an `[s.s] (ok|err) > ` prompt.
+//! Each command is timed and you’ll see an `[s.s] (ok|err) > ` prompt; the
+//! reason for an `err` (e.g. `Formula error: cycle A1 → B1 → A1`) goes to
+//! stderr. On a
+//! terminal the prompt is a line editor: arrow keys move and recall history,
+//! and Tab completes commands, function names and cell references.
+//!
+//! # Batch mode
+//! ```text
+//! spreadsheet run [SCRIPT | -] [--rows N] [--cols N] [--keep-going]
+//!                 [--print CELLS]... [--export csv|json|values]
+//! ```
+//! Runs the commands in `SCRIPT` (or stdin) without the grid or prompts,
+//! stopping with a non-zero exit status at the first failing line unless
+//! `--keep-going` is given. The sheet defaults to 100 × 100. `--print` and
+//! `--export` are written to stdout after the script finishes.
+//!
+//! # One-off evaluation
+//! ```text
+//! spreadsheet eval [--input data.csv] [--set A1=EXPR]... [--print CELLS]...
+//!                  [--format text|csv|json]
+//! ```
+//! Loads the CSV (if any), applies each `--set` in order and prints the
+//! requested cells; errors go to stderr with a non-zero exit status.
 #[cfg(feature = "autograder")]
 #[allow(unused_assignments)]
 fn main() {
     use std::env;
-    use std::io::{self, Write};
+    use std::io;
+    use std::path::PathBuf;
     use std::time::Instant;
 
     // Import modules from your library (the name here must match the package name in Cargo.toml)
-    use lab1::{display, input_parser, scrolling, spreadsheet};
+    use lab1::command::{self, Outcome};
+    use lab1::{journal, rpc, scrolling, spreadsheet};
 
     // Original CLI code from your previous main.rs:
     let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        println!("Usage: {} <rows> <cols>", args[0]);
+    match args.get(1).map(String::as_str) {
+        Some("run") => std::process::exit(run_batch(&args[2..])),
+        Some("eval") => std::process::exit(run_eval(&args[2..])),
+        _ => {}
+    }
+    let mut positional = Vec::new();
+    let mut recover = false;
+    let mut discard = false;
+    let mut rpc = false;
+    let mut history_path =
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(".spreadsheet_history"));
+    let mut tui = false;
+    let mut journal_path = Some(PathBuf::from("spreadsheet.journal"));
+    let mut budget = None;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--recover" => recover = true,
+            "--discard-journal" => discard = true,
+            "--rpc" => rpc = true,
+            "--no-history" => history_path = None,
+            "--history" if i + 1 < args.len() => {
+                i += 1;
+                history_path = Some(PathBuf::from(&args[i]));
+            }
+            "--tui" => tui = true,
+            "--timeout" if i + 1 < args.len() => {
+                i += 1;
+                match command::parse_budget(&args[i]) {
+                    Ok(b) => budget = b,
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        return;
+                    }
+                }
+            }
+            "--no-journal" => journal_path = None,
+            "--journal" if i + 1 < args.len() => {
+                i += 1;
+                journal_path = Some(PathBuf::from(&args[i]));
+            }
+            _ => positional.push(args[i].as_str()),
+        }
+        i += 1;
+    }
+    if rpc && positional.is_empty() {
+        positional = vec!["100", "100"];
+    }
+    if positional.len() != 2
+        || ((recover || discard) && journal_path.is_none())
+        || (recover && discard)
+    {
+        println!(
+            "Usage: {} <rows> <cols> [--recover | --discard-journal] [--journal PATH | --no-journal] [--history PATH | --no-history] [--timeout SECS] [--rpc | --tui]",
+            args[0]
+        );
         return;
     }
-    let rows: usize = match args[1].parse() {
+    let rows: usize = match positional[0].parse() {
         Ok(num) if (1..=1000).contains(&num) => num,
         _ => {
             println!("Error: Rows should be between 1 and 1000 inclusive");
             return;
         }
     };
-    let cols: usize = match args[2].parse() {
+    let cols: usize = match positional[1].parse() {
         Ok(num) if (1..=18278).contains(&num) => num,
         _ => {
             println!("Error: Cols should be between 1 and 18278 inclusive");
@@ -46,36 +157,98 @@ fn main() {
         }
     };
 
-    // Initialize the spreadsheet
+    if rpc {
+        let mut sheet = spreadsheet::initialize_spreadsheet(rows, cols);
+        sheet.graph.scheduler.set_budget(budget);
+        if let Err(e) = rpc::serve(&mut sheet, io::stdin().lock(), &mut io::stdout().lock()) {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    // Initialize the spreadsheet, restoring the last session if asked to
     let mut spreadsheet = spreadsheet::initialize_spreadsheet(rows, cols);
+    let mut journal = None;
+    if let Some(path) = &journal_path {
+        let opened = if recover {
+            match journal::recover(path, rows, cols) {
+                Ok((restored, replayed, skipped)) => {
+                    spreadsheet = restored;
+                    println!("Recovered {} edits from {}", replayed, path.display());
+                    for line in skipped {
+                        eprintln!("Warning: skipped journal {}", line);
+                    }
+                }
+                Err(e) => {
+                    println!("Error: cannot recover from {}: {}", path.display(), e);
+                    return;
+                }
+            }
+            journal::Journal::resume(path)
+        } else if discard {
+            journal::Journal::discard(path).and_then(|_| journal::Journal::create(path))
+        } else {
+            journal::Journal::create(path)
+        };
+        match opened {
+            Ok(j) => journal = Some(j),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                println!(
+                    "Error: {} holds edits from an earlier session; start with --recover to restore them or --discard-journal to delete them",
+                    path.display()
+                );
+                return;
+            }
+            Err(e) => eprintln!("Warning: autosave disabled ({}: {})", path.display(), e),
+        }
+    }
+    spreadsheet.graph.scheduler.set_budget(budget);
+    if tui {
+        #[cfg(feature = "tui")]
+        {
+            // the screen is in raw mode, so report autosave problems on exit
+            let mut save_error = None;
+            let result = lab1::tui::run(&mut spreadsheet, |sheet, input| {
+                if let Some(j) = journal.as_mut() {
+                    if let Err(e) = j.record(sheet, input) {
+                        save_error = Some(e);
+                    }
+                }
+            });
+            match (result, save_error) {
+                (Err(e), _) => eprintln!("Error: terminal UI failed: {}", e),
+                (Ok(()), Some(e)) => eprintln!("Warning: autosave failed: {}", e),
+                (Ok(()), None) => close_journal(journal),
+            }
+        }
+        #[cfg(not(feature = "tui"))]
+        eprintln!("Error: this binary was built without the `tui` feature");
+        return;
+    }
     spreadsheet.output_disabled = false;
 
     if !spreadsheet.output_disabled {
-        display::printer(
-            spreadsheet.curr_x,
-            spreadsheet.curry,
-            &spreadsheet.arr,
-            spreadsheet.cols,
-            spreadsheet.rows,
-        );
+        spreadsheet.print();
     }
 
     let global_start = Instant::now();
-    // Print prompt on same line
-    print!("[{:.1}] (ok) > ", global_start.elapsed().as_secs_f64());
-    io::stdout().flush().unwrap();
+    let mut reader = LineReader::new(&spreadsheet, history_path);
+    let mut prompt = format!("[{:.1}] (ok) > ", global_start.elapsed().as_secs_f64());
 
-    let mut input = String::new();
     loop {
-        input.clear();
-
-        if io::stdin().read_line(&mut input).is_err() {
-            eprintln!(
-                "[{:.1}] (Error reading input)",
-                global_start.elapsed().as_secs_f64()
-            );
-            break;
-        }
+        let input = match reader.read(&prompt) {
+            Ok(Some(input)) => input,
+            Ok(None) => break,
+            Err(_) => {
+                eprintln!(
+                    "[{:.1}] (Error reading input)",
+                    global_start.elapsed().as_secs_f64()
+                );
+                // keep the journal: the session did not end on purpose
+                return;
+            }
+        };
         let trimmed = input.trim();
         if trimmed.eq_ignore_ascii_case("q") {
             break;
@@ -86,19 +259,11 @@ fn main() {
 
         if trimmed == "disable_output" {
             spreadsheet.output_disabled = true;
-            print!("[{:.1}] (ok) > ", cmd_start.elapsed().as_secs_f64());
-            io::stdout().flush().unwrap();
+            prompt = format!("[{:.1}] (ok) > ", cmd_start.elapsed().as_secs_f64());
         } else if trimmed == "enable_output" {
             spreadsheet.output_disabled = false;
-            display::printer(
-                spreadsheet.curr_x,
-                spreadsheet.curry,
-                &spreadsheet.arr,
-                spreadsheet.cols,
-                spreadsheet.rows,
-            );
-            print!("[{:.1}] (ok) > ", cmd_start.elapsed().as_secs_f64());
-            io::stdout().flush().unwrap();
+            spreadsheet.print();
+            prompt = format!("[{:.1}] (ok) > ", cmd_start.elapsed().as_secs_f64());
         } else {
             if trimmed == "w"
                 || trimmed == "a"
@@ -108,26 +273,314 @@ fn main() {
             {
                 status = scrolling::scroller(trimmed, &mut spreadsheet);
             } else {
-                status = input_parser::parser(&mut spreadsheet, trimmed);
+                // Ctrl-C while the command runs cancels its evaluation
+                // instead of killing the program
+                let token = spreadsheet.graph.scheduler.cancel_token();
+                let interrupt =
+                    signal_hook::flag::register(signal_hook::consts::SIGINT, token.flag()).ok();
+                let result = command::execute(&mut spreadsheet, trimmed);
+                if let Some(id) = interrupt {
+                    signal_hook::low_level::unregister(id);
+                }
+                match result {
+                    Ok(outcome @ (Outcome::Edited(_) | Outcome::Configured)) => {
+                        if let Outcome::Edited(idx) = outcome {
+                            reader.note_edit(idx);
+                        }
+                        if let Some(j) = journal.as_mut() {
+                            if let Err(e) = j.record(&spreadsheet, trimmed) {
+                                eprintln!("Warning: autosave failed: {}", e);
+                            }
+                        }
+                    }
+                    Ok(Outcome::Output(text)) => println!("{}", text),
+                    Ok(Outcome::Done) => {}
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        status = 1;
+                    }
+                }
             }
 
             let elapsed = cmd_start.elapsed().as_secs_f64();
             if !spreadsheet.output_disabled {
-                display::printer(
-                    spreadsheet.curr_x,
-                    spreadsheet.curry,
-                    &spreadsheet.arr,
-                    spreadsheet.cols,
-                    spreadsheet.rows,
-                );
+                spreadsheet.print();
             }
-            // Print prompt without newline, then flush
             if status == 0 {
-                print!("[{:.1}] (ok) > ", elapsed);
+                prompt = format!("[{:.1}] (ok) > ", elapsed);
             } else {
-                print!("[{:.1}] (err) > ", elapsed);
+                prompt = format!("[{:.1}] (err) > ", elapsed);
             }
-            io::stdout().flush().unwrap();
+        }
+    }
+    close_journal(journal);
+}
+
+/// Delete the autosave of a session that ended normally, so the next start
+/// does not take it for a crashed one.
+#[cfg(feature = "autograder")]
+fn close_journal(journal: Option<lab1::journal::Journal>) {
+    if let Some(Err(e)) = journal.map(|j| j.close()) {
+        eprintln!("Warning: cannot remove the journal: {}", e);
+    }
+}
+
+/// Tab completion for the line editor, backed by
+/// [`Suggester`](lab1::completion::Suggester).
+#[cfg(feature = "autograder")]
+struct ReplHelper(lab1::completion::Suggester);
+
+#[cfg(feature = "autograder")]
+impl rustyline::completion::Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.0.complete(line, pos))
+    }
+}
+
+#[cfg(feature = "autograder")]
+impl rustyline::hint::Hinter for ReplHelper {
+    type Hint = String;
+}
+
+#[cfg(feature = "autograder")]
+impl rustyline::highlight::Highlighter for ReplHelper {}
+#[cfg(feature = "autograder")]
+impl rustyline::validate::Validator for ReplHelper {}
+#[cfg(feature = "autograder")]
+impl rustyline::Helper for ReplHelper {}
+
+/// Where the REPL reads its lines from: a line editor with history and tab
+/// completion when talking to a terminal, plain `read_line` otherwise so
+/// piped input and graders see exactly the classic prompt protocol.
+#[cfg(feature = "autograder")]
+struct LineReader {
+    editor: Option<rustyline::Editor<ReplHelper, rustyline::history::DefaultHistory>>,
+    history: Option<std::path::PathBuf>,
+}
+
+#[cfg(feature = "autograder")]
+impl LineReader {
+    fn new(
+        sheet: &lab1::spreadsheet::Spreadsheet,
+        history: Option<std::path::PathBuf>,
+    ) -> LineReader {
+        use std::io::IsTerminal;
+
+        let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+        let editor = if interactive {
+            rustyline::Editor::new().ok().map(|mut editor| {
+                editor.set_helper(Some(ReplHelper(lab1::completion::Suggester::new(sheet))));
+                if let Some(path) = &history {
+                    // a missing history file just means a first session
+                    let _ = editor.load_history(path);
+                }
+                editor
+            })
+        } else {
+            None
+        };
+        LineReader { editor, history }
+    }
+
+    /// Show `prompt` and read one line; `None` at end of input.
+    fn read(&mut self, prompt: &str) -> std::io::Result<Option<String>> {
+        use rustyline::error::ReadlineError;
+        use std::io::{self, Write};
+
+        let Some(editor) = self.editor.as_mut() else {
+            print!("{}", prompt);
+            io::stdout().flush()?;
+            let mut line = String::new();
+            return Ok(match io::stdin().read_line(&mut line)? {
+                0 => None,
+                _ => Some(line),
+            });
+        };
+        loop {
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.as_str());
+                        if let Some(path) = &self.history {
+                            if let Err(e) = editor.append_history(path) {
+                                eprintln!("Warning: cannot save history: {}", e);
+                                self.history = None;
+                            }
+                        }
+                    }
+                    return Ok(Some(line));
+                }
+                // Ctrl-C drops the current line, like a shell
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(None),
+                Err(ReadlineError::Io(e)) => return Err(e),
+                Err(e) => return Err(io::Error::other(e.to_string())),
+            }
+        }
+    }
+
+    /// Offer the edited cell in later completions.
+    fn note_edit(&mut self, index: usize) {
+        if let Some(helper) = self.editor.as_mut().and_then(|e| e.helper_mut()) {
+            helper.0.note_edit(index);
+        }
+    }
+}
+
+/// `spreadsheet run ...`: execute a script non-interactively and return the
+/// process exit status.
+#[cfg(feature = "autograder")]
+fn run_batch(args: &[String]) -> i32 {
+    use std::fs::File;
+    use std::io::{self, BufRead, BufReader};
+
+    use lab1::{command, script, spreadsheet};
+
+    let usage = "Usage: spreadsheet run [SCRIPT | -] [--rows N] [--cols N] [--keep-going] \
+                 [--print CELLS]... [--export csv|json|values]";
+    let mut source = None;
+    let mut rows = 100;
+    let mut cols = 100;
+    let mut keep_going = false;
+    let mut prints = Vec::new();
+    let mut export = None;
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--keep-going" => keep_going = true,
+            "--rows" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(n) if (1..=1000).contains(&n) => rows = n,
+                _ => {
+                    eprintln!("Error: Rows should be between 1 and 1000 inclusive");
+                    return 2;
+                }
+            },
+            "--cols" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(n) if (1..=18278).contains(&n) => cols = n,
+                _ => {
+                    eprintln!("Error: Cols should be between 1 and 18278 inclusive");
+                    return 2;
+                }
+            },
+            "--print" | "--export" => match args.next() {
+                Some(v) if arg == "--print" => prints.push(v.to_string()),
+                Some(v) => export = Some(v.to_string()),
+                None => {
+                    eprintln!("{}", usage);
+                    return 2;
+                }
+            },
+            _ if source.is_none() && (arg == "-" || !arg.starts_with("--")) => {
+                source = Some(arg.to_string())
+            }
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            }
+        }
+    }
+
+    let input: Box<dyn BufRead> = match source.as_deref() {
+        None | Some("-") => Box::new(BufReader::new(io::stdin())),
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Error: cannot open {}: {}", path, e);
+                return 2;
+            }
+        },
+    };
+
+    let mut sheet = spreadsheet::initialize_spreadsheet(rows, cols);
+    let mut stdout = io::stdout().lock();
+    let errors = match script::run_script(&mut sheet, input, &mut stdout, keep_going) {
+        Ok(errors) => errors,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
+    for e in &errors {
+        eprintln!("{}", e);
+    }
+
+    let mut failed = !errors.is_empty();
+    let requested = prints
+        .iter()
+        .map(|spec| command::print_cells(&sheet, spec))
+        .chain(export.iter().map(|format| command::export(&sheet, format)));
+    for result in requested {
+        match result {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                failed = true;
+            }
+        }
+    }
+    i32::from(failed)
+}
+
+/// `spreadsheet eval ...`: load a CSV, apply edits, print cells and return the
+/// process exit status.
+#[cfg(feature = "autograder")]
+fn run_eval(args: &[String]) -> i32 {
+    use lab1::query::{self, Format};
+
+    let usage = "Usage: spreadsheet eval [--input data.csv] [--set A1=EXPR]... \
+                 [--print CELLS]... [--format text|csv|json]";
+    let mut input = None;
+    let mut sets = Vec::new();
+    let mut prints = Vec::new();
+    let mut format = Format::Text;
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        let Some(value) = args.next() else {
+            eprintln!("{}", usage);
+            return 2;
+        };
+        match arg {
+            "--input" => input = Some(value),
+            "--set" => sets.push(value),
+            "--print" => prints.push(value),
+            "--format" => match value.parse() {
+                Ok(f) => format = f,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return 2;
+                }
+            },
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            }
+        }
+    }
+
+    let csv = match input.map(std::fs::read_to_string).transpose() {
+        Ok(csv) => csv,
+        Err(e) => {
+            eprintln!("Error: cannot read {}: {}", input.unwrap_or_default(), e);
+            return 2;
+        }
+    };
+    let result = query::evaluate(csv.as_deref(), &sets, &prints)
+        .and_then(|sheet| query::report(&sheet, &prints, format));
+    match result {
+        Ok(text) => {
+            println!("{}", text);
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
         }
     }
 }
@@ -0,0 +1,502 @@
+//! Module `command`.
+//!
+//! Executes one line of the terminal command language against a
+//! [`Spreadsheet`], independent of how the line was read or how the sheet is
+//! shown afterwards. Recognized lines:
+//!
+//! - `A1=EXPR`: a cell edit, applied through [`assign`]
+//! - `w`, `a`, `s`, `d`, `scroll_to A1`: move the viewport
+//! - `disable_output` / `enable_output`: toggle re-rendering
+//! - `set_view ROWS COLS`, `width B 20`, `scroll_step N`: change the
+//!   sheet's [`Viewport`](crate::view::Viewport) settings
+//! - `precision N` / `precision auto`: decimals shown for numbers
+//! - `iterate on [MAX [TOLERANCE]]` / `iterate off`: allow circular
+//!   references, evaluated iteratively (see [`iteration`](crate::iteration))
+//! - `timeout SECONDS` / `timeout off`: per-edit evaluation time budget
+//! - `numbers float` / `numbers int` / `numbers decimal SCALE [half-even|half-up]`:
+//!   compute numbers as `f64`, as exact `i64` or as fixed-point decimals with
+//!   `SCALE` decimals (see [`NumberMode`])
+//! - `print A1` or `print A1:C3`: show values (tab-separated rows for a range)
+//! - `export csv`, `export json`, `export values`: dump the whole sheet
+//! - `show B3`, `why B3`: inspect a cell's formula or the origin of its error
+//! - `deps B3`, `precs A1:B3`: direct dependents / precedents of a cell or
+//!   range
+//! - `graph dot` / `graph json [B3]`: the dependency graph, optionally only
+//!   the cone around one cell
+//! - empty lines and lines starting with `#` are ignored
+
+use std::mem;
+use std::time::Duration;
+
+use crate::csv::to_csv;
+use crate::decimal::{DecimalMode, Rounding};
+use crate::depgraph;
+use crate::edit::assign;
+use crate::graph::recalculate_all;
+use crate::input_parser::cell_parser;
+use crate::inspect::{cell_list, show, why};
+use crate::iteration::{Iteration, DEFAULT_MAX_ITERATIONS, DEFAULT_TOLERANCE};
+use crate::json::{export_cells, export_values};
+use crate::scrolling;
+use crate::spreadsheet::Spreadsheet;
+use crate::trace::{trace_dependents, trace_precedents, Depth};
+use crate::value::NumberMode;
+use crate::view::column_index;
+
+/// What an executed command did.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// An `A1=...` edit was accepted for the cell at this index.
+    Edited(usize),
+    /// A setting that changes what formulas evaluate to (`iterate` or
+    /// `numbers`) was applied.
+    Configured,
+    /// The command produced text to show the user.
+    Output(String),
+    /// The command only changed view state (or was a comment).
+    Done,
+}
+
+/// Parse `A1` or `A1:C3` into an inclusive `(row, col)` rectangle.
+pub fn parse_cells(
+    spec: &str,
+    sheet: &Spreadsheet,
+) -> Result<(usize, usize, usize, usize), String> {
+    let (from, to) = spec.split_once(':').unwrap_or((spec, spec));
+    let cell = |name: &str| {
+        let idx = cell_parser(name.trim(), sheet.cols as i32, sheet.rows as i32);
+        if idx == -1 {
+            Err(format!("invalid cell {}", name.trim()))
+        } else {
+            Ok((idx as usize / sheet.cols, idx as usize % sheet.cols))
+        }
+    };
+    let (r1, c1) = cell(from)?;
+    let (r2, c2) = cell(to)?;
+    Ok((r1.min(r2), c1.min(c2), r1.max(r2), c1.max(c2)))
+}
+
+/// Values of `spec` (a cell or range) as text: a single value, or one
+/// tab-separated line per row, shown with the view's precision.
+pub fn print_cells(sheet: &Spreadsheet, spec: &str) -> Result<String, String> {
+    let (r1, c1, r2, c2) = parse_cells(spec, sheet)?;
+    let lines: Vec<String> = (r1..=r2)
+        .map(|r| {
+            (c1..=c2)
+                .map(|c| sheet.arr[r * sheet.cols + c].format(sheet.view.precision))
+                .collect::<Vec<_>>()
+                .join("\t")
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// Indices of the cells in `spec` (a cell or range), row by row.
+pub fn cell_indices(spec: &str, sheet: &Spreadsheet) -> Result<Vec<usize>, String> {
+    let (r1, c1, r2, c2) = parse_cells(spec, sheet)?;
+    Ok((r1..=r2)
+        .flat_map(|r| (c1..=c2).map(move |c| r * sheet.cols + c))
+        .collect())
+}
+
+/// Index of the single cell named by `spec`.
+pub fn one_cell(spec: &str, sheet: &Spreadsheet) -> Result<usize, String> {
+    match parse_cells(spec, sheet)? {
+        (r1, c1, r2, c2) if r1 == r2 && c1 == c2 => Ok(r1 * sheet.cols + c1),
+        _ => Err(format!("expected a single cell, got {}", spec)),
+    }
+}
+
+/// Parse whitespace-separated numeric arguments, exactly `N` of them.
+fn numbers<const N: usize>(rest: &str, usage: &str) -> Result<[usize; N], String> {
+    let parsed: Vec<usize> = rest
+        .split_whitespace()
+        .map(|s| s.parse().map_err(|_| format!("usage: {}", usage)))
+        .collect::<Result<_, _>>()?;
+    parsed.try_into().map_err(|_| format!("usage: {}", usage))
+}
+
+/// Apply `iterate on [MAX [TOLERANCE]]` or `iterate off`.
+fn set_iteration(sheet: &mut Spreadsheet, rest: &str) -> Result<(), String> {
+    let usage = || "usage: iterate on [MAX [TOLERANCE]] | iterate off".to_string();
+    let args: Vec<&str> = rest.split_whitespace().collect();
+    match args.as_slice() {
+        ["off"] => sheet.graph.iteration = None,
+        ["on", more @ ..] if more.len() <= 2 => {
+            let max = match more.first() {
+                Some(n) => n.parse().map_err(|_| usage())?,
+                None => DEFAULT_MAX_ITERATIONS,
+            };
+            let tolerance = match more.get(1) {
+                Some(t) => t.parse().map_err(|_| usage())?,
+                None => DEFAULT_TOLERANCE,
+            };
+            sheet.graph.iteration = Some(Iteration::new(max, tolerance)?);
+        }
+        _ => return Err(usage()),
+    }
+    Ok(())
+}
+
+/// Apply `numbers float`, `numbers int` or `numbers decimal SCALE [ROUNDING]`
+/// and recompute the whole sheet in the new mode. If that recalculation is
+/// interrupted or meets a loop, the old mode is put back and recomputed.
+pub fn set_numbers(sheet: &mut Spreadsheet, mode: &str) -> Result<(), String> {
+    let usage = || {
+        "usage: numbers float | numbers int | numbers decimal SCALE [half-even|half-up]".to_string()
+    };
+    let words: Vec<&str> = mode.split_whitespace().collect();
+    let numbers = match words[..] {
+        ["float"] => NumberMode::Float,
+        ["int"] => NumberMode::Int,
+        ["decimal", scale, ref rounding @ ..] => {
+            let rounding = match rounding {
+                [] | ["half-even"] => Rounding::HalfEven,
+                ["half-up"] => Rounding::HalfUp,
+                _ => return Err(usage()),
+            };
+            let scale = scale.parse().map_err(|_| usage())?;
+            DecimalMode::new(scale, rounding)?.into()
+        }
+        _ => return Err(usage()),
+    };
+    let old = mem::replace(&mut sheet.graph.numbers, numbers);
+    let cols = sheet.cols as i32;
+    sheet.graph.scheduler.begin_edit();
+    let done = recalculate_all(&mut sheet.graph, cols, &mut sheet.arr, &sheet.formula_array);
+    let interrupt = sheet.graph.scheduler.end_edit();
+    if done {
+        return Ok(());
+    }
+    sheet.graph.numbers = old;
+    recalculate_all(&mut sheet.graph, cols, &mut sheet.arr, &sheet.formula_array);
+    match interrupt {
+        Some(interrupt) => Err(interrupt.to_string()),
+        None => Err(
+            "The sheet has a circular reference (see `iterate on`); numbers unchanged".to_string(),
+        ),
+    }
+}
+
+/// Parse a `timeout` argument: seconds, or `off` for no budget.
+pub fn parse_budget(arg: &str) -> Result<Option<Duration>, String> {
+    if arg == "off" {
+        return Ok(None);
+    }
+    match arg.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Some(Duration::from_secs_f64(secs))),
+        _ => Err("usage: timeout SECONDS | timeout off".to_string()),
+    }
+}
+
+/// Dump the whole sheet in `format` (`csv`, `json` or `values`).
+pub fn export(sheet: &Spreadsheet, format: &str) -> Result<String, String> {
+    match format {
+        "csv" => Ok(to_csv(sheet).trim_end().to_string()),
+        "json" => Ok(export_cells(sheet)),
+        "values" => Ok(export_values(sheet)),
+        _ => Err(format!("unknown export format {}", format)),
+    }
+}
+
+/// Execute one command line.
+///
+/// # Errors
+///
+/// Returns a message for unknown commands, invalid cells and rejected edits.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::command::{execute, Outcome};
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// assert_eq!(execute(&mut sheet, "B1=4"), Ok(Outcome::Edited(1)));
+/// assert_eq!(execute(&mut sheet, "print B1"), Ok(Outcome::Output("4".to_string())));
+/// assert!(execute(&mut sheet, "frobnicate").is_err());
+/// ```
+pub fn execute(sheet: &mut Spreadsheet, line: &str) -> Result<Outcome, String> {
+    let line = line.trim();
+    let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let rest = rest.trim();
+    match word {
+        "" => Ok(Outcome::Done),
+        _ if word.starts_with('#') => Ok(Outcome::Done),
+        "w" | "a" | "s" | "d" => {
+            scrolling::scroller(line, sheet);
+            Ok(Outcome::Done)
+        }
+        "scroll_to" => {
+            parse_cells(rest, sheet)?;
+            scrolling::scroller(&format!("scroll_to {}", rest), sheet);
+            Ok(Outcome::Done)
+        }
+        "disable_output" => {
+            sheet.output_disabled = true;
+            Ok(Outcome::Done)
+        }
+        "enable_output" => {
+            sheet.output_disabled = false;
+            Ok(Outcome::Done)
+        }
+        "set_view" => {
+            let [rows, cols] = numbers(rest, "set_view ROWS COLS")?;
+            sheet.view.set_size(rows, cols)?;
+            Ok(Outcome::Done)
+        }
+        "scroll_step" => {
+            let [step] = numbers(rest, "scroll_step N")?;
+            sheet.view.set_scroll_step(step)?;
+            Ok(Outcome::Done)
+        }
+        "precision" => {
+            let precision = match rest {
+                "auto" => None,
+                _ => {
+                    let [decimals] = numbers(rest, "precision N | precision auto")?;
+                    Some(decimals)
+                }
+            };
+            sheet.view.set_precision(precision)?;
+            Ok(Outcome::Done)
+        }
+        "width" => {
+            let usage = "usage: width COLUMN CHARS";
+            let (name, width) = rest.split_once(' ').ok_or(usage)?;
+            let col = column_index(name)
+                .filter(|&c| c < sheet.cols)
+                .ok_or(format!("invalid column {}", name))?;
+            let [width] = numbers(width, "width COLUMN CHARS")?;
+            sheet.view.set_width(col, width)?;
+            Ok(Outcome::Done)
+        }
+        "iterate" => {
+            set_iteration(sheet, rest)?;
+            Ok(Outcome::Configured)
+        }
+        "timeout" => {
+            sheet.graph.scheduler.set_budget(parse_budget(rest)?);
+            Ok(Outcome::Done)
+        }
+        "numbers" => {
+            set_numbers(sheet, rest)?;
+            Ok(Outcome::Configured)
+        }
+        "print" => print_cells(sheet, rest).map(Outcome::Output),
+        "export" => export(sheet, rest).map(Outcome::Output),
+        "show" => Ok(Outcome::Output(show(sheet, one_cell(rest, sheet)?))),
+        "deps" => {
+            let cells = trace_dependents(sheet, &cell_indices(rest, sheet)?, Depth::Direct);
+            Ok(Outcome::Output(cell_list(sheet, &cells)))
+        }
+        "precs" => {
+            let cells = trace_precedents(sheet, &cell_indices(rest, sheet)?, Depth::Direct);
+            Ok(Outcome::Output(cell_list(sheet, &cells)))
+        }
+        "graph" => {
+            let (format, cell) = rest.split_once(' ').unwrap_or((rest, ""));
+            let focus = match cell.trim() {
+                "" => None,
+                cell => Some(one_cell(cell, sheet)?),
+            };
+            depgraph::export(sheet, format, focus).map(Outcome::Output)
+        }
+        "why" => Ok(Outcome::Output(why(sheet, one_cell(rest, sheet)?))),
+        _ if line.contains('=') => assign(sheet, line).map(Outcome::Edited),
+        _ => Err(format!("unknown command: {}", line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spreadsheet::initialize_spreadsheet;
+    use crate::value::{Value, ERR, NUM};
+
+    #[test]
+    fn test_print_range() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        execute(&mut sheet, "A1=1").unwrap();
+        execute(&mut sheet, "B2=A1/0").unwrap();
+        assert_eq!(print_cells(&sheet, "B2:A1").unwrap(), "1\t0\n0\tERR");
+        assert!(print_cells(&sheet, "D1").is_err());
+    }
+
+    #[test]
+    fn test_view_commands() {
+        let mut sheet = initialize_spreadsheet(30, 30);
+        assert_eq!(execute(&mut sheet, "s"), Ok(Outcome::Done));
+        assert_eq!(sheet.curry, 10);
+        execute(&mut sheet, "scroll_to C5").unwrap();
+        assert_eq!((sheet.curry, sheet.curr_x), (4, 2));
+        assert!(execute(&mut sheet, "scroll_to ZZ99").is_err());
+        execute(&mut sheet, "disable_output").unwrap();
+        assert!(sheet.output_disabled);
+        assert_eq!(execute(&mut sheet, "# comment"), Ok(Outcome::Done));
+    }
+
+    #[test]
+    fn test_view_settings() {
+        let mut sheet = initialize_spreadsheet(30, 30);
+        execute(&mut sheet, "set_view 5 3").unwrap();
+        execute(&mut sheet, "width B 20").unwrap();
+        execute(&mut sheet, "scroll_step 2").unwrap();
+        assert_eq!((sheet.view.rows, sheet.view.cols), (5, 3));
+        assert_eq!(sheet.view.width(1), 20);
+        execute(&mut sheet, "s").unwrap();
+        assert_eq!(sheet.curry, 2);
+        assert!(execute(&mut sheet, "set_view 5").is_err());
+        assert!(execute(&mut sheet, "set_view 0 5").is_err());
+        assert!(execute(&mut sheet, "width AE 4").is_err());
+        assert!(execute(&mut sheet, "width B wide").is_err());
+        assert!(execute(&mut sheet, "scroll_step").is_err());
+        execute(&mut sheet, "precision 3").unwrap();
+        assert_eq!(sheet.view.precision, Some(3));
+        execute(&mut sheet, "precision auto").unwrap();
+        assert_eq!(sheet.view.precision, None);
+        assert!(execute(&mut sheet, "precision 99").is_err());
+        assert!(execute(&mut sheet, "precision some").is_err());
+    }
+
+    #[test]
+    fn test_numbers_mode() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        execute(&mut sheet, "A1=2147483647+1").unwrap();
+        execute(&mut sheet, "B1=A1*2147483647").unwrap();
+        execute(&mut sheet, "A2=B1*4").unwrap();
+        // a float holds B1 only to about 16 digits
+        assert_eq!(
+            print_cells(&sheet, "A1:B1").unwrap(),
+            "2147483648\t4611686016279904000"
+        );
+        execute(&mut sheet, "numbers int").unwrap();
+        assert_eq!(
+            print_cells(&sheet, "A1:B1").unwrap(),
+            "2147483648\t4611686016279904256"
+        );
+        assert_eq!(sheet.arr[2], NUM);
+        assert_eq!(print_cells(&sheet, "A2").unwrap(), "#NUM!");
+        execute(&mut sheet, "A1=3").unwrap();
+        assert_eq!(print_cells(&sheet, "B1").unwrap(), "6442450941");
+        assert_eq!(print_cells(&sheet, "A2").unwrap(), "25769803764");
+        execute(&mut sheet, "numbers float").unwrap();
+        assert!(matches!(sheet.arr[1], Value::Number(_)));
+        assert!(execute(&mut sheet, "numbers big").is_err());
+    }
+
+    #[test]
+    fn test_decimal_numbers_mode() {
+        let mut sheet = initialize_spreadsheet(3, 2);
+        execute(&mut sheet, "A1=0.1").unwrap();
+        execute(&mut sheet, "B1=0.2").unwrap();
+        execute(&mut sheet, "A2=1.005").unwrap();
+        execute(&mut sheet, "B2=A1+B1").unwrap();
+        execute(&mut sheet, "A3=SUM(A1:B1)").unwrap();
+        assert_ne!(sheet.arr[3], 0.3);
+        execute(&mut sheet, "numbers decimal 2").unwrap();
+        assert_eq!(
+            print_cells(&sheet, "A2:B3").unwrap(),
+            "1.00\t0.30\n0.30\t0.00"
+        );
+        assert_eq!(sheet.arr[3], 0.3);
+        execute(&mut sheet, "numbers decimal 2 half-up").unwrap();
+        assert_eq!(print_cells(&sheet, "A2").unwrap(), "1.01");
+        execute(&mut sheet, "B3=B2/3").unwrap();
+        assert_eq!(print_cells(&sheet, "B3").unwrap(), "0.10");
+        execute(&mut sheet, "precision 1").unwrap();
+        assert_eq!(print_cells(&sheet, "A2").unwrap(), "1.0");
+        assert!(execute(&mut sheet, "numbers decimal 10").is_err());
+        assert!(execute(&mut sheet, "numbers decimal 2 down").is_err());
+        assert!(execute(&mut sheet, "numbers decimal").is_err());
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let mut sheet = initialize_spreadsheet(1, 2);
+        execute(&mut sheet, "A1=2").unwrap();
+        assert_eq!(export(&sheet, "csv").unwrap(), "2,0");
+        assert_eq!(export(&sheet, "values").unwrap(), "[[2,0]]");
+        assert!(export(&sheet, "json").unwrap().contains("\"A1\""));
+        assert!(execute(&mut sheet, "export xml").is_err());
+    }
+
+    #[test]
+    fn test_inspection_commands() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        for input in ["A1=5", "B1=A1/0", "C1=B1+A1", "A2=A1*2"] {
+            execute(&mut sheet, input).unwrap();
+        }
+        let output = |sheet: &mut Spreadsheet, line| match execute(sheet, line) {
+            Ok(Outcome::Output(text)) => text,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(output(&mut sheet, "show A2"), "A2: A1*2 = 10");
+        assert_eq!(output(&mut sheet, "deps A1"), "B1, C1, A2");
+        assert_eq!(output(&mut sheet, "precs C1"), "A1, B1");
+        assert_eq!(output(&mut sheet, "deps C1"), "none");
+        assert_eq!(output(&mut sheet, "precs B1:C1"), "A1");
+        assert_eq!(
+            output(&mut sheet, "why C1"),
+            "C1 is ERR\nC1 ← B1: divides by zero"
+        );
+        assert!(execute(&mut sheet, "show A1:B2").is_err());
+        assert!(execute(&mut sheet, "why").is_err());
+        assert!(output(&mut sheet, "graph dot A2").contains("\"A1\" -> \"A2\""));
+        assert!(execute(&mut sheet, "graph png").is_err());
+    }
+
+    #[test]
+    fn test_iterate_allows_loops() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        execute(&mut sheet, "A1=100").unwrap();
+        execute(&mut sheet, "B1=C1/2").unwrap();
+        assert!(execute(&mut sheet, "C1=B1+A1").is_err());
+        execute(&mut sheet, "iterate on 200 0").unwrap();
+        execute(&mut sheet, "C1=B1+A1").unwrap();
+        assert_eq!(sheet.arr[1..=2], [Value::from(100), Value::from(200)]);
+        // edits upstream of the loop re-run it
+        execute(&mut sheet, "A1=10").unwrap();
+        assert_eq!(sheet.arr[1..=2], [Value::from(10), Value::from(20)]);
+
+        // a loop that never settles is an error value, not a rejected edit
+        execute(&mut sheet, "iterate on 10").unwrap();
+        execute(&mut sheet, "A2=A2+1").unwrap();
+        execute(&mut sheet, "B2=A2*2").unwrap();
+        assert_eq!(sheet.arr[3..=4], [ERR, ERR]);
+
+        execute(&mut sheet, "iterate off").unwrap();
+        assert!(sheet.graph.iteration.is_none());
+        assert!(execute(&mut sheet, "A1=5").is_err());
+        // without iteration the loop cannot be recomputed in another mode
+        let err = execute(&mut sheet, "numbers int").unwrap_err();
+        assert!(err.contains("circular reference"), "{}", err);
+        assert_eq!(sheet.graph.numbers, NumberMode::Float);
+        assert_eq!(sheet.arr[1..=2], [Value::from(10), Value::from(20)]);
+        assert!(matches!(sheet.arr[0], Value::Number(_)));
+        assert!(execute(&mut sheet, "iterate on 0").is_err());
+        assert!(execute(&mut sheet, "iterate on 5 -1").is_err());
+        assert!(execute(&mut sheet, "iterate maybe").is_err());
+    }
+
+    #[test]
+    fn test_timeout_setting() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        execute(&mut sheet, "timeout 1.5").unwrap();
+        assert_eq!(
+            sheet.graph.scheduler.budget(),
+            Some(Duration::from_millis(1500))
+        );
+        execute(&mut sheet, "timeout off").unwrap();
+        assert_eq!(sheet.graph.scheduler.budget(), None);
+        assert!(execute(&mut sheet, "timeout 0").is_err());
+        assert!(execute(&mut sheet, "timeout soon").is_err());
+    }
+
+    #[test]
+    fn test_rejected_edit() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        assert!(execute(&mut sheet, "A1=A1+1").is_err());
+        assert!(execute(&mut sheet, "A1=").is_err());
+    }
+}
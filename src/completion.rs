@@ -0,0 +1,303 @@
+//! Module `completion`.
+//!
+//! Tab-completion candidates for the terminal REPL: command names at the
+//! start of a line, function names inside a formula, export formats after
+//! `export`, and the names of cells that already hold something wherever a
+//! cell reference fits. The line editor itself lives in the binary; this
+//! module only decides what to offer.
+
+use std::collections::BTreeSet;
+
+use crate::json::cell_name;
+use crate::spreadsheet::Spreadsheet;
+
+/// Commands understood at the start of a REPL line.
+pub const COMMANDS: &[&str] = &[
+    "deps",
+    "disable_output",
+    "enable_output",
+    "export",
+    "graph",
+    "iterate",
+    "numbers",
+    "precision",
+    "precs",
+    "print",
+    "q",
+    "scroll_step",
+    "scroll_to",
+    "set_view",
+    "show",
+    "timeout",
+    "why",
+    "width",
+];
+
+/// Functions usable in formulas.
+pub const FUNCTIONS: &[&str] = &[
+    "ABS",
+    "ACOS",
+    "AND",
+    "ASIN",
+    "ATAN",
+    "ATAN2",
+    "AVERAGEIF",
+    "AVERAGEIFS",
+    "AVG",
+    "CONCAT",
+    "COS",
+    "COUNT",
+    "COUNTA",
+    "COUNTBLANK",
+    "COUNTIF",
+    "COUNTIFS",
+    "DATE",
+    "DATEDIF",
+    "DAY",
+    "DEGREES",
+    "EDATE",
+    "EOMONTH",
+    "EXP",
+    "FIND",
+    "HLOOKUP",
+    "IF",
+    "IFERROR",
+    "INDEX",
+    "INDIRECT",
+    "INT",
+    "ISBLANK",
+    "ISERROR",
+    "LEFT",
+    "LEN",
+    "LN",
+    "LOG10",
+    "LOWER",
+    "MATCH",
+    "MAX",
+    "MID",
+    "MIN",
+    "MOD",
+    "MONTH",
+    "NETWORKDAYS",
+    "NOT",
+    "NOW",
+    "OFFSET",
+    "OR",
+    "PI",
+    "POWER",
+    "RADIANS",
+    "RIGHT",
+    "ROUND",
+    "ROUNDDOWN",
+    "ROUNDUP",
+    "SIGN",
+    "SIN",
+    "SLEEP",
+    "SQRT",
+    "STDEV",
+    "SUBSTITUTE",
+    "SUM",
+    "SUMIF",
+    "SUMIFS",
+    "TAN",
+    "TEXT",
+    "TODAY",
+    "TRIM",
+    "UPPER",
+    "VALUE",
+    "VLOOKUP",
+    "WEEKDAY",
+    "XLOOKUP",
+    "YEAR",
+];
+
+/// Arguments of `export`.
+const FORMATS: &[&str] = &["csv", "json", "values"];
+
+/// First argument of `graph`.
+const GRAPH_FORMATS: &[&str] = &["dot", "json"];
+
+/// Commands that take arguments; completing one adds the separating space.
+const ARG_COMMANDS: &[&str] = &[
+    "deps",
+    "export",
+    "graph",
+    "iterate",
+    "numbers",
+    "precision",
+    "precs",
+    "print",
+    "scroll_step",
+    "scroll_to",
+    "set_view",
+    "show",
+    "timeout",
+    "why",
+    "width",
+];
+
+/// Commands whose argument is a cell or range.
+const CELL_COMMANDS: &[&str] = &["deps", "precs", "print", "scroll_to", "show", "why"];
+
+/// Most candidates offered at once.
+const LIMIT: usize = 50;
+
+/// Completion state for one sheet: which cells are worth suggesting.
+#[derive(Debug, Default)]
+pub struct Suggester {
+    cols: usize,
+    used: BTreeSet<usize>,
+}
+
+impl Suggester {
+    /// Index the cells of `sheet` that hold a formula or value.
+    pub fn new(sheet: &Spreadsheet) -> Suggester {
+        Suggester {
+            cols: sheet.cols,
+            used: sheet.formula_strings.keys().copied().collect(),
+        }
+    }
+
+    /// Remember that the cell at `index` was just edited.
+    pub fn note_edit(&mut self, index: usize) {
+        self.used.insert(index);
+    }
+
+    fn cells(&self, prefix: &str) -> impl Iterator<Item = String> + '_ {
+        let prefix = prefix.to_ascii_uppercase();
+        self.used
+            .iter()
+            .map(|&i| cell_name(i, self.cols))
+            .filter(move |name| !prefix.is_empty() && name.starts_with(&prefix))
+    }
+
+    /// Candidates for completing `line` at byte offset `pos`: the offset where
+    /// the word being completed starts, and the sorted replacements for it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::completion::Suggester;
+    /// use lab1::edit::assign;
+    /// use lab1::spreadsheet::initialize_spreadsheet;
+    ///
+    /// let mut sheet = initialize_spreadsheet(20, 5);
+    /// assign(&mut sheet, "B12=4").unwrap();
+    /// let suggester = Suggester::new(&sheet);
+    /// assert_eq!(suggester.complete("scr", 3), (0, vec!["scroll_step ".to_string(), "scroll_to ".to_string()]));
+    /// assert_eq!(suggester.complete("A1=st", 5), (3, vec!["STDEV(".to_string()]));
+    /// assert_eq!(suggester.complete("A1=SUM(b1", 9), (7, vec!["B12".to_string()]));
+    /// ```
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let head = &line[..pos];
+        let start = head
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        let word = &head[start..];
+        let before = &head[..start];
+        let command = before.split_whitespace().next().unwrap_or("");
+
+        let mut found: BTreeSet<String> = BTreeSet::new();
+        if before.trim().is_empty() {
+            found.extend(COMMANDS.iter().filter(|c| c.starts_with(word)).map(|c| {
+                if ARG_COMMANDS.contains(c) {
+                    format!("{} ", c)
+                } else {
+                    c.to_string()
+                }
+            }));
+            found.extend(self.cells(word));
+        } else if before.contains('=') {
+            let upper = word.to_ascii_uppercase();
+            if !word.is_empty() {
+                found.extend(
+                    FUNCTIONS
+                        .iter()
+                        .filter(|f| f.starts_with(&upper))
+                        .map(|f| format!("{}(", f)),
+                );
+            }
+            found.extend(self.cells(word));
+        } else if command == "export" {
+            found.extend(
+                FORMATS
+                    .iter()
+                    .filter(|f| f.starts_with(word))
+                    .map(|f| f.to_string()),
+            );
+        } else if command == "graph" && before.split_whitespace().count() == 1 {
+            found.extend(
+                GRAPH_FORMATS
+                    .iter()
+                    .filter(|f| f.starts_with(word))
+                    .map(|f| format!("{} ", f)),
+            );
+        } else if CELL_COMMANDS.contains(&command) || command == "graph" {
+            found.extend(self.cells(word));
+        }
+        (start, found.into_iter().take(LIMIT).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::assign;
+    use crate::spreadsheet::initialize_spreadsheet;
+
+    fn suggester() -> Suggester {
+        let mut sheet = initialize_spreadsheet(30, 30);
+        for input in ["A1=1", "A2=2", "A10=3", "AB3=4"] {
+            assign(&mut sheet, input).unwrap();
+        }
+        Suggester::new(&sheet)
+    }
+
+    #[test]
+    fn test_line_start_offers_commands_and_cells() {
+        let s = suggester();
+        assert_eq!(s.complete("", 0).1.len(), COMMANDS.len());
+        assert_eq!(s.complete("p", 1).1, vec!["precision ", "precs ", "print "]);
+        assert_eq!(s.complete("a", 1).1, vec!["A1", "A10", "A2", "AB3"]);
+        assert_eq!(s.complete("dis", 3).1, vec!["disable_output"]);
+    }
+
+    #[test]
+    fn test_arguments() {
+        let mut s = suggester();
+        assert_eq!(s.complete("export j", 8), (7, vec!["json".to_string()]));
+        assert_eq!(s.complete("print A1:A1", 11).1, vec!["A1", "A10"]);
+        assert_eq!(s.complete("print A1:A1", 11).0, 9);
+        assert!(s.complete("width ", 6).1.is_empty());
+        assert_eq!(s.complete("graph d", 7).1, vec!["dot "]);
+        assert_eq!(s.complete("graph dot A1", 12).1, vec!["A1", "A10"]);
+        s.note_edit(25);
+        assert_eq!(s.complete("scroll_to Z", 11).1, vec!["Z1"]);
+    }
+
+    #[test]
+    fn test_formula_context() {
+        let s = suggester();
+        assert_eq!(s.complete("B1=ma", 5).1, vec!["MATCH(", "MAX("]);
+        assert!(s.complete("B1=", 3).1.is_empty());
+        // completes the word under the cursor, not the end of the line
+        assert_eq!(
+            s.complete("B1=A+1", 4).1,
+            vec![
+                "A1",
+                "A10",
+                "A2",
+                "AB3",
+                "ABS(",
+                "ACOS(",
+                "AND(",
+                "ASIN(",
+                "ATAN(",
+                "ATAN2(",
+                "AVERAGEIF(",
+                "AVERAGEIFS(",
+                "AVG("
+            ]
+        );
+    }
+}
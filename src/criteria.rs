@@ -0,0 +1,203 @@
+//! Module `criteria`.
+//!
+//! The conditions of `COUNTIF`, `SUMIF`, `AVERAGEIF` and their `*IFS`
+//! variants, written the way spreadsheets write them: `">10"`, `"<>0"`,
+//! `"<=2.5"`, `"=TRUE"`, `">=2024-01-01"`, or just `"5"` (or the number 5,
+//! or a cell holding it) to match a value exactly. A date is a number here.
+//!
+//! Any other text, such as `"apples"` or `">=M"`, matches text cells, ignoring
+//! case. With `=` (or no operator) and `<>` it may hold wildcards: `*` for any
+//! run of characters, `?` for any one, and `~*` or `~?` for the character
+//! itself.
+//!
+//! A criterion only matches values of its own kind: `">0"` counts numbers,
+//! not booleans, texts or errors. An empty cell matches `"="` (or `""`) and
+//! every `"<>"` criterion, and nothing else, so `"<>"` on its own counts the
+//! cells that are not empty.
+
+use crate::date;
+use crate::expr::Cmp;
+use crate::value::Value;
+
+/// A parsed criterion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Criterion {
+    op: Cmp,
+    operand: Operand,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    /// Nothing after the operator: compares emptiness.
+    Blank,
+    Value(Value),
+    /// Anything that is neither a number nor a boolean, in lower case.
+    Text(String),
+}
+
+impl Criterion {
+    /// Parse criterion text such as `">=10"`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::criteria::Criterion;
+    /// use lab1::value::{Value, ERR};
+    ///
+    /// let big = Criterion::parse(">10");
+    /// assert!(big.matches(&Value::from(11), false));
+    /// assert!(!big.matches(&Value::from(10), false));
+    /// assert!(!big.matches(&ERR, false));
+    /// assert!(Criterion::parse("<>").matches(&Value::from(0), false));
+    /// assert!(Criterion::parse("=").matches(&Value::from(0), true));
+    /// ```
+    pub fn parse(text: &str) -> Criterion {
+        let (op, rest) = [
+            ("<>", Cmp::Ne),
+            ("<=", Cmp::Le),
+            (">=", Cmp::Ge),
+            ("=", Cmp::Eq),
+            ("<", Cmp::Lt),
+            (">", Cmp::Gt),
+        ]
+        .into_iter()
+        .find_map(|(token, op)| text.strip_prefix(token).map(|rest| (op, rest)))
+        .unwrap_or((Cmp::Eq, text));
+        let rest = rest.trim();
+        let number = rest
+            .parse::<f64>()
+            .ok()
+            .filter(|x| x.is_finite())
+            .or_else(|| date::parse(rest));
+        let operand = match number {
+            _ if rest.is_empty() => Operand::Blank,
+            Some(x) => Operand::Value(Value::Number(x)),
+            _ if rest.eq_ignore_ascii_case("TRUE") => Operand::Value(Value::Bool(true)),
+            _ if rest.eq_ignore_ascii_case("FALSE") => Operand::Value(Value::Bool(false)),
+            _ => Operand::Text(rest.to_lowercase()),
+        };
+        Criterion { op, operand }
+    }
+
+    /// The criterion "equal to `v`"; text is parsed as criterion text.
+    pub fn equal(v: Value) -> Criterion {
+        match v {
+            Value::Text(text) => Criterion::parse(&text),
+            v => Criterion {
+                op: Cmp::Eq,
+                operand: Operand::Value(v),
+            },
+        }
+    }
+
+    /// Whether a cell holding `v` (and empty if `blank`) meets this
+    /// criterion.
+    pub fn matches(&self, v: &Value, blank: bool) -> bool {
+        match &self.operand {
+            Operand::Blank => match self.op {
+                Cmp::Eq => blank,
+                Cmp::Ne => !blank,
+                _ => false,
+            },
+            _ if blank => self.op == Cmp::Ne,
+            Operand::Value(x) => {
+                if v.is_error() || !v.same_kind(x) {
+                    return self.op == Cmp::Ne;
+                }
+                self.op.holds(v, x)
+            }
+            Operand::Text(pattern) => {
+                let Value::Text(text) = v else {
+                    return self.op == Cmp::Ne;
+                };
+                let text = text.to_lowercase();
+                let chars = |s: &str| s.chars().collect::<Vec<char>>();
+                match self.op {
+                    Cmp::Eq => wildcard(&chars(pattern), &chars(&text)),
+                    Cmp::Ne => !wildcard(&chars(pattern), &chars(&text)),
+                    op => op.orders(Some(text.as_str().cmp(pattern))),
+                }
+            }
+        }
+    }
+}
+
+/// Whether `text` matches `pattern`, in which `*` stands for any run of
+/// characters, `?` for any one and `~` makes the next character literal.
+fn wildcard(pattern: &[char], text: &[char]) -> bool {
+    // each pattern position as (character, whether it is a wildcard)
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.iter();
+    while let Some(&c) = chars.next() {
+        tokens.push(match c {
+            '~' => chars.next().map_or(('~', false), |&next| (next, false)),
+            '*' | '?' => (c, true),
+            _ => (c, false),
+        });
+    }
+    // on a mismatch, let the last `*` swallow one more character
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(&('*', true)) => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&(c, wild)) if (wild && c == '?') || (!wild && c == text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    star = Some((sp, st + 1));
+                    p = sp + 1;
+                    t = st + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[p..].iter().all(|&token| token == ('*', true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ERR;
+
+    #[test]
+    fn test_operators_and_kinds() {
+        let n = |x: f64| Value::from(x);
+        assert!(Criterion::parse("5").matches(&Value::Int(5), false));
+        assert!(Criterion::parse("=5").matches(&n(5.0), false));
+        assert!(Criterion::parse("<=2.5").matches(&n(2.5), false));
+        assert!(Criterion::parse(">= -1").matches(&n(-1.0), false));
+        assert!(Criterion::parse("<>5").matches(&ERR, false));
+        assert!(Criterion::parse("<>5").matches(&n(0.0), true));
+        assert!(!Criterion::parse("<5").matches(&n(0.0), true));
+        assert!(Criterion::parse("true").matches(&Value::Bool(true), false));
+        assert!(!Criterion::parse("1").matches(&Value::Bool(true), false));
+        assert!(!Criterion::parse("apples").matches(&n(1.0), false));
+        assert!(Criterion::equal(n(2.0)).matches(&Value::Int(2), false));
+        let may_day = Value::Date(45413.0);
+        assert!(Criterion::parse(">=2024-05-01").matches(&may_day, false));
+        assert!(!Criterion::parse("<2024-05-01").matches(&may_day, false));
+    }
+
+    #[test]
+    fn test_text_and_wildcards() {
+        let t = Value::text;
+        assert!(Criterion::parse("apples").matches(&t("Apples"), false));
+        assert!(!Criterion::parse("apples").matches(&t("apple"), false));
+        assert!(Criterion::parse("a*").matches(&t("Avocado"), false));
+        assert!(Criterion::parse("<>?ig").matches(&t("figs"), false));
+        assert!(!Criterion::parse("<>?ig").matches(&t("Fig"), false));
+        assert!(Criterion::parse("*~?").matches(&t("why?"), false));
+        assert!(!Criterion::parse("*~?").matches(&t("why"), false));
+        assert!(Criterion::parse(">=m").matches(&t("Pear"), false));
+        assert!(!Criterion::parse(">=m").matches(&Value::from(99), false));
+        assert!(!Criterion::parse("5").matches(&t("5"), false));
+        assert!(Criterion::equal(t(">1")).matches(&Value::from(2), false));
+    }
+}
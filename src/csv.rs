@@ -0,0 +1,183 @@
+//! Module `csv`.
+//!
+//! Comma-separated export of computed cell values, one line per row, with
+//! `ERR`, `#NUM!` or `#N/A` for error cells and text quoted where it holds
+//! a comma, quote or line break, and import of CSV files whose fields are
+//! numbers, dates such as `2024-05-01`, `=FORMULA` right-hand sides, text,
+//! or empty.
+
+use crate::date;
+use crate::display::column_index_to_name;
+use crate::edit::assign;
+use crate::spreadsheet::{initialize_spreadsheet, Spreadsheet, MAX_COLS, MAX_ROWS};
+use crate::value::{decimal_parts, Value};
+
+/// Text of a single cell value as shown to users: the number (see
+/// [`Value::format`]), the text, or the error, e.g. `ERR`.
+pub fn value_text(v: &Value) -> String {
+    v.format(None)
+}
+
+/// Export all cell values of `sheet` as CSV.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::csv::to_csv;
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(2, 2);
+/// assign(&mut sheet, "A1=3").unwrap();
+/// assign(&mut sheet, "B2=A1/0").unwrap();
+/// assert_eq!(to_csv(&sheet), "3,0\n0,ERR\n");
+/// ```
+pub fn to_csv(sheet: &Spreadsheet) -> String {
+    let mut csv_data = String::new();
+    for row in 0..sheet.rows {
+        let row_data: Vec<String> = (0..sheet.cols)
+            .map(|col| quote(&value_text(&sheet.arr[row * sheet.cols + col])))
+            .collect();
+        csv_data.push_str(&row_data.join(","));
+        csv_data.push('\n');
+    }
+    csv_data
+}
+
+/// `field` as a CSV field: in double quotes, with `""` for each quote, if
+/// it holds a comma, quote or line break.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Split one CSV line into fields, honouring double-quoted fields with `""`
+/// escapes.
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(ch),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Build a sheet from CSV text, at least `rows` × `cols` large and big enough
+/// for every field.
+///
+/// Fields holding a number or a date become constants, fields starting
+/// with `=` become formulas (so a CSV may refer to its own cells) and any
+/// others text cells; empty fields stay empty.
+///
+/// # Errors
+///
+/// Returns a message naming the cell for a rejected formula, or if the sheet
+/// would be larger than [`MAX_ROWS`] × [`MAX_COLS`].
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::csv::from_csv;
+///
+/// let sheet = from_csv("1,2\n3,=A1/B1\nname,\n", 0, 0).unwrap();
+/// assert_eq!((sheet.rows, sheet.cols), (3, 2));
+/// assert_eq!(&sheet.arr[..4], &[1.0, 2.0, 3.0, 0.5]);
+/// assert_eq!(sheet.arr[4].to_string(), "name");
+/// ```
+pub fn from_csv(text: &str, rows: usize, cols: usize) -> Result<Spreadsheet, String> {
+    let grid: Vec<Vec<String>> = text.lines().map(split_fields).collect();
+    let rows = rows.max(grid.len()).max(1);
+    let cols = grid.iter().map(Vec::len).fold(cols, usize::max).max(1);
+    if rows > MAX_ROWS || cols > MAX_COLS {
+        return Err(format!(
+            "CSV needs {} rows × {} columns; a sheet has at most {} × {}",
+            rows, cols, MAX_ROWS, MAX_COLS
+        ));
+    }
+    let mut sheet = initialize_spreadsheet(rows, cols);
+    for (r, fields) in grid.iter().enumerate() {
+        for (c, field) in fields.iter().enumerate() {
+            let field = field.trim();
+            if field.is_empty() {
+                continue;
+            }
+            let name = format!("{}{}", column_index_to_name(c), r + 1);
+            let rhs = match field.strip_prefix('=') {
+                Some(formula) => formula.to_string(),
+                None if decimal_parts(field.strip_prefix('-').unwrap_or(field)).is_some() => {
+                    field.to_string()
+                }
+                None if date::parse(field).is_some() => format!("DATE(\"{}\")", field),
+                None => format!("'{}", field),
+            };
+            assign(&mut sheet, &format!("{}={}", name, rhs))
+                .map_err(|e| format!("{}: {}", name, e))?;
+        }
+    }
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_fields_quoted() {
+        assert_eq!(
+            split_fields(r#"1,"=SUM(A1:A2)","a""b",,"#),
+            vec!["1", "=SUM(A1:A2)", "a\"b", "", ""]
+        );
+    }
+
+    #[test]
+    fn test_from_csv_ragged_and_padded() {
+        let sheet = from_csv("5\n1,2,3\n", 4, 2).unwrap();
+        assert_eq!((sheet.rows, sheet.cols), (4, 3));
+        assert_eq!(&sheet.arr[..6], &[5, 0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_decimal_fields_round_trip() {
+        let sheet = from_csv("2.5,-0.75,=A1/3\n", 0, 0).unwrap();
+        assert_eq!(to_csv(&sheet), "2.5,-0.75,0.833333\n");
+    }
+
+    #[test]
+    fn test_date_fields_round_trip() {
+        let sheet = from_csv("2024-05-01,=A1+30\n2024-05-01 09:30,=B1-A1\n", 0, 0).unwrap();
+        assert!(matches!(sheet.arr[0], Value::Date(_)));
+        assert_eq!(
+            to_csv(&sheet),
+            "2024-05-01,2024-05-31\n2024-05-01 09:30,30\n"
+        );
+    }
+
+    #[test]
+    fn test_text_fields_round_trip() {
+        let sheet = from_csv("1,x\n\"Smith, J\",\"say \"\"hi\"\"\"\n", 0, 0).unwrap();
+        assert_eq!(sheet.arr[1].to_string(), "x");
+        assert_eq!(to_csv(&sheet), "1,x\n\"Smith, J\",\"say \"\"hi\"\"\"\n");
+    }
+
+    #[test]
+    fn test_from_csv_errors() {
+        assert!(from_csv("=A1+1\n", 0, 0).err().unwrap().starts_with("A1:"));
+        assert!(from_csv(&",".repeat(MAX_COLS), 0, 0).is_err());
+        assert!(from_csv(&"1\n".repeat(MAX_ROWS + 1), 0, 0).is_err());
+        assert!(from_csv("", MAX_ROWS, MAX_COLS + 1).is_err());
+    }
+}
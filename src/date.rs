@@ -0,0 +1,577 @@
+//! Module `date`.
+//!
+//! Dates and times as serial numbers: whole days since 1899-12-30, so
+//! 2024-05-01 is 45413, with the time of day as the fraction (6 pm is .75).
+//! These are the serials other spreadsheets use from March 1900 on.
+//!
+//! A date is a number in arithmetic and in range functions, except that
+//! adding days to a date or taking them away gives a date again, and the
+//! earliest or latest of some dates is a date. A date is shown as
+//! `2024-05-01`, or `2024-05-01 09:30` when it has a time (see [`format`]).
+//!
+//! `DATE("2024-05-01")` or `DATE("2024-05-01 09:30:15")` enters a date
+//! written as text, like `DATE(2024, 5, 1)` does from its parts; a bare
+//! `2024-05-01` in a formula is a subtraction. `YEAR`, `MONTH`, `DAY`,
+//! `EDATE`, `EOMONTH`, `DATEDIF`, `NETWORKDAYS` and `WEEKDAY` also read dates
+//! written as text. `TODAY()` and `NOW()` read the calendar time of the
+//! sheet's [`Clock`](crate::schedule::Clock), in UTC, when their cell is
+//! computed.
+
+use std::time::Duration;
+
+use crate::text::parse_number;
+use crate::value::{NumberMode, Value, ERR, NUM};
+
+/// Serial of 1970-01-01, where Unix time starts.
+const UNIX_EPOCH: i64 = 25569;
+/// Serial of 9999-12-31, the last date.
+pub const MAX_SERIAL: f64 = 2958465.0;
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// A date function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Date {
+    /// `DATE(year, month, day)`, where months and days past the end carry
+    /// over, or `DATE(text)` for a date written as `2024-05-01 09:30`.
+    Date,
+    Year,
+    Month,
+    Day,
+    /// The same day some months later or earlier, kept within the month.
+    EDate,
+    /// The last day of the month some months later or earlier.
+    EoMonth,
+    /// `DATEDIF(start, end, unit)`: whole `"Y"`, `"M"` or `"D"` between two
+    /// dates, or the days (`"MD"`, `"YD"`) or months (`"YM"`) left over.
+    DateDif,
+    /// Monday to Friday from start to end, both included, less holidays.
+    NetworkDays,
+    /// The day of the week: Sunday is 1 (type 1), Monday is 1 (type 2) or
+    /// Monday is 0 (type 3).
+    Weekday,
+    Today,
+    Now,
+}
+
+/// `f` applied to `args`, whose count the parser has checked, on a sheet
+/// of mode `numbers` at date and time `now`. The first error among the
+/// arguments is passed on; a date before 1899-12-30 or after 9999-12-31 is
+/// [`NUM`] and any other bad argument [`ERR`].
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::date::{apply, Date};
+/// use lab1::value::{NumberMode, Value, ERR};
+///
+/// let float = NumberMode::Float;
+/// let day = apply(Date::Date, &[2024, 1, 31].map(Value::from), float, 0.0);
+/// assert_eq!(day.to_string(), "2024-01-31");
+/// assert_eq!(apply(Date::EDate, &[day, Value::from(1)], float, 0.0).to_string(), "2024-02-29");
+/// assert_eq!(apply(Date::Month, &[Value::text("2024-05-01")], float, 0.0), 5);
+/// assert_eq!(apply(Date::Year, &[Value::text("May 1")], float, 0.0), ERR);
+/// ```
+pub fn apply(f: Date, args: &[Value], numbers: NumberMode, now: f64) -> Value {
+    if let Some(e) = args.iter().find(|v| v.is_error()) {
+        return e.clone();
+    }
+    compute(f, args, numbers, now).unwrap_or(ERR)
+}
+
+fn compute(f: Date, args: &[Value], numbers: NumberMode, now: f64) -> Option<Value> {
+    let whole = |n: i64| Some(numbers.whole(n));
+    let result = match f {
+        Date::Date if args.len() == 1 => match &args[0] {
+            Value::Text(t) => parse(t)?,
+            v => v.number()?,
+        },
+        Date::Date if args.len() != 3 => return None,
+        Date::Date => {
+            let year = count(&args[0])?;
+            let year = if (0..1900).contains(&year) {
+                year + 1900
+            } else {
+                year
+            };
+            if !(0..=9999).contains(&year) {
+                return Some(NUM);
+            }
+            serial(year, count(&args[1])?, count(&args[2])?)
+        }
+        Date::Year => return whole(ymd(day(&args[0])?).0),
+        Date::Month => return whole(ymd(day(&args[0])?).1 as i64),
+        Date::Day => return whole(ymd(day(&args[0])?).2 as i64),
+        Date::EDate | Date::EoMonth => {
+            let (y, m, d) = ymd(day(&args[0])?);
+            let m = m as i64 + count(&args[1])?;
+            let last = serial(y, m + 1, 0);
+            match f {
+                Date::EDate => serial(y, m, d as i64).min(last),
+                _ => last,
+            }
+        }
+        Date::DateDif => return date_dif(args, numbers),
+        Date::NetworkDays => return network_days(args, numbers),
+        Date::Weekday => {
+            // Sunday is 0
+            let w = (day(&args[0])? as i64 + 6).rem_euclid(7);
+            return whole(match args.get(1).map_or(Some(1), count)? {
+                1 => w + 1,
+                2 => (w + 6) % 7 + 1,
+                3 => (w + 6) % 7,
+                _ => return None,
+            });
+        }
+        Date::Today => now.floor(),
+        Date::Now => now,
+    };
+    Some(checked(result))
+}
+
+/// `DATEDIF(start, end, unit)`.
+fn date_dif(args: &[Value], numbers: NumberMode) -> Option<Value> {
+    let (start, end) = (day(&args[0])?, day(&args[1])?);
+    if start > end {
+        return None;
+    }
+    let ((y1, m1, d1), (y2, m2, d2)) = (ymd(start), ymd(end));
+    let months = (y2 - y1) * 12 + m2 as i64 - m1 as i64 - (d2 < d1) as i64;
+    let unit = match &args[2] {
+        Value::Text(t) => t.to_uppercase(),
+        _ => return None,
+    };
+    let n = match unit.as_str() {
+        "Y" => months / 12,
+        "M" => months,
+        "D" => (end - start) as i64,
+        "YM" => months % 12,
+        "MD" if d2 >= d1 => (d2 - d1) as i64,
+        // from the start's day in the month before the end, or its last day
+        "MD" => (end - serial(y2, m2 as i64 - 1, d1 as i64).min(serial(y2, m2 as i64, 0))) as i64,
+        "YD" => {
+            let mut anniversary = serial(y2, m1 as i64, d1 as i64);
+            if anniversary > end {
+                anniversary = serial(y2 - 1, m1 as i64, d1 as i64);
+            }
+            (end - anniversary) as i64
+        }
+        _ => return None,
+    };
+    Some(numbers.whole(n))
+}
+
+/// `NETWORKDAYS(start, end, holidays...)`: negative when end comes first.
+fn network_days(args: &[Value], numbers: NumberMode) -> Option<Value> {
+    let (start, end) = (day(&args[0])? as i64, day(&args[1])? as i64);
+    let (from, to, sign) = if start <= end {
+        (start, end, 1)
+    } else {
+        (end, start, -1)
+    };
+    let weekday = |d: i64| (1..=5).contains(&(d + 6).rem_euclid(7));
+    let days = to - from + 1;
+    let mut n = days / 7 * 5;
+    n += (from + days / 7 * 7..=to).filter(|&d| weekday(d)).count() as i64;
+    let mut holidays = Vec::new();
+    for v in &args[2..] {
+        holidays.push(day(v)? as i64);
+    }
+    holidays.sort_unstable();
+    holidays.dedup();
+    n -= holidays
+        .iter()
+        .filter(|&&d| (from..=to).contains(&d) && weekday(d))
+        .count() as i64;
+    Some(numbers.whole(sign * n))
+}
+
+/// `v` as a day: a date or number, or text such as `2024-05-01` (see
+/// [`parse`]), with the time of day cut off.
+fn day(v: &Value) -> Option<f64> {
+    let x = match v {
+        Value::Text(t) => parse(t)?,
+        v => v.number()?,
+    };
+    (0.0..MAX_SERIAL + 1.0).contains(&x).then(|| x.floor())
+}
+
+/// `v` as a whole count, with any fraction cut off.
+fn count(v: &Value) -> Option<i64> {
+    let x = match v {
+        Value::Text(t) => parse_number(t)?,
+        v => v.number()?,
+    };
+    // far beyond any date, but safe to do month arithmetic with
+    Some(x.trunc().clamp(-1e9, 1e9) as i64)
+}
+
+/// The serial of `day` of `month` of `year`; a month or day outside its
+/// usual range carries into the neighbouring ones, so month 13 is January of
+/// the next year and day 0 the last day of the previous month.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::date::serial;
+///
+/// assert_eq!(serial(2024, 5, 1), 45413.0);
+/// assert_eq!(serial(2023, 14, 1), serial(2024, 2, 1));
+/// assert_eq!(serial(2024, 3, 0), serial(2024, 2, 29));
+/// ```
+pub fn serial(year: i64, month: i64, day: i64) -> f64 {
+    let months = year * 12 + month - 1;
+    let (year, month) = (months.div_euclid(12), months.rem_euclid(12) + 1);
+    (days_from_civil(year, month, 1) + day - 1 + UNIX_EPOCH) as f64
+}
+
+/// Year, month and day of the date `serial`.
+pub fn ymd(serial: f64) -> (i64, u32, u32) {
+    civil_from_days(serial.floor() as i64 - UNIX_EPOCH)
+}
+
+/// The date `serial`, or [`NUM`] outside 1899-12-30 to 9999-12-31.
+pub fn checked(serial: f64) -> Value {
+    if (0.0..MAX_SERIAL + 1.0).contains(&serial) {
+        Value::Date(serial)
+    } else {
+        NUM
+    }
+}
+
+/// `a op b` when it is a date moved by some days: a date plus or minus a
+/// number, or a number plus a date. `None` for anything else, which is plain
+/// arithmetic (so two dates subtract to the days between them).
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::date::shift;
+/// use lab1::value::Value;
+///
+/// let may_day = Value::Date(45413.0);
+/// assert_eq!(shift(&may_day, &Value::from(30), '+').unwrap().to_string(), "2024-05-31");
+/// assert!(shift(&may_day, &may_day, '-').is_none());
+/// assert!(shift(&may_day, &Value::from(2), '*').is_none());
+/// ```
+pub fn shift(a: &Value, b: &Value, op: char) -> Option<Value> {
+    let moved = match (a, b, op) {
+        (Value::Date(_), Value::Date(_), _) => return None,
+        (&Value::Date(d), n, '+') | (n, &Value::Date(d), '+') => d + n.number()?,
+        (&Value::Date(d), n, '-') => d - n.number()?,
+        _ => return None,
+    };
+    Some(checked(moved))
+}
+
+/// The serial of the time `since` the Unix epoch, in UTC.
+pub fn from_unix(since: Duration) -> f64 {
+    UNIX_EPOCH as f64 + since.as_secs_f64() / SECONDS_PER_DAY
+}
+
+/// The date spelled by `text`: `YYYY-MM-DD`, optionally followed by a space
+/// or `T` and a time `HH:MM` or `HH:MM:SS`.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::date::parse;
+///
+/// assert_eq!(parse("2024-05-01"), Some(45413.0));
+/// assert_eq!(parse("2024-05-01 18:00"), Some(45413.75));
+/// assert_eq!(parse("2024-02-30"), None);
+/// assert_eq!(parse("2024-5-1T25:00"), None);
+/// ```
+pub fn parse(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time.trim_start())),
+        None => (text, None),
+    };
+    let number = |s: &str, digits: std::ops::RangeInclusive<usize>| {
+        let ok = digits.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit());
+        ok.then(|| s.parse::<i64>().ok()).flatten()
+    };
+    let mut parts = date.split('-');
+    let y = number(parts.next()?, 4..=4)?;
+    let m = number(parts.next()?, 1..=2)?;
+    let d = number(parts.next()?, 1..=2)?;
+    if parts.next().is_some() || !(1..=12).contains(&m) || d < 1 {
+        return None;
+    }
+    if serial(y, m, d) >= serial(y, m + 1, 1) {
+        return None;
+    }
+    let seconds = match time {
+        None => 0,
+        Some(time) => {
+            let mut parts = time.split(':');
+            let h = number(parts.next()?, 1..=2)?;
+            let min = number(parts.next()?, 2..=2)?;
+            let s = parts.next().map_or(Some(0), |s| number(s, 2..=2))?;
+            if parts.next().is_some() || h > 23 || min > 59 || s > 59 {
+                return None;
+            }
+            h * 3600 + min * 60 + s
+        }
+    };
+    Some(serial(y, m, d) + seconds as f64 / SECONDS_PER_DAY)
+}
+
+/// The date `serial` as `2024-05-01`, with the time to the minute (or the
+/// second, if it has seconds) when it is not midnight.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::date::format;
+///
+/// assert_eq!(format(45413.0), "2024-05-01");
+/// assert_eq!(format(45413.75), "2024-05-01 18:00");
+/// assert_eq!(format(45413.5 + 15.0 / 86400.0), "2024-05-01 12:00:15");
+/// ```
+pub fn format(serial: f64) -> String {
+    let seconds = (serial * SECONDS_PER_DAY).round() as i64;
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    let (y, m, d) = ymd(days as f64);
+    let date = format!("{:04}-{:02}-{:02}", y, m, d);
+    match time {
+        0 => date,
+        t if t % 60 == 0 => format!("{} {:02}:{:02}", date, t / 3600, t / 60 % 60),
+        t => format!("{} {:02}:{:02}:{:02}", date, t / 3600, t / 60 % 60, t % 60),
+    }
+}
+
+/// The date `serial` written with `pattern`, as `TEXT` does: `yyyy` or `yy`
+/// for the year, `m`, `mm`, `mmm` or `mmmm` for the month, `d`, `dd`, `ddd`
+/// or `dddd` for the day, and `h`, `hh`, `m`, `mm`, `s`, `ss` for the time,
+/// an `m` right after an hour or before a second being minutes. Any other
+/// character is kept. `None` if `pattern` holds none of these.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::date::format_with;
+///
+/// let noon = 45413.5;
+/// assert_eq!(format_with(noon, "dddd, d mmmm yyyy").unwrap(), "Wednesday, 1 May 2024");
+/// assert_eq!(format_with(noon, "dd/mm/yy hh:mm").unwrap(), "01/05/24 12:00");
+/// assert_eq!(format_with(noon, "Total"), None);
+/// ```
+pub fn format_with(serial: f64, pattern: &str) -> Option<String> {
+    // runs of one code letter, and the characters between them
+    let mut tokens: Vec<(char, usize)> = Vec::new();
+    for c in pattern.chars() {
+        let code = c.to_ascii_lowercase();
+        match tokens.last_mut() {
+            Some((last, n)) if *last == code && "ymdhs".contains(code) => *n += 1,
+            _ => tokens.push((if "ymdhs".contains(code) { code } else { c }, 1)),
+        }
+    }
+    let codes: Vec<usize> = (0..tokens.len())
+        .filter(|&i| "ymdhs".contains(tokens[i].0))
+        .collect();
+    if codes.is_empty() {
+        return None;
+    }
+    let seconds = (serial * SECONDS_PER_DAY).round() as i64;
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    let (y, m, d) = ymd(days as f64);
+    let weekday = WEEKDAYS[(days + 6).rem_euclid(7) as usize];
+    let month = MONTHS[m as usize - 1];
+    let (hour, minute, second) = (time / 3600, time / 60 % 60, time % 60);
+    let mut out = String::new();
+    for (i, &(code, n)) in tokens.iter().enumerate() {
+        let at = codes.iter().position(|&c| c == i);
+        let near = |offset: isize| {
+            at.and_then(|at| codes.get(at.checked_add_signed(offset)?))
+                .map(|&j| tokens[j].0)
+        };
+        let pad = |x: i64| {
+            if n >= 2 {
+                format!("{:02}", x)
+            } else {
+                x.to_string()
+            }
+        };
+        match code {
+            'y' if n <= 2 => out.push_str(&format!("{:02}", y.rem_euclid(100))),
+            'y' => out.push_str(&format!("{:04}", y)),
+            'm' if near(-1) == Some('h') || near(1) == Some('s') => out.push_str(&pad(minute)),
+            'm' if n == 3 => out.push_str(&month[..3]),
+            'm' if n >= 4 => out.push_str(month),
+            'm' => out.push_str(&pad(m as i64)),
+            'd' if n == 3 => out.push_str(&weekday[..3]),
+            'd' if n >= 4 => out.push_str(weekday),
+            'd' => out.push_str(&pad(d as i64)),
+            'h' => out.push_str(&pad(hour)),
+            's' => out.push_str(&pad(second)),
+            c => (0..n).for_each(|_| out.push(c)),
+        }
+    }
+    Some(out)
+}
+
+/// Days from 1970-01-01 to `y`-`m`-`d` in the Gregorian calendar.
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The date `days` after 1970-01-01, as [`days_from_civil`] counts.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + (m <= 2) as i64, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(f: Date, args: &[Value]) -> Value {
+        apply(f, args, NumberMode::Float, 0.0)
+    }
+
+    fn date(text: &str) -> Value {
+        Value::Date(parse(text).unwrap())
+    }
+
+    #[test]
+    fn test_calendar_round_trips() {
+        assert_eq!(serial(1899, 12, 30), 0.0);
+        assert_eq!(serial(1970, 1, 1), UNIX_EPOCH as f64);
+        assert_eq!(ymd(MAX_SERIAL), (9999, 12, 31));
+        for s in [0.0, 60.0, 61.0, 36585.0, 45351.0, 45352.0, MAX_SERIAL] {
+            let (y, m, d) = ymd(s);
+            assert_eq!(serial(y, m as i64, d as i64), s);
+        }
+        assert_eq!(
+            format(parse("2024-02-29T7:05:09").unwrap()),
+            "2024-02-29 07:05:09"
+        );
+        assert_eq!(parse("2023-02-29"), None);
+        assert_eq!(parse("2024-05-01 12:00 pm"), None);
+        assert_eq!(from_unix(Duration::from_secs(86400 * 3 / 2)), 25570.5);
+    }
+
+    #[test]
+    fn test_month_arithmetic() {
+        let jan31 = date("2024-01-31");
+        assert_eq!(
+            call(Date::EoMonth, &[jan31.clone(), Value::from(1)]).to_string(),
+            "2024-02-29"
+        );
+        assert_eq!(
+            call(Date::EDate, &[jan31.clone(), Value::from(-2)]).to_string(),
+            "2023-11-30"
+        );
+        assert_eq!(
+            call(Date::EDate, &[jan31.clone(), Value::from(13)]).to_string(),
+            "2025-02-28"
+        );
+        let args = [99, 14, 0].map(Value::from);
+        assert_eq!(call(Date::Date, &args).to_string(), "2000-01-31");
+        assert_eq!(call(Date::Date, &[10000, 1, 1].map(Value::from)), NUM);
+        assert_eq!(call(Date::EDate, &[Value::from(0), Value::from(-1)]), NUM);
+    }
+
+    #[test]
+    fn test_date_dif_units() {
+        let dif = |a: &str, b: &str, unit: &str| {
+            call(Date::DateDif, &[date(a), date(b), Value::text(unit)])
+        };
+        assert_eq!(dif("2020-02-29", "2024-02-28", "Y"), 3);
+        assert_eq!(dif("2020-02-29", "2024-02-28", "m"), 47);
+        assert_eq!(dif("2024-01-31", "2024-03-01", "D"), 30);
+        assert_eq!(dif("2023-05-20", "2024-03-10", "YM"), 9);
+        assert_eq!(dif("2024-01-31", "2024-03-01", "MD"), 1);
+        assert_eq!(dif("2023-12-25", "2024-03-01", "YD"), 67);
+        assert_eq!(dif("2024-03-01", "2024-01-01", "D"), ERR);
+        assert_eq!(dif("2024-01-01", "2024-03-01", "W"), ERR);
+    }
+
+    #[test]
+    fn test_weekdays_and_working_days() {
+        // 2024-05-01 was a Wednesday
+        let wed = date("2024-05-01");
+        assert_eq!(call(Date::Weekday, std::slice::from_ref(&wed)), 4);
+        assert_eq!(call(Date::Weekday, &[wed.clone(), Value::from(2)]), 3);
+        assert_eq!(call(Date::Weekday, &[wed.clone(), Value::from(3)]), 2);
+        assert_eq!(call(Date::Weekday, &[wed.clone(), Value::from(4)]), ERR);
+        let end = date("2024-05-31");
+        assert_eq!(call(Date::NetworkDays, &[wed.clone(), end.clone()]), 23);
+        assert_eq!(call(Date::NetworkDays, &[end.clone(), wed.clone()]), -23);
+        let holidays = [
+            wed.clone(),
+            end.clone(),
+            date("2024-05-27"),
+            date("2024-05-27"),
+            date("2024-05-05"),
+        ];
+        assert_eq!(
+            call(
+                Date::NetworkDays,
+                &[&[wed.clone(), end.clone()][..], &holidays].concat()
+            ),
+            20
+        );
+        assert_eq!(
+            call(
+                Date::NetworkDays,
+                &[wed.clone(), end.clone(), Value::text("soon")]
+            ),
+            ERR
+        );
+    }
+
+    #[test]
+    fn test_clock_and_formats() {
+        let now = parse("2024-05-01 09:30").unwrap();
+        let today = apply(Date::Today, &[], NumberMode::Float, now);
+        assert_eq!(today.to_string(), "2024-05-01");
+        assert_eq!(
+            apply(Date::Now, &[], NumberMode::Float, now).to_string(),
+            "2024-05-01 09:30"
+        );
+        assert_eq!(format_with(now, "mmm d, h:mm").unwrap(), "May 1, 9:30");
+        assert_eq!(
+            format_with(now, "ddd yyyy-mm-dd").unwrap(),
+            "Wed 2024-05-01"
+        );
+        assert_eq!(format_with(now, "mm:ss").unwrap(), "30:00");
+    }
+}
@@ -0,0 +1,321 @@
+//! Module `decimal`.
+//!
+//! Exact fixed-point numbers for sheets that hold money. A sheet in decimal
+//! mode ([`NumberMode::Decimal`]) keeps every number as a whole count of
+//! `10^-scale` units, so `0.1 + 0.2` is exactly `0.3` and a column of prices
+//! never drifts.
+//!
+//! Sums and differences are exact. Products, quotients, AVG and STDEV are
+//! rounded to the sheet's scale with its [`Rounding`] rule, as is a literal
+//! with more decimals than the scale. A result beyond the `i64` range of
+//! units is `#NUM!`.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::graph::aggregate;
+use crate::value::{NumberMode, Value, ERR, NUM};
+
+/// Largest number of decimals a decimal sheet may keep.
+pub const MAX_SCALE: u32 = 9;
+
+/// How a result is rounded to the sheet's scale when it falls exactly
+/// halfway between two representable numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// To the neighbour with an even last digit (banker's rounding).
+    #[default]
+    HalfEven,
+    /// Away from zero.
+    HalfUp,
+}
+
+/// A fixed-point number: `units × 10^-scale`.
+///
+/// Packed to 4-byte alignment so that a [`Value`] holding it stays 16 bytes.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed(4))]
+pub struct Decimal {
+    /// The number in units of `10^-scale`.
+    pub units: i64,
+    /// Decimals kept, at most [`MAX_SCALE`].
+    pub scale: u32,
+}
+
+fn pow10(n: u32) -> i128 {
+    10i128.pow(n)
+}
+
+/// `n / d` rounded to a whole number with `rounding`; `d` must not be zero.
+fn div_round(n: i128, d: i128, rounding: Rounding) -> i128 {
+    let (q, r) = (n / d, n % d);
+    let away = if (n < 0) == (d < 0) { q + 1 } else { q - 1 };
+    match (2 * r.abs()).cmp(&d.abs()) {
+        Ordering::Less => q,
+        Ordering::Greater => away,
+        Ordering::Equal if rounding == Rounding::HalfUp || q % 2 != 0 => away,
+        Ordering::Equal => q,
+    }
+}
+
+impl Decimal {
+    /// `units × 10^-scale`, if the units fit an `i64`.
+    fn checked(units: i128, scale: u32) -> Option<Decimal> {
+        Some(Decimal {
+            units: i64::try_from(units).ok()?,
+            scale,
+        })
+    }
+
+    /// This number with `scale` decimals, rounded with `rounding` if that
+    /// drops digits; `None` if it no longer fits.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::decimal::{Decimal, Rounding};
+    ///
+    /// let d = Decimal { units: 125, scale: 2 };
+    /// assert_eq!(d.rescale(1, Rounding::HalfEven).unwrap().to_string(), "1.2");
+    /// assert_eq!(d.rescale(1, Rounding::HalfUp).unwrap().to_string(), "1.3");
+    /// assert_eq!(d.rescale(3, Rounding::HalfUp).unwrap().to_string(), "1.250");
+    /// ```
+    pub fn rescale(self, scale: u32, rounding: Rounding) -> Option<Decimal> {
+        let units = self.units as i128;
+        let units = if scale >= self.scale {
+            units * pow10(scale - self.scale)
+        } else {
+            div_round(units, pow10(self.scale - scale), rounding)
+        };
+        Decimal::checked(units, scale)
+    }
+
+    /// The nearest `f64`.
+    pub fn to_f64(self) -> f64 {
+        self.units as f64 / pow10(self.scale) as f64
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        let scale = self.scale.max(other.scale);
+        let a = self.units as i128 * pow10(scale - self.scale);
+        let b = other.units as i128 * pow10(scale - other.scale);
+        Some(a.cmp(&b))
+    }
+}
+
+impl PartialEq for Decimal {
+    /// Equal in value, whatever the scales.
+    fn eq(&self, other: &Decimal) -> bool {
+        let scale = self.scale.max(other.scale);
+        self.units as i128 * pow10(scale - self.scale)
+            == other.units as i128 * pow10(scale - other.scale)
+    }
+}
+
+impl fmt::Display for Decimal {
+    /// All `scale` decimals, e.g. `-0.50` for 50 units at scale 2.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.units < 0 { "-" } else { "" };
+        let digits = self.units.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, frac)
+    }
+}
+
+/// The arithmetic of a decimal sheet: its scale and rounding rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecimalMode {
+    /// Decimals every number keeps.
+    pub scale: u32,
+    /// How results with more decimals are rounded.
+    pub rounding: Rounding,
+}
+
+impl DecimalMode {
+    /// A mode keeping `scale` decimals.
+    pub fn new(scale: u32, rounding: Rounding) -> Result<DecimalMode, String> {
+        if scale > MAX_SCALE {
+            return Err(format!("decimal scale must be at most {}", MAX_SCALE));
+        }
+        Ok(DecimalMode { scale, rounding })
+    }
+
+    fn wrap(self, units: Option<i128>) -> Value {
+        units
+            .and_then(|u| Decimal::checked(u, self.scale))
+            .map_or(NUM, Value::Decimal)
+    }
+
+    /// `v` as a number of this mode: exact for whole numbers and decimals
+    /// with no more decimals than the scale, rounded otherwise. Errors are
+    /// kept; a date is its number of days and text is [`ERR`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::decimal::{DecimalMode, Rounding};
+    /// use lab1::value::Value;
+    ///
+    /// let cents = DecimalMode::new(2, Rounding::HalfUp).unwrap();
+    /// assert_eq!(cents.convert(Value::from(2.675)).to_string(), "2.68");
+    /// assert_eq!(cents.convert(Value::Int(3)).to_string(), "3.00");
+    /// ```
+    pub fn convert(self, v: Value) -> Value {
+        match v {
+            Value::Decimal(d) => d
+                .rescale(self.scale, self.rounding)
+                .map_or(NUM, Value::Decimal),
+            Value::Int(i) => self.wrap((i as i128).checked_mul(pow10(self.scale))),
+            Value::Bool(b) => self.convert(Value::Int(b as i64)),
+            Value::Number(x) | Value::Date(x) if !x.is_finite() => NUM,
+            Value::Number(x) | Value::Date(x) => {
+                // 2.675 is stored just below itself, so round the shortest
+                // decimal text of the float rather than its binary value
+                let text = x.to_string();
+                let (whole, frac) = text.split_once('.').unwrap_or((&text, ""));
+                let frac = &frac[..frac.len().min(18)];
+                let digits = format!("{}{}", whole, frac).parse::<i128>().ok();
+                let shift = frac.len() as u32;
+                self.wrap(digits.map(|n| match shift.checked_sub(self.scale) {
+                    Some(drop) => div_round(n, pow10(drop), self.rounding),
+                    None => n * pow10(self.scale - shift),
+                }))
+            }
+            Value::Error(_) => v,
+            Value::Text(_) => ERR,
+        }
+    }
+
+    /// The units of `v` at this mode's scale, or the error to return.
+    fn units(self, v: Value) -> Result<i128, Value> {
+        match self.convert(v) {
+            Value::Decimal(d) => Ok(d.units as i128),
+            other => Err(other),
+        }
+    }
+
+    /// One arithmetic operation on two numbers of this mode. Division by
+    /// zero (or an unknown operator) is [`ERR`]; an error operand is passed
+    /// on.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::decimal::{DecimalMode, Rounding};
+    /// use lab1::value::Value;
+    ///
+    /// let cents = DecimalMode::new(2, Rounding::HalfEven).unwrap();
+    /// let sum = cents.arith(Value::from(0.1), Value::from(0.2), '+');
+    /// assert_eq!(sum.to_string(), "0.30");
+    /// assert_eq!(cents.arith(Value::from(1), Value::from(8), '/').to_string(), "0.12");
+    /// ```
+    pub fn arith(self, v1: Value, v2: Value, op: char) -> Value {
+        let (a, b) = match (self.units(v1), self.units(v2)) {
+            (Err(e), _) | (_, Err(e)) => return e,
+            (Ok(a), Ok(b)) => (a, b),
+        };
+        let one = pow10(self.scale);
+        self.wrap(match op {
+            '+' => Some(a + b),
+            '-' => Some(a - b),
+            '*' => Some(div_round(a * b, one, self.rounding)),
+            '/' if b != 0 => Some(div_round(a * one, b, self.rounding)),
+            _ => return ERR,
+        })
+    }
+
+    /// Range formula `op_type` (see [`aggregate`]) over numbers of this
+    /// mode. MIN, MAX and SUM are exact, AVG is rounded to the scale and
+    /// STDEV is computed in floating point and then rounded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::decimal::{DecimalMode, Rounding};
+    /// use lab1::value::Value;
+    ///
+    /// let cents = DecimalMode::new(2, Rounding::HalfUp).unwrap();
+    /// let prices = [0.1, 0.2, 0.1].map(Value::from);
+    /// assert_eq!(cents.aggregate(12, &prices).to_string(), "0.40");
+    /// assert_eq!(cents.aggregate(11, &prices).to_string(), "0.13");
+    /// ```
+    pub fn aggregate(self, op_type: i32, values: &[Value]) -> Value {
+        let mut units = Vec::with_capacity(values.len());
+        for v in values {
+            match self.units(v.clone()) {
+                Ok(u) => units.push(u),
+                Err(e) => return e,
+            }
+        }
+        if units.is_empty() {
+            return ERR;
+        }
+        let sum: i128 = units.iter().sum();
+        self.wrap(match op_type {
+            9 => units.iter().copied().min(),
+            10 => units.iter().copied().max(),
+            11 => Some(div_round(sum, units.len() as i128, self.rounding)),
+            12 => Some(sum),
+            13 => {
+                let floats: Vec<Value> = values.iter().map(|v| self.convert(v.clone())).collect();
+                return self.convert(aggregate(13, &floats));
+            }
+            _ => return ERR,
+        })
+    }
+}
+
+impl From<DecimalMode> for NumberMode {
+    fn from(mode: DecimalMode) -> NumberMode {
+        NumberMode::Decimal(mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounding_rules() {
+        for (n, even, up) in [(5, 0, 1), (15, 2, 2), (25, 2, 3), (-25, -2, -3), (26, 3, 3)] {
+            assert_eq!(div_round(n, 10, Rounding::HalfEven), even);
+            assert_eq!(div_round(n, 10, Rounding::HalfUp), up);
+        }
+        assert_eq!(div_round(7, -2, Rounding::HalfEven), -4);
+    }
+
+    #[test]
+    fn test_display_and_equality() {
+        let d = |units, scale| Decimal { units, scale };
+        assert_eq!(d(-5, 2).to_string(), "-0.05");
+        assert_eq!(d(1234, 0).to_string(), "1234");
+        assert_eq!(d(150, 2), d(15, 1));
+        assert_ne!(d(150, 2), d(15, 2));
+    }
+
+    #[test]
+    fn test_exact_money_arithmetic() {
+        let cents = DecimalMode::new(2, Rounding::HalfEven).unwrap();
+        let mut total = Value::Int(0);
+        for _ in 0..10 {
+            total = cents.arith(total, Value::from(0.1), '+');
+        }
+        assert_eq!(total.to_string(), "1.00");
+        assert_eq!(
+            cents
+                .arith(Value::from(1.25), Value::from(0.5), '*')
+                .to_string(),
+            "0.62"
+        );
+        assert_eq!(cents.arith(Value::from(1), Value::Int(0), '/'), ERR);
+        assert_eq!(cents.arith(Value::Int(i64::MAX), Value::Int(1), '+'), NUM);
+        assert!(DecimalMode::new(MAX_SCALE + 1, Rounding::HalfUp).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+//! Module `depgraph`.
+//!
+//! The dependency structure of a sheet as a picture: every formula cell and
+//! every cell it reads becomes a node, every reference an edge. A range
+//! argument such as `SUM(A1:A100)`, on its own or inside a larger formula
+//! like `IF(SUM(A1:A100)>0, …)`, is collapsed into a single range node
+//! instead of a hundred edges; member cells that hold formulas of their own
+//! are tied to it with a `member` edge so the structure stays connected.
+//!
+//! Two encodings are provided, Graphviz [`DOT`](to_dot) and [`JSON`](to_json):
+//!
+//! ```json
+//! {"nodes": [{"id": "A1", "formula": "5", "value": 5},
+//!            {"id": "B1", "formula": "SUM(A1:A3)", "value": 5},
+//!            {"id": "A1:A3", "range": true}],
+//!  "edges": [{"from": "A1", "to": "A1:A3", "kind": "member"},
+//!            {"from": "A1:A3", "to": "B1", "kind": "ref"}]}
+//! ```
+//!
+//! Either can be restricted to the *cone* of one cell: the cell plus its
+//! transitive precedents and dependents.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::{json, Map, Value};
+
+use crate::expr::{Expr, EXPR};
+use crate::json::{cell_name, cell_value};
+use crate::spreadsheet::Spreadsheet;
+use crate::trace::{formula_precedents, precedents, trace_dependents, trace_precedents, Depth};
+
+/// A node: a single cell (by index) or a collapsed range (by its two corners).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Node {
+    Cell(usize),
+    Range(usize, usize),
+}
+
+/// The nodes and edges to draw; each edge is `(from, to, is_member)`.
+struct Structure {
+    nodes: BTreeSet<Node>,
+    edges: Vec<(Node, Node, bool)>,
+}
+
+fn formula_text(sheet: &Spreadsheet, cell: usize) -> &str {
+    sheet
+        .formula(cell)
+        .split_once('=')
+        .map_or("", |(_, rhs)| rhs)
+}
+
+fn structure(sheet: &Spreadsheet, focus: Option<usize>) -> Structure {
+    let cone: Option<BTreeSet<usize>> = focus.map(|cell| {
+        let mut cone: BTreeSet<usize> = trace_precedents(sheet, &[cell], Depth::Transitive)
+            .into_iter()
+            .chain(trace_dependents(sheet, &[cell], Depth::Transitive))
+            .collect();
+        cone.insert(cell);
+        cone
+    });
+    let wanted = |cell: usize| cone.as_ref().is_none_or(|cone| cone.contains(&cell));
+
+    let mut nodes = BTreeSet::new();
+    let mut edges = Vec::new();
+    let mut ranges: BTreeMap<Node, Vec<usize>> = BTreeMap::new();
+    for cell in (0..sheet.arr.len()).filter(|&c| wanted(c)) {
+        if formula_text(sheet, cell).is_empty() {
+            continue;
+        }
+        nodes.insert(Node::Cell(cell));
+        let f = &sheet.formula_array[cell];
+        if (9..=13).contains(&f.op_type) {
+            let range = Node::Range(f.p1 as usize, f.p2 as usize);
+            nodes.insert(range);
+            edges.push((range, Node::Cell(cell), false));
+            ranges
+                .entry(range)
+                .or_insert_with(|| formula_precedents(f, usize::MAX, sheet.cols));
+        } else if let (EXPR, Some(expr)) = (f.op_type, sheet.graph.exprs.get(&cell)) {
+            let (mut cells, parts) = expr.ref_parts();
+            cells.extend(sheet.graph.dynamic.get(&cell).into_iter().flatten());
+            for src in cells {
+                nodes.insert(Node::Cell(src));
+                edges.push((Node::Cell(src), Node::Cell(cell), false));
+            }
+            for (start, end) in parts {
+                let range = Node::Range(start, end);
+                nodes.insert(range);
+                edges.push((range, Node::Cell(cell), false));
+                ranges
+                    .entry(range)
+                    .or_insert_with(|| Expr::Range(start, end).refs(sheet.cols));
+            }
+        } else {
+            for src in precedents(sheet, cell) {
+                nodes.insert(Node::Cell(src));
+                edges.push((Node::Cell(src), Node::Cell(cell), false));
+            }
+        }
+    }
+    for (range, members) in ranges {
+        for member in members {
+            if wanted(member) && !formula_text(sheet, member).is_empty() {
+                edges.push((Node::Cell(member), range, true));
+            }
+        }
+    }
+    edges.sort_unstable();
+    edges.dedup();
+    Structure { nodes, edges }
+}
+
+fn node_id(sheet: &Spreadsheet, node: Node) -> String {
+    match node {
+        Node::Cell(c) => cell_name(c, sheet.cols),
+        Node::Range(a, b) => format!("{}:{}", cell_name(a, sheet.cols), cell_name(b, sheet.cols)),
+    }
+}
+
+/// Quote `text` as a DOT string.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The dependency graph as Graphviz DOT, optionally limited to the cone of
+/// `focus`. Cell nodes are labelled with their name and formula.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::depgraph::to_dot;
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(2, 2);
+/// assign(&mut sheet, "B1=A1+1").unwrap();
+/// assert_eq!(
+///     to_dot(&sheet, None),
+///     "digraph sheet {\n  node [shape=box];\n  \"A1\";\n  \"B1\" [label=\"B1\\nA1+1\"];\n  \"A1\" -> \"B1\";\n}\n"
+/// );
+/// ```
+pub fn to_dot(sheet: &Spreadsheet, focus: Option<usize>) -> String {
+    let Structure { nodes, edges } = structure(sheet, focus);
+    let mut out = String::from("digraph sheet {\n  node [shape=box];\n");
+    for node in nodes {
+        let id = quote(&node_id(sheet, node));
+        match node {
+            Node::Cell(c) if formula_text(sheet, c).is_empty() => {
+                out.push_str(&format!("  {};\n", id))
+            }
+            Node::Cell(c) => out.push_str(&format!(
+                "  {} [label={}];\n",
+                id,
+                quote(&format!(
+                    "{}\n{}",
+                    node_id(sheet, node),
+                    formula_text(sheet, c)
+                ))
+                .replace('\n', "\\n")
+            )),
+            Node::Range(..) => out.push_str(&format!("  {} [shape=folder];\n", id)),
+        }
+    }
+    for (from, to, member) in edges {
+        out.push_str(&format!(
+            "  {} -> {}{};\n",
+            quote(&node_id(sheet, from)),
+            quote(&node_id(sheet, to)),
+            if member { " [style=dotted]" } else { "" }
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// The dependency graph as JSON (see the module docs for the shape),
+/// optionally limited to the cone of `focus`.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::depgraph::to_json;
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(2, 2);
+/// assign(&mut sheet, "B1=A1+1").unwrap();
+/// assign(&mut sheet, "B2=7").unwrap();
+/// assert_eq!(
+///     to_json(&sheet, Some(0)),
+///     r#"{"edges":[{"from":"A1","kind":"ref","to":"B1"}],"nodes":[{"id":"A1","value":0},{"formula":"A1+1","id":"B1","value":1}]}"#
+/// );
+/// ```
+pub fn to_json(sheet: &Spreadsheet, focus: Option<usize>) -> String {
+    let Structure { nodes, edges } = structure(sheet, focus);
+    let nodes: Vec<Value> = nodes
+        .into_iter()
+        .map(|node| {
+            let mut entry = Map::new();
+            entry.insert("id".to_string(), json!(node_id(sheet, node)));
+            match node {
+                Node::Cell(c) => {
+                    let formula = formula_text(sheet, c);
+                    if !formula.is_empty() {
+                        entry.insert("formula".to_string(), json!(formula));
+                    }
+                    entry.insert("value".to_string(), cell_value(&sheet.arr[c]));
+                }
+                Node::Range(..) => {
+                    entry.insert("range".to_string(), json!(true));
+                }
+            }
+            Value::Object(entry)
+        })
+        .collect();
+    let edges: Vec<Value> = edges
+        .into_iter()
+        .map(|(from, to, member)| {
+            json!({
+                "from": node_id(sheet, from),
+                "to": node_id(sheet, to),
+                "kind": if member { "member" } else { "ref" },
+            })
+        })
+        .collect();
+    json!({ "nodes": nodes, "edges": edges }).to_string()
+}
+
+/// Export in `format` (`dot` or `json`), optionally around `focus`.
+pub fn export(sheet: &Spreadsheet, format: &str, focus: Option<usize>) -> Result<String, String> {
+    match format {
+        "dot" => Ok(to_dot(sheet, focus)),
+        "json" => Ok(to_json(sheet, focus)),
+        _ => Err(format!("unknown graph format {}", format)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::assign;
+    use crate::spreadsheet::initialize_spreadsheet;
+
+    fn sheet() -> Spreadsheet {
+        let mut sheet = initialize_spreadsheet(4, 4);
+        for input in [
+            "A1=5",
+            "A2=A1*2",
+            "B1=SUM(A1:A3)",
+            "C1=B1+1",
+            "D4=9",
+            "D3=D4",
+        ] {
+            assign(&mut sheet, input).unwrap();
+        }
+        sheet
+    }
+
+    #[test]
+    fn test_dot_collapses_ranges() {
+        let dot = to_dot(&sheet(), None);
+        assert!(dot.contains("  \"A1:A3\" [shape=folder];\n"));
+        assert!(dot.contains("  \"A1:A3\" -> \"B1\";\n"));
+        assert!(dot.contains("  \"A2\" -> \"A1:A3\" [style=dotted];\n"));
+        // A3 is empty and only reached through the range
+        assert!(!dot.contains("\"A3\""));
+        assert!(dot.contains("  \"C1\" [label=\"C1\\nB1+1\"];\n"));
+        assert!(dot.contains("  \"D4\" -> \"D3\";\n"));
+    }
+
+    #[test]
+    fn test_ranges_inside_expressions_collapse() {
+        let mut sheet = initialize_spreadsheet(500, 3);
+        assign(&mut sheet, "A2=7").unwrap();
+        assign(&mut sheet, "B1=IF(SUM(A1:A500)>0, C1, SUM(A1:A500))").unwrap();
+        let dot = to_dot(&sheet, None);
+        assert!(dot.contains("  \"A1:A500\" [shape=folder];\n"));
+        assert!(dot.contains("  \"A1:A500\" -> \"B1\";\n"));
+        assert!(dot.contains("  \"A2\" -> \"A1:A500\" [style=dotted];\n"));
+        assert!(dot.contains("  \"C1\" -> \"B1\";\n"));
+        assert_eq!(dot.matches(" -> ").count(), 3);
+    }
+
+    #[test]
+    fn test_cone_excludes_unrelated_cells() {
+        let json = to_json(&sheet(), Some(2));
+        let doc: Value = serde_json::from_str(&json).unwrap();
+        let ids: Vec<&str> = doc["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["A1", "B1", "C1", "A2", "A1:A3"]);
+        assert_eq!(doc["edges"].as_array().unwrap().len(), 5);
+        assert!(!json.contains("D4"));
+    }
+
+    #[test]
+    fn test_quote_escapes() {
+        assert_eq!(quote(r#"say "hi"\"#), r#""say \"hi\"\\""#);
+        assert!(export(&sheet(), "svg", None).is_err());
+    }
+}
@@ -1,61 +1,45 @@
 use crate::graph::Graph;
 use crate::input_parser::cell_parser;
+use crate::value::Value;
+use crate::view::Viewport;
 /// Print a 10×10 “window” of the spreadsheet, starting at column `curr_x` and row `curry`.
 ///
 /// Prints column-letter headers (A, B, …, AA, …) and up to 10 rows/columns of values
-/// (or `ERR` for `i32::MIN`).
+/// (or `ERR` for error cells), using the default [`Viewport`]; see
+/// [`Spreadsheet::print`](crate::spreadsheet::Spreadsheet::print) for a sheet's
+/// own view settings.
 ///
 /// # Parameters
 ///
 /// - `curr_x`, `curry`: the top-left corner of the viewport (zero-based indices)
 /// - `arr`: the full row-major cell buffer
 /// - `cols`, `rows`: the full sheet dimensions
+/// - `precision`: decimals shown for numbers, or `None` for whole numbers as
+///   integers and fractions rounded to a few places
 ///
 /// # Examples
 ///
 /// ```rust
 /// # use lab1::display::printer;
 /// # // create a 5×5 sheet, all zeros
-/// # let arr = vec![0; 25];
-/// printer(1, 2, &arr, 5, 5);
+/// # use lab1::value::Value;
+/// # let arr = vec![Value::default(); 25];
+/// printer(1, 2, &arr, 5, 5, None);
 /// // this will print columns B–K and rows 3–12 (but sheet is only 5×5, so stops at E5)
 /// ```
-use std::cmp;
-pub fn printer(curr_x: usize, curry: usize, arr: &[i32], cols: usize, rows: usize) {
-    // Print column headers
-    print!("      ");
-    let num_cols = cmp::min(cols.saturating_sub(curr_x), 10);
-    for i in 0..num_cols {
-        let mut val = (curr_x + i + 1) as i32; // 1-indexed value
-        let mut col_str = String::new();
-        while val > 0 {
-            val -= 1;
-            let letter = ((val % 26) as u8 + b'A') as char;
-            col_str.push(letter);
-            val /= 26;
-        }
-        // Reverse to get the correct order
-        let header: String = col_str.chars().rev().collect();
-        print!("{:<10}", header);
-    }
-    println!();
-
-    // Print rows with cell values
-    let num_rows = cmp::min(rows.saturating_sub(curry), 10);
-    for j in 0..num_rows {
-        // Print row number (1-indexed) left aligned in width 3
-        print!("{:<3}   ", curry + j + 1);
-        for i in 0..num_cols {
-            let index = (curr_x + i) + cols * (curry + j);
-            let value = arr[index];
-            if value == i32::MIN {
-                print!("{:<10}", "ERR");
-            } else {
-                print!("{:<10}", value);
-            }
-        }
-        println!();
-    }
+pub fn printer(
+    curr_x: usize,
+    curry: usize,
+    arr: &[Value],
+    cols: usize,
+    rows: usize,
+    precision: Option<usize>,
+) {
+    let view = Viewport {
+        precision,
+        ..Viewport::default()
+    };
+    print!("{}", view.render(curr_x, curry, arr, cols, rows));
 }
 /// Scroll the visible “window” by page or jump to a specific cell.
 ///
@@ -110,7 +94,7 @@ pub fn printer(curr_x: usize, curry: usize, arr: &[i32], cols: usize, rows: usiz
 /// ```
 pub fn scroller_display(
     cmd: &str,
-    _arr: &[i32],
+    _arr: &[Value],
     curr_x: &mut usize,
     curry: &mut usize,
     cols: usize,
@@ -209,6 +193,7 @@ pub fn column_index_to_name(mut col: usize) -> String {
 /// - `_curr_x`, `_curr_y`: not yet used (always renders all rows/cols)
 /// - `arr`: full row-major buffer
 /// - `cols`, `rows`: sheet dimensions
+/// - `precision`: decimals shown for numbers (see [`printer`])
 ///
 /// # Returns
 ///
@@ -218,17 +203,19 @@ pub fn column_index_to_name(mut col: usize) -> String {
 ///
 /// ```rust
 /// # use lab1::display::render_spreadsheet;
-/// # let arr = vec![0; 6];
-/// # let html = render_spreadsheet(0, 0, &arr, 3, 2);
+/// # use lab1::value::Value;
+/// # let arr = vec![Value::default(); 6];
+/// # let html = render_spreadsheet(0, 0, &arr, 3, 2, None);
 /// assert!(html.contains("<table"));
 /// assert!(html.contains(r#"data-cell="A1""#));
 /// ```
 pub fn render_spreadsheet(
     _curr_x: usize,
     _curr_y: usize,
-    arr: &[i32],
+    arr: &[Value],
     cols: usize,
     rows: usize,
+    precision: Option<usize>,
 ) -> String {
     let mut output = String::new();
 
@@ -261,11 +248,7 @@ pub fn render_spreadsheet(
         for col in 0..cols {
             let index = row * cols + col;
             let cell_label = format!("{}{}", column_index_to_name(col), row_num);
-            let cell_value = if arr[index] == i32::MIN {
-                "ERR".to_string()
-            } else {
-                arr[index].to_string()
-            };
+            let cell_value = escape_html(&arr[index].format(precision));
 
             output.push_str(&format!(
                 r#"<td style="padding: 5px;">
@@ -289,10 +272,28 @@ pub fn render_spreadsheet(
     output
 }
 
+/// `text` with the characters HTML gives meaning to replaced by entities,
+/// so a cell's text shows as typed inside an attribute or an element.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::graph::Graph;
+    use crate::value::ERR;
 
     #[test]
     fn test_column_index_to_name_single_letter() {
@@ -315,10 +316,10 @@ mod tests {
 
     #[test]
     fn test_render_spreadsheet_basic() {
-        let arr = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let arr = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10].map(Value::from).to_vec();
         let cols = 5;
         let rows = 2;
-        let output = render_spreadsheet(0, 0, &arr, cols, rows);
+        let output = render_spreadsheet(0, 0, &arr, cols, rows, None);
 
         assert!(output.contains(r#"<table border="1" style="border-collapse:collapse;">"#));
         assert!(output.contains(r#"<th style="padding: 5px;">A</th>"#));
@@ -339,10 +340,11 @@ mod tests {
 
     #[test]
     fn test_render_spreadsheet_with_error() {
-        let arr = vec![1, i32::MIN, 3, 4, 5, 6, 7, 8, 9, 10];
+        let mut arr = [1, 0, 3, 4, 5, 6, 7, 8, 9, 10].map(Value::from).to_vec();
+        arr[1] = ERR;
         let cols = 5;
         let rows = 2;
-        let output = render_spreadsheet(0, 0, &arr, cols, rows);
+        let output = render_spreadsheet(0, 0, &arr, cols, rows, None);
 
         assert!(output.contains(
             r#"<td style="padding: 5px;">
@@ -360,10 +362,12 @@ mod tests {
 
     #[test]
     fn test_render_spreadsheet_partial_view() {
-        let arr = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+        let arr = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+            .map(Value::from)
+            .to_vec();
         let cols = 5;
         let rows = 3;
-        let output = render_spreadsheet(1, 1, &arr, cols, rows);
+        let output = render_spreadsheet(1, 1, &arr, cols, rows, None);
 
         assert!(output.contains(r#"<th style="padding: 5px;">B</th>"#));
         assert!(output.contains(r#"<th style="padding: 5px;">C</th>"#));
@@ -381,6 +385,35 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_render_spreadsheet_precision() {
+        let arr = [Value::from(2.5), Value::from(1.0 / 3.0)];
+        let auto = render_spreadsheet(0, 0, &arr, 2, 1, None);
+        assert!(auto.contains(r#"value="2.5""#));
+        assert!(auto.contains(r#"value="0.333333""#));
+        let fixed = render_spreadsheet(0, 0, &arr, 2, 1, Some(2));
+        assert!(fixed.contains(r#"value="2.50""#));
+        assert!(fixed.contains(r#"value="0.33""#));
+    }
+
+    #[test]
+    fn test_render_spreadsheet_decimals() {
+        let price = crate::decimal::Decimal {
+            units: 1250,
+            scale: 2,
+        };
+        let arr = [Value::Decimal(price)];
+        assert!(render_spreadsheet(0, 0, &arr, 1, 1, None).contains(r#"value="12.50""#));
+        assert!(render_spreadsheet(0, 0, &arr, 1, 1, Some(0)).contains(r#"value="12""#));
+    }
+
+    #[test]
+    fn test_render_spreadsheet_escapes_text() {
+        let arr = [Value::text(r#"<b>"x"&'y'</b>"#)];
+        let html = render_spreadsheet(0, 0, &arr, 1, 1, None);
+        assert!(html.contains(r#"value="&lt;b&gt;&quot;x&quot;&amp;&#39;y&#39;&lt;/b&gt;""#));
+    }
+
     #[test]
     fn test_scroller_display_scroll_up() {
         let mut curr_x = 0;
@@ -388,7 +421,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display("w", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
         assert_eq!(curry, 0); // Scrolled up to the top
@@ -402,7 +435,7 @@ mod tests {
         let cols = 10;
         let rows = 20;
         let mut graph = Graph::new();
-        let arr = vec![0; 200];
+        let arr = vec![Value::default(); 200];
 
         scroller_display("s", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
         assert_eq!(curry, 10); // Scrolled down by 10 rows
@@ -416,7 +449,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display("a", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
         assert_eq!(curr_x, 0); // Scrolled left to the start
@@ -430,7 +463,7 @@ mod tests {
         let cols = 20;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 200];
+        let arr = vec![Value::default(); 200];
 
         scroller_display("d", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
         assert_eq!(curr_x, 10); // Scrolled right by 10 columns
@@ -444,7 +477,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display(
             "scroll_to B2",
@@ -466,7 +499,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display(
             "scroll_to Z99",
@@ -488,7 +521,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display(
             "invalid_command",
@@ -510,7 +543,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         // Valid cell within bounds
         scroller_display(
@@ -1,51 +1,34 @@
 use crate::graph::Graph;
 use crate::input_parser::cell_parser;
+use crate::value::Value;
+use crate::view::Viewport;
 /// Print a 10×10 “window” of the spreadsheet, starting at column `curr_x` and row `curry`.
 ///
 /// Prints column-letter headers (A, B, …, AA, …) and up to 10 rows/columns of values
-/// (or `ERR` for `i32::MIN`).
+/// (or `ERR` for error cells), using the default [`Viewport`]; see
+/// [`Spreadsheet::print`](crate::spreadsheet::Spreadsheet::print) for a sheet's
+/// own view settings.
 ///
 /// # Parameters
 ///
 /// - `curr_x`, `curry`: the top-left corner of the viewport (zero-based indices)
 /// - `arr`: the full row-major cell buffer
 /// - `cols`, `rows`: the full sheet dimensions
-use std::cmp;
-pub fn printer(curr_x: usize, curry: usize, arr: &[i32], cols: usize, rows: usize) {
-    // Print column headers
-    print!("      ");
-    let num_cols = cmp::min(cols.saturating_sub(curr_x), 10);
-    for i in 0..num_cols {
-        let mut val = (curr_x + i + 1) as i32; // 1-indexed value
-        let mut col_str = String::new();
-        while val > 0 {
-            val -= 1;
-            let letter = ((val % 26) as u8 + b'A') as char;
-            col_str.push(letter);
-            val /= 26;
-        }
-        // Reverse to get the correct order
-        let header: String = col_str.chars().rev().collect();
-        print!("{:<10}", header);
-    }
-    println!();
-
-    // Print rows with cell values
-    let num_rows = cmp::min(rows.saturating_sub(curry), 10);
-    for j in 0..num_rows {
-        // Print row number (1-indexed) left aligned in width 3
-        print!("{:<3}   ", curry + j + 1);
-        for i in 0..num_cols {
-            let index = (curr_x + i) + cols * (curry + j);
-            let value = arr[index];
-            if value == i32::MIN {
-                print!("{:<10}", "ERR");
-            } else {
-                print!("{:<10}", value);
-            }
-        }
-        println!();
-    }
+/// - `precision`: decimals shown for numbers, or `None` for whole numbers as
+///   integers and fractions rounded to a few places
+pub fn printer(
+    curr_x: usize,
+    curry: usize,
+    arr: &[Value],
+    cols: usize,
+    rows: usize,
+    precision: Option<usize>,
+) {
+    let view = Viewport {
+        precision,
+        ..Viewport::default()
+    };
+    print!("{}", view.render(curr_x, curry, arr, cols, rows));
 }
 /// Scroll the visible “window” by page or jump to a specific cell.
 ///
@@ -99,7 +82,7 @@ pub fn printer(curr_x: usize, curry: usize, arr: &[i32], cols: usize, rows: usiz
 /// ```
 pub fn scroller_display(
     cmd: &str,
-    _arr: &[i32],
+    _arr: &[Value],
     curr_x: &mut usize,
     curry: &mut usize,
     cols: usize,
@@ -190,6 +173,7 @@ pub fn column_index_to_name(mut col: usize) -> String {
 /// - `_curr_x`, `_curr_y`: not yet used (always renders all rows/cols)
 /// - `arr`: full row-major buffer
 /// - `cols`, `rows`: sheet dimensions
+/// - `precision`: decimals shown for numbers (see [`printer`])
 ///
 /// # Returns
 ///
@@ -197,9 +181,10 @@ pub fn column_index_to_name(mut col: usize) -> String {
 pub fn render_spreadsheet(
     _curr_x: usize,
     _curr_y: usize,
-    arr: &[i32],
+    arr: &[Value],
     cols: usize,
     rows: usize,
+    precision: Option<usize>,
 ) -> String {
     let mut output = String::new();
 
@@ -231,11 +216,7 @@ pub fn render_spreadsheet(
         for col in 0..cols {
             let index = row * cols + col;
             let cell_label = format!("{}{}", column_index_to_name(col), row_num);
-            let cell_value = if arr[index] == i32::MIN {
-                "ERR".to_string()
-            } else {
-                arr[index].to_string()
-            };
+            let cell_value = escape_html(&arr[index].format(precision));
 
             output.push_str(&format!(
                 r#"<td style="padding: 5px;">
@@ -258,10 +239,28 @@ pub fn render_spreadsheet(
     output
 }
 
+/// `text` with the characters HTML gives meaning to replaced by entities,
+/// so a cell's text shows as typed inside an attribute or an element.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::graph::Graph;
+    use crate::value::ERR;
 
     #[test]
     fn test_column_index_to_name_single_letter() {
@@ -284,10 +283,10 @@ mod tests {
 
     #[test]
     fn test_render_spreadsheet_basic() {
-        let arr = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let arr = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10].map(Value::from).to_vec();
         let cols = 5;
         let rows = 2;
-        let output = render_spreadsheet(0, 0, &arr, cols, rows);
+        let output = render_spreadsheet(0, 0, &arr, cols, rows, None);
 
         assert!(output.contains(r#"<table border="1" style="border-collapse:collapse;">"#));
         assert!(output.contains(r#"<th style="padding: 5px;">A</th>"#));
@@ -308,10 +307,11 @@ mod tests {
 
     #[test]
     fn test_render_spreadsheet_with_error() {
-        let arr = vec![1, i32::MIN, 3, 4, 5, 6, 7, 8, 9, 10];
+        let mut arr = [1, 0, 3, 4, 5, 6, 7, 8, 9, 10].map(Value::from).to_vec();
+        arr[1] = ERR;
         let cols = 5;
         let rows = 2;
-        let output = render_spreadsheet(0, 0, &arr, cols, rows);
+        let output = render_spreadsheet(0, 0, &arr, cols, rows, None);
 
         assert!(output.contains(
             r#"<td style="padding: 5px;">
@@ -329,10 +329,12 @@ mod tests {
 
     #[test]
     fn test_render_spreadsheet_partial_view() {
-        let arr = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+        let arr = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+            .map(Value::from)
+            .to_vec();
         let cols = 5;
         let rows = 3;
-        let output = render_spreadsheet(1, 1, &arr, cols, rows);
+        let output = render_spreadsheet(1, 1, &arr, cols, rows, None);
 
         assert!(output.contains(r#"<th style="padding: 5px;">B</th>"#));
         assert!(output.contains(r#"<th style="padding: 5px;">C</th>"#));
@@ -350,6 +352,35 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_render_spreadsheet_precision() {
+        let arr = [Value::from(2.5), Value::from(1.0 / 3.0)];
+        let auto = render_spreadsheet(0, 0, &arr, 2, 1, None);
+        assert!(auto.contains(r#"value="2.5""#));
+        assert!(auto.contains(r#"value="0.333333""#));
+        let fixed = render_spreadsheet(0, 0, &arr, 2, 1, Some(2));
+        assert!(fixed.contains(r#"value="2.50""#));
+        assert!(fixed.contains(r#"value="0.33""#));
+    }
+
+    #[test]
+    fn test_render_spreadsheet_decimals() {
+        let price = crate::decimal::Decimal {
+            units: 1250,
+            scale: 2,
+        };
+        let arr = [Value::Decimal(price)];
+        assert!(render_spreadsheet(0, 0, &arr, 1, 1, None).contains(r#"value="12.50""#));
+        assert!(render_spreadsheet(0, 0, &arr, 1, 1, Some(0)).contains(r#"value="12""#));
+    }
+
+    #[test]
+    fn test_render_spreadsheet_escapes_text() {
+        let arr = [Value::text(r#"<b>"x"&'y'</b>"#)];
+        let html = render_spreadsheet(0, 0, &arr, 1, 1, None);
+        assert!(html.contains(r#"value="&lt;b&gt;&quot;x&quot;&amp;&#39;y&#39;&lt;/b&gt;""#));
+    }
+
     #[test]
     fn test_scroller_display_scroll_up() {
         let mut curr_x = 0;
@@ -357,7 +388,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display("w", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
         assert_eq!(curry, 0); // Scrolled up to the top
@@ -371,7 +402,7 @@ mod tests {
         let cols = 10;
         let rows = 20;
         let mut graph = Graph::new();
-        let arr = vec![0; 200];
+        let arr = vec![Value::default(); 200];
 
         scroller_display("s", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
         assert_eq!(curry, 10); // Scrolled down by 10 rows
@@ -385,7 +416,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display("a", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
         assert_eq!(curr_x, 0); // Scrolled left to the start
@@ -399,7 +430,7 @@ mod tests {
         let cols = 20;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 200];
+        let arr = vec![Value::default(); 200];
 
         scroller_display("d", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
         assert_eq!(curr_x, 10); // Scrolled right by 10 columns
@@ -413,7 +444,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display(
             "scroll_to B2",
@@ -435,7 +466,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display(
             "scroll_to Z99",
@@ -457,7 +488,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         scroller_display(
             "invalid_command",
@@ -479,7 +510,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; 100];
+        let arr = vec![Value::default(); 100];
 
         // Valid cell within bounds
         scroller_display(
@@ -571,11 +602,13 @@ mod tests {
     fn test_printer_basic() {
         let cols = 5;
         let rows = 5;
-        let arr = vec![
+        let arr = [
             1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
             25,
-        ];
-        printer(0, 0, &arr, cols, rows);
+        ]
+        .map(Value::from)
+        .to_vec();
+        printer(0, 0, &arr, cols, rows, None);
         // This is a visual test, but we can capture output if needed
     }
 
@@ -583,8 +616,9 @@ mod tests {
     fn test_printer_with_errors() {
         let cols = 3;
         let rows = 3;
-        let arr = vec![1, i32::MIN, 3, 4, 5, 6, 7, 8, 9];
-        printer(0, 0, &arr, cols, rows);
+        let mut arr = [1, 0, 3, 4, 5, 6, 7, 8, 9].map(Value::from).to_vec();
+        arr[1] = ERR;
+        printer(0, 0, &arr, cols, rows, None);
         // Visual test for ERR display
     }
 
@@ -592,8 +626,8 @@ mod tests {
     fn test_printer_partial_view() {
         let cols = 10;
         let rows = 20;
-        let arr = vec![0; cols * rows];
-        printer(5, 10, &arr, cols, rows);
+        let arr = vec![Value::default(); cols * rows];
+        printer(5, 10, &arr, cols, rows, None);
         // Test partial window display
     }
 
@@ -604,7 +638,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; cols * rows];
+        let arr = vec![Value::default(); cols * rows];
 
         // Test scroll up at top boundary
         scroller_display("w", &arr, &mut curr_x, &mut curry, cols, rows, &mut graph);
@@ -633,8 +667,8 @@ mod tests {
     fn test_render_spreadsheet_empty() {
         let cols = 0;
         let rows = 0;
-        let arr = vec![];
-        let output = render_spreadsheet(0, 0, &arr, cols, rows);
+        let arr: Vec<Value> = vec![];
+        let output = render_spreadsheet(0, 0, &arr, cols, rows, None);
         assert!(output.contains("<table"));
         assert!(!output.contains("<td")); // No cells should be rendered
     }
@@ -643,8 +677,8 @@ mod tests {
     fn test_render_spreadsheet_large() {
         let cols = 30;
         let rows = 30;
-        let arr = vec![0; cols * rows];
-        let output = render_spreadsheet(0, 0, &arr, cols, rows);
+        let arr = vec![Value::default(); cols * rows];
+        let output = render_spreadsheet(0, 0, &arr, cols, rows, None);
         assert!(output.contains("AA")); // Should contain multi-letter column headers
         assert!(output.contains("30")); // Should contain max row number
     }
@@ -656,7 +690,7 @@ mod tests {
         let cols = 10;
         let rows = 10;
         let mut graph = Graph::new();
-        let arr = vec![0; cols * rows];
+        let arr = vec![Value::default(); cols * rows];
 
         // Invalid format
         scroller_display(
@@ -688,12 +722,12 @@ mod tests {
     #[test]
     fn test_printer_edge_cases() {
         // Test empty spreadsheet
-        printer(0, 0, &[], 0, 0);
+        printer(0, 0, &[], 0, 0, None);
 
         // Test single cell spreadsheet
-        printer(0, 0, &[42], 1, 1);
+        printer(0, 0, &[Value::from(42)], 1, 1, None);
 
         // Test viewport larger than spreadsheet
-        printer(0, 0, &[1, 2, 3, 4], 2, 2);
+        printer(0, 0, &[1, 2, 3, 4].map(Value::from), 2, 2, None);
     }
 }
@@ -0,0 +1,266 @@
+//! Module `edit`.
+//!
+//! Applies a user edit of the form `A1=EXPR` to a [`Spreadsheet`] the way the
+//! front ends expect it:
+//! - the text is run through [`parser`](crate::input_parser::parser),
+//! - a rejected edit restores the cell's previous formula; the error says
+//!   why the expression does not parse, or names the loop it would close,
+//! - an accepted edit is remembered in `formula_strings` and its previous text
+//!   is pushed on the undo stack.
+//!
+//! Each edit is evaluated under the sheet's
+//! [`Scheduler`](crate::schedule::Scheduler): if it is cancelled or runs out
+//! of time budget, the sheet is left exactly as it was before the edit.
+//!
+//! [`undo`] and [`redo`] move single-cell edits between the two stacks.
+
+use crate::display::column_index_to_name;
+use crate::expr::{is_expr, parse};
+use crate::input_parser::{cell_parser, parser};
+use crate::json::cell_name;
+use crate::spreadsheet::Spreadsheet;
+use crate::trace::{find_cycle, formula_refs};
+
+/// Apply `input` (e.g. `"B2=A1+1"`) to `sheet`, keeping the formula text and
+/// undo history in sync.
+///
+/// Returns the index of the edited cell, or an error message if the cell
+/// reference is invalid or the parser rejected the formula (in which case the
+/// sheet is left as it was).
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// assert_eq!(assign(&mut sheet, "B1=7"), Ok(1));
+/// assert_eq!(sheet.arr[1], 7);
+/// assert_eq!(sheet.formula(1), "B1=7");
+/// assert!(assign(&mut sheet, "Z9=1").is_err());
+/// ```
+pub fn assign(sheet: &mut Spreadsheet, input: &str) -> Result<usize, String> {
+    let eq = input.find('=').ok_or("Invalid formula input")?;
+    let cell = cell_parser(&input[..eq], sheet.cols as i32, sheet.rows as i32);
+    if cell == -1 {
+        return Err("Invalid cell reference".to_string());
+    }
+    let cell = cell as usize;
+
+    let old_formula = sheet.formula(cell).to_string();
+
+    // run parser (0 = OK, non-zero = cycle/error) under the edit's budget
+    sheet.graph.scheduler.begin_edit();
+    let status = parser(sheet, input);
+    if let Some(interrupt) = sheet.graph.scheduler.end_edit() {
+        // the parser has already rolled the cell and its dependents back
+        return Err(interrupt.to_string());
+    }
+    if status != 0 {
+        // error → restore old formula & re-parse it
+        sheet.set_formula(cell, old_formula.clone());
+        let _ = parser(sheet, &old_formula);
+        let rhs = &input[eq + 1..];
+        if is_expr(rhs) {
+            if let Err(e) = parse(rhs, sheet.cols, sheet.rows) {
+                return Err(format!("Formula error: {}", e));
+            }
+        }
+        let refs = formula_refs(sheet, rhs);
+        if let Some(path) = find_cycle(sheet, cell, &refs) {
+            let names: Vec<String> = path.iter().map(|&c| cell_name(c, sheet.cols)).collect();
+            return Err(format!("Formula error: cycle {}", names.join(" → ")));
+        }
+        return Err("Formula error: cycle or invalid input.".to_string());
+    }
+    if old_formula.is_empty() {
+        let default_formula = format!(
+            "{}{}=0",
+            column_index_to_name(cell % sheet.cols),
+            cell / sheet.cols + 1
+        );
+        sheet.undo_stack.push_back((cell, default_formula));
+    } else {
+        sheet.undo_stack.push_back((cell, old_formula));
+    }
+    sheet.set_formula(cell, input.to_string());
+    sheet.redo_stack.clear();
+    Ok(cell)
+}
+
+/// Revert the most recent edit. Returns the index of the restored cell, or
+/// `None` if there is nothing to undo.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::{assign, redo, undo};
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(2, 2);
+/// assign(&mut sheet, "A1=4").unwrap();
+/// assert_eq!(undo(&mut sheet), Some(0));
+/// assert_eq!(sheet.arr[0], 0);
+/// assert_eq!(redo(&mut sheet), Some(0));
+/// assert_eq!(sheet.arr[0], 4);
+/// assert_eq!(redo(&mut sheet), None);
+/// ```
+pub fn undo(sheet: &mut Spreadsheet) -> Option<usize> {
+    let (idx, old_formula) = sheet.undo_stack.pop_back()?;
+    let current_formula = sheet.formula(idx).to_string();
+    sheet.redo_stack.push_back((idx, current_formula));
+    sheet.set_formula(idx, old_formula.clone());
+    parser(sheet, &old_formula);
+    Some(idx)
+}
+
+/// Re-apply the most recently undone edit. Returns the index of the cell, or
+/// `None` if there is nothing to redo.
+pub fn redo(sheet: &mut Spreadsheet) -> Option<usize> {
+    let (idx, redo_formula) = sheet.redo_stack.pop_back()?;
+    let current_formula = sheet.formula(idx).to_string();
+    sheet.undo_stack.push_back((idx, current_formula));
+    sheet.set_formula(idx, redo_formula.clone());
+    parser(sheet, &redo_formula);
+    Some(idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spreadsheet::initialize_spreadsheet;
+    use crate::value::Value;
+
+    #[test]
+    fn test_assign_records_formula_and_undo() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        assert_eq!(assign(&mut sheet, "A1=5"), Ok(0));
+        assert_eq!(assign(&mut sheet, "A1=6"), Ok(0));
+        assert_eq!(sheet.arr[0], 6);
+        assert_eq!(sheet.formula(0), "A1=6");
+        assert_eq!(sheet.undo_stack.len(), 2);
+        assert_eq!(sheet.undo_stack[0], (0, "A1=0".to_string()));
+        assert_eq!(sheet.undo_stack[1], (0, "A1=5".to_string()));
+    }
+
+    #[test]
+    fn test_assign_rejects_cycle_and_keeps_old_formula() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        assign(&mut sheet, "A1=B1+1").unwrap();
+        assert_eq!(
+            assign(&mut sheet, "B1=A1+1"),
+            Err("Formula error: cycle B1 → A1 → B1".to_string())
+        );
+        assert_eq!(sheet.formula(1), "");
+        assert_eq!(sheet.arr[0], 1);
+        assert_eq!(sheet.undo_stack.len(), 1);
+    }
+
+    #[test]
+    fn test_cycle_message_names_the_loop() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        assign(&mut sheet, "B1=A1").unwrap();
+        assign(&mut sheet, "C1=B1*2").unwrap();
+        assert_eq!(
+            assign(&mut sheet, "A1=SUM(C1:C3)"),
+            Err("Formula error: cycle A1 → B1 → C1 → A1".to_string())
+        );
+        assert_eq!(
+            assign(&mut sheet, "D1=D1+1"),
+            Err("Formula error: cycle D1 → D1".to_string())
+        );
+        assert_eq!(
+            assign(&mut sheet, "E1=A1+"),
+            Err("Formula error: cycle or invalid input.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_expression_errors_are_reported() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        assert_eq!(
+            assign(&mut sheet, "A1=IF(B1>1, 2"),
+            Err("Formula error: missing ')' after IF arguments".to_string())
+        );
+        assert_eq!(
+            assign(&mut sheet, "A1=NOPE(B1)+1"),
+            Err("Formula error: unknown function NOPE".to_string())
+        );
+        // a loop is still named once the expression parses
+        assert_eq!(
+            assign(&mut sheet, "A1=IF(A1>1, 2, 3)"),
+            Err("Formula error: cycle A1 → A1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rejected_huge_names_are_plain_errors() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        for input in [
+            "A1=99999999999999999999+",
+            "A1=AAAAAAAAAAAAAAAAAAAA1",
+            "A1=(AAAAAAAA1+1)*2",
+        ] {
+            let err = assign(&mut sheet, input).unwrap_err();
+            assert!(err.starts_with("Formula error: "), "{}: {}", input, err);
+        }
+        assert!(assign(&mut sheet, "AAAAAAAAAAAAAAAAAAAA1=1").is_err());
+    }
+
+    #[test]
+    fn test_sleep_over_budget_is_rolled_back() {
+        use crate::schedule::{Clock, ManualClock};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let clock = ManualClock::default();
+        let mut sheet = initialize_spreadsheet(3, 3);
+        sheet.graph.scheduler.set_clock(Arc::new(clock.clone()));
+        sheet
+            .graph
+            .scheduler
+            .set_budget(Some(Duration::from_secs(5)));
+        assign(&mut sheet, "A1=2").unwrap();
+        assign(&mut sheet, "B1=SLEEP(A1)").unwrap();
+        assign(&mut sheet, "C1=B1+1").unwrap();
+        assert_eq!(clock.now(), Duration::from_secs(2));
+
+        assert_eq!(
+            assign(&mut sheet, "B1=SLEEP(9)"),
+            Err("Evaluation exceeded the 5.0s time budget; edit rolled back".to_string())
+        );
+        assert_eq!(clock.now(), Duration::from_secs(7));
+        assert_eq!(sheet.arr[1..=2], [Value::from(2), Value::from(3)]);
+        assert_eq!(sheet.formula(1), "B1=SLEEP(A1)");
+        assert_eq!(sheet.undo_stack.len(), 3);
+        // the old formula still reacts to its input
+        assign(&mut sheet, "A1=3").unwrap();
+        assert_eq!(sheet.arr[1..=2], [Value::from(3), Value::from(4)]);
+    }
+
+    #[test]
+    fn test_cancelled_recalculation_keeps_old_values() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        assign(&mut sheet, "A1=1").unwrap();
+        assign(&mut sheet, "B1=A1*10").unwrap();
+        assign(&mut sheet, "C1=SUM(A1:B1)").unwrap();
+        // a token that is already set when recalculation starts stops it
+        let token = sheet.graph.scheduler.cancel_token();
+        sheet.graph.scheduler.begin_edit();
+        token.cancel();
+        assert_ne!(parser(&mut sheet, "A1=5"), 0);
+        assert!(sheet.graph.scheduler.end_edit().is_some());
+        assert_eq!(&sheet.arr[..3], &[1, 10, 11]);
+        assert_eq!(sheet.formula_array[0].p1, 1);
+        assign(&mut sheet, "A1=5").unwrap();
+        assert_eq!(&sheet.arr[..3], &[5, 50, 55]);
+    }
+
+    #[test]
+    fn test_assign_invalid_cell() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        assert!(assign(&mut sheet, "C3=1").is_err());
+        assert!(assign(&mut sheet, "no equals").is_err());
+    }
+}
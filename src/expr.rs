@@ -0,0 +1,1546 @@
+//! Module `expr`.
+//!
+//! Formulas that do not fit the single operation a [`Formula`] record holds:
+//! nested arithmetic such as `(A1+B1)*2`, comparisons and function calls such
+//! as `IF(AND(A1>0, B1<>0), A1/B1, 0)`. A cell holding one has op type
+//! [`EXPR`] and its parsed [`Expr`] in [`Graph::exprs`].
+//!
+//! - `"quoted"` text is a value, and a right-hand side starting with `'`
+//!   makes the rest of it, spaces included, a label: `A1='Total due`.
+//!   `a & b` joins numbers and text into text; arithmetic on text is `ERR`.
+//! - `=`, `<>`, `<`, `<=`, `>`, `>=` compare numbers, or texts ignoring
+//!   case, and give `TRUE` or `FALSE`; they bind more loosely than
+//!   arithmetic.
+//! - `IF(cond, then, [else])` evaluates only the branch it takes; a missing
+//!   else branch gives `FALSE`.
+//! - `AND`, `OR` and `NOT` combine conditions; any nonzero number is true.
+//! - `IFERROR(x, fallback)`, `ISERROR(x)` and `ISBLANK(cell)` look at a value
+//!   without passing its error on.
+//! - `MIN`, `MAX`, `AVG`, `SUM` and `STDEV` take ranges and single values.
+//! - `COUNT` counts numbers, `COUNTA` cells that are not empty and
+//!   `COUNTBLANK(range)` empty ones.
+//! - `COUNTIF(range, criterion)`, `SUMIF(range, criterion, [sum_range])` and
+//!   `AVERAGEIF` look only at the cells meeting a [`Criterion`] such as
+//!   `">10"`; `COUNTIFS(range1, criterion1, ...)`, `SUMIFS(sum_range, range1,
+//!   criterion1, ...)` and `AVERAGEIFS` at those meeting every one.
+//! - `VLOOKUP(key, table, column, [approximate])` and `HLOOKUP` find a key in
+//!   a table's first column (row); `MATCH(key, range, [type])` gives its
+//!   position, `INDEX(range, row, [column])` the value at a position and
+//!   `XLOOKUP(key, range, results, [if_not_found], [mode])` the matching
+//!   result. A key that is not found is `#N/A` (see [`crate::lookup`]).
+//! - `INDIRECT(text)` reads the cell or range named by `text`, e.g.
+//!   `INDIRECT("B"&A1)`, and `OFFSET(ref, rows, cols, [height], [width])`
+//!   the one `rows` down and `cols` right of `ref`. Both work wherever a cell
+//!   does and in the range arguments of `MIN`, `MAX`, `AVG`, `SUM`, `STDEV`,
+//!   `COUNT`, `COUNTA`, `AND` and `OR`.
+//! - `ABS`, `ROUND`, `MOD`, `SQRT`, `SIN` and the other functions of
+//!   [`crate::math`] take single values.
+//! - `CONCAT`, `LEN`, `LEFT`, `MID`, `SUBSTITUTE`, `TEXT`, `VALUE` and the
+//!   other functions of [`crate::text`] work on text; an empty cell is the
+//!   empty text.
+//! - `DATE(2024, 5, 1)` or `DATE("2024-05-01")` enters a date (a bare
+//!   `2024-05-01` is a subtraction); `EDATE`, `DATEDIF`,
+//!   `NETWORKDAYS(start, end, [holidays])`, `TODAY()` and the other
+//!   functions of [`crate::date`] work on dates.
+//!
+//! Every cell the formula names, in branches not taken too, is a precedent,
+//! so the cell is recomputed whenever any of them changes. The cells
+//! `INDIRECT` and `OFFSET` read are only known once the formula has been
+//! evaluated; each evaluation reports them in [`Env::reads`] and the graph
+//! links them (see [`Graph::dynamic`]).
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use crate::criteria::Criterion;
+use crate::date::{self, Date};
+use crate::graph::{add_formula, aggregate_in, arith_in, delete_edge, recalculate, Formula, Graph};
+use crate::input_parser::cell_parser;
+use crate::lookup::{find, MatchMode};
+use crate::math::{self, Math};
+use crate::text::{self, as_text, Text};
+use crate::value::{decimal_parts, NumberMode, Value, ERR, NA};
+
+/// Op type of a cell whose formula is an [`Expr`].
+pub const EXPR: i32 = 19;
+
+/// A comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A function callable in an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Func {
+    If,
+    And,
+    Or,
+    Not,
+    IfError,
+    IsError,
+    IsBlank,
+    /// A range formula, by its op type (9–13, see [`Formula`]).
+    Aggregate(i32),
+    Count,
+    CountA,
+    CountBlank,
+    /// `COUNTIFS(range1, criterion1, ...)`, and `COUNTIF`.
+    CountIfs,
+    /// `SUMIFS(sum_range, range1, criterion1, ...)`, and `SUMIF` with its
+    /// sum range moved to the front.
+    SumIfs,
+    /// Like [`Func::SumIfs`], for `AVERAGEIFS` and `AVERAGEIF`.
+    AverageIfs,
+    VLookup,
+    HLookup,
+    Index,
+    Match,
+    XLookup,
+    Indirect,
+    Offset,
+    Math(Math),
+    Text(Text),
+    Date(Date),
+}
+
+/// Function names with their fewest and most arguments.
+const FUNCTIONS: &[(&str, Func, usize, usize)] = &[
+    ("ABS", Func::Math(Math::Abs), 1, 1),
+    ("ACOS", Func::Math(Math::Acos), 1, 1),
+    ("AND", Func::And, 1, usize::MAX),
+    ("ASIN", Func::Math(Math::Asin), 1, 1),
+    ("ATAN", Func::Math(Math::Atan), 1, 1),
+    ("ATAN2", Func::Math(Math::Atan2), 2, 2),
+    ("AVERAGEIF", Func::AverageIfs, 2, 3),
+    ("AVERAGEIFS", Func::AverageIfs, 3, usize::MAX),
+    ("AVG", Func::Aggregate(11), 1, usize::MAX),
+    ("CONCAT", Func::Text(Text::Concat), 1, usize::MAX),
+    ("COS", Func::Math(Math::Cos), 1, 1),
+    ("COUNT", Func::Count, 1, usize::MAX),
+    ("COUNTA", Func::CountA, 1, usize::MAX),
+    ("COUNTBLANK", Func::CountBlank, 1, 1),
+    ("COUNTIF", Func::CountIfs, 2, 2),
+    ("COUNTIFS", Func::CountIfs, 2, usize::MAX),
+    ("DATE", Func::Date(Date::Date), 1, 3),
+    ("DATEDIF", Func::Date(Date::DateDif), 3, 3),
+    ("DAY", Func::Date(Date::Day), 1, 1),
+    ("DEGREES", Func::Math(Math::Degrees), 1, 1),
+    ("EDATE", Func::Date(Date::EDate), 2, 2),
+    ("EOMONTH", Func::Date(Date::EoMonth), 2, 2),
+    ("EXP", Func::Math(Math::Exp), 1, 1),
+    ("FIND", Func::Text(Text::Find), 2, 3),
+    ("HLOOKUP", Func::HLookup, 3, 4),
+    ("IF", Func::If, 2, 3),
+    ("IFERROR", Func::IfError, 2, 2),
+    ("INDEX", Func::Index, 2, 3),
+    ("INDIRECT", Func::Indirect, 1, 1),
+    ("INT", Func::Math(Math::Int), 1, 1),
+    ("ISBLANK", Func::IsBlank, 1, 1),
+    ("ISERROR", Func::IsError, 1, 1),
+    ("LEFT", Func::Text(Text::Left), 1, 2),
+    ("LEN", Func::Text(Text::Len), 1, 1),
+    ("LN", Func::Math(Math::Ln), 1, 1),
+    ("LOG10", Func::Math(Math::Log10), 1, 1),
+    ("LOWER", Func::Text(Text::Lower), 1, 1),
+    ("MATCH", Func::Match, 2, 3),
+    ("MAX", Func::Aggregate(10), 1, usize::MAX),
+    ("MID", Func::Text(Text::Mid), 3, 3),
+    ("MIN", Func::Aggregate(9), 1, usize::MAX),
+    ("MOD", Func::Math(Math::Mod), 2, 2),
+    ("MONTH", Func::Date(Date::Month), 1, 1),
+    ("NETWORKDAYS", Func::Date(Date::NetworkDays), 2, usize::MAX),
+    ("NOT", Func::Not, 1, 1),
+    ("NOW", Func::Date(Date::Now), 0, 0),
+    ("OFFSET", Func::Offset, 3, 5),
+    ("OR", Func::Or, 1, usize::MAX),
+    ("PI", Func::Math(Math::Pi), 0, 0),
+    ("POWER", Func::Math(Math::Power), 2, 2),
+    ("RADIANS", Func::Math(Math::Radians), 1, 1),
+    ("RIGHT", Func::Text(Text::Right), 1, 2),
+    ("ROUND", Func::Math(Math::Round), 1, 2),
+    ("ROUNDDOWN", Func::Math(Math::RoundDown), 1, 2),
+    ("ROUNDUP", Func::Math(Math::RoundUp), 1, 2),
+    ("SIGN", Func::Math(Math::Sign), 1, 1),
+    ("SIN", Func::Math(Math::Sin), 1, 1),
+    ("SQRT", Func::Math(Math::Sqrt), 1, 1),
+    ("STDEV", Func::Aggregate(13), 1, usize::MAX),
+    ("SUBSTITUTE", Func::Text(Text::Substitute), 3, 4),
+    ("SUM", Func::Aggregate(12), 1, usize::MAX),
+    ("SUMIF", Func::SumIfs, 2, 3),
+    ("SUMIFS", Func::SumIfs, 3, usize::MAX),
+    ("TAN", Func::Math(Math::Tan), 1, 1),
+    ("TEXT", Func::Text(Text::Format), 2, 2),
+    ("TODAY", Func::Date(Date::Today), 0, 0),
+    ("TRIM", Func::Text(Text::Trim), 1, 1),
+    ("UPPER", Func::Text(Text::Upper), 1, 1),
+    ("VALUE", Func::Text(Text::Value), 1, 1),
+    ("VLOOKUP", Func::VLookup, 3, 4),
+    ("WEEKDAY", Func::Date(Date::Weekday), 1, 2),
+    ("XLOOKUP", Func::XLookup, 3, 5),
+    ("YEAR", Func::Date(Date::Year), 1, 1),
+];
+
+/// A parsed formula.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Bool(bool),
+    /// Quoted text, or a whole right-hand side entered as `'text`.
+    Text(String),
+    /// A cell, by index.
+    Cell(usize),
+    /// The rectangle between two corner cells; only a function argument.
+    Range(usize, usize),
+    Neg(Box<Expr>),
+    /// `+`, `-`, `*` or `/`.
+    Arith(char, Box<Expr>, Box<Expr>),
+    Compare(Cmp, Box<Expr>, Box<Expr>),
+    /// `a & b`, joined as text.
+    Concat(Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+/// What an expression reads while it is evaluated.
+pub struct Env<'a> {
+    pub arr: &'a [Value],
+    pub formulas: &'a [Formula],
+    pub cols: usize,
+    pub numbers: NumberMode,
+    /// The date and time `TODAY()` and `NOW()` give, as a serial.
+    pub now: f64,
+    /// Cells read through `INDIRECT` or `OFFSET` so far.
+    pub reads: RefCell<Vec<usize>>,
+}
+
+impl Env<'_> {
+    /// Whether cell `c` is empty: it has no formula, and reads as 0.
+    pub fn blank(&self, c: usize) -> bool {
+        self.formulas[c]
+            == Formula {
+                op_type: 0,
+                p1: 0,
+                p2: 0,
+            }
+    }
+}
+
+impl Cmp {
+    /// Whether `a op b` holds, comparing texts ignoring case (see
+    /// [`Value::collate`]); values that cannot be ordered (errors, or a
+    /// text and a number) only satisfy `<>`.
+    pub fn holds(self, a: &Value, b: &Value) -> bool {
+        self.orders(a.collate(b))
+    }
+
+    /// Whether two values ordered as `order` satisfy this operator.
+    pub fn orders(self, order: Option<Ordering>) -> bool {
+        match self {
+            Cmp::Eq => order == Some(Ordering::Equal),
+            Cmp::Ne => order != Some(Ordering::Equal),
+            Cmp::Lt => order == Some(Ordering::Less),
+            Cmp::Le => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+            Cmp::Gt => order == Some(Ordering::Greater),
+            Cmp::Ge => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+/// Whether the right-hand side `rhs` needs this module rather than one of
+/// the fixed formula shapes the parser handles itself: it is text, compares,
+/// nests, chains operators, calls anything but a single range function or
+/// has a literal too large for an `i32`.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::expr::is_expr;
+///
+/// assert!(is_expr("IF(A1>2,1,0)"));
+/// assert!(is_expr("A1+B1+C1"));
+/// assert!(is_expr("A1*0.5"));
+/// assert!(!is_expr("A1*-2"));
+/// assert!(!is_expr("SUM(A1:A9)"));
+/// assert!(!is_expr("2.5"));
+/// assert!(is_expr("'Total"));
+/// assert!(is_expr(r#"A1&" kg""#));
+/// assert!(is_expr("3000000000"));
+/// assert!(is_expr("A1*3000000000"));
+/// ```
+pub fn is_expr(rhs: &str) -> bool {
+    let rhs = rhs.trim();
+    if rhs.starts_with('\'') || rhs.contains(['"', '&', '<', '>', '=', ',']) {
+        return true;
+    }
+    if rhs == "TRUE" || rhs == "FALSE" {
+        return true;
+    }
+    // the fixed shapes keep literals in `i32`s; larger ones are read here
+    let literals = rhs.split(|c: char| !c.is_ascii_alphanumeric() && c != '.');
+    if literals
+        .filter(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .any(|word| decimal_parts(word).is_none())
+    {
+        return true;
+    }
+    if let Some(open) = rhs.find('(') {
+        let single_call = ["MIN", "MAX", "AVG", "SUM", "STDEV", "SLEEP"].contains(&&rhs[..open])
+            && rhs.matches('(').count() == 1
+            && rhs.find(')').is_none_or(|close| close == rhs.len() - 1);
+        return !single_call;
+    }
+    // a sign right after an operator is not another operation
+    let mut binary = 0;
+    let mut after_operand = false;
+    for c in rhs.chars().filter(|c| !c.is_whitespace()) {
+        if "+-*/".contains(c) {
+            binary += after_operand as usize;
+            after_operand = false;
+        } else {
+            after_operand = true;
+        }
+    }
+    binary > 1 || (binary == 1 && rhs.contains('.'))
+}
+
+/// Parse the right-hand side of `A1=...` on a sheet of `rows` × `cols`.
+///
+/// # Errors
+///
+/// Returns a message for malformed text, unknown functions, wrong argument
+/// counts and cells outside the sheet.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::expr::{parse, Cmp, Expr};
+///
+/// let e = parse("A1>=2", 3, 3).unwrap();
+/// assert_eq!(
+///     e,
+///     Expr::Compare(Cmp::Ge, Box::new(Expr::Cell(0)), Box::new(Expr::Number(2.0)))
+/// );
+/// assert!(parse("IF(A1)", 3, 3).is_err());
+/// assert!(parse("A1+", 3, 3).is_err());
+/// ```
+pub fn parse(text: &str, cols: usize, rows: usize) -> Result<Expr, String> {
+    if let Some(label) = text.trim_start().strip_prefix('\'') {
+        return Ok(Expr::Text(label.to_string()));
+    }
+    let mut p = Parser {
+        src: text.as_bytes(),
+        pos: 0,
+        cols,
+        rows,
+    };
+    let e = p.compare()?;
+    p.skip_spaces();
+    if p.pos < p.src.len() {
+        return Err(format!("unexpected '{}'", p.src[p.pos] as char));
+    }
+    if matches!(e, Expr::Range(..)) {
+        return Err("a range needs a function".to_string());
+    }
+    Ok(e)
+}
+
+/// The index of cell `name` on a sheet of `rows` × `cols`.
+pub(crate) fn cell_index(name: &str, cols: usize, rows: usize) -> Option<usize> {
+    // longer names cannot be on the sheet and would overflow the parser
+    if name.len() > 12 {
+        return None;
+    }
+    usize::try_from(cell_parser(name, cols as i32, rows as i32)).ok()
+}
+
+/// The top-left and bottom-right corners of the rectangle spanned by cells
+/// `a` and `b`.
+fn rectangle(a: usize, b: usize, cols: usize) -> (usize, usize) {
+    let (r1, c1) = (a / cols, a % cols);
+    let (r2, c2) = (b / cols, b % cols);
+    (
+        r1.min(r2) * cols + c1.min(c2),
+        r1.max(r2) * cols + c1.max(c2),
+    )
+}
+
+/// The corners of a cell or range argument.
+fn corners(e: &Expr) -> Option<(usize, usize)> {
+    match *e {
+        Expr::Cell(c) => Some((c, c)),
+        Expr::Range(start, end) => Some((start, end)),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    cols: usize,
+    rows: usize,
+}
+
+impl Parser<'_> {
+    fn skip_spaces(&mut self) {
+        while self.src.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_spaces();
+        if self.src[self.pos..].starts_with(token.as_bytes()) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let mut left = self.concat()?;
+        loop {
+            // two-character operators first
+            let op = [
+                ("<>", Cmp::Ne),
+                ("<=", Cmp::Le),
+                (">=", Cmp::Ge),
+                ("=", Cmp::Eq),
+                ("<", Cmp::Lt),
+                (">", Cmp::Gt),
+            ]
+            .into_iter()
+            .find(|(token, _)| self.eat(token));
+            let Some((_, op)) = op else {
+                return Ok(left);
+            };
+            let right = self.concat()?;
+            left = Expr::Compare(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn concat(&mut self) -> Result<Expr, String> {
+        let mut left = self.additive()?;
+        while self.eat("&") {
+            let right = self.additive()?;
+            left = Expr::Concat(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut left = self.term()?;
+        loop {
+            let op = if self.eat("+") {
+                '+'
+            } else if self.eat("-") {
+                '-'
+            } else {
+                return Ok(left);
+            };
+            let right = self.term()?;
+            left = Expr::Arith(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                '*'
+            } else if self.eat("/") {
+                '/'
+            } else {
+                return Ok(left);
+            };
+            let right = self.unary()?;
+            left = Expr::Arith(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else if self.eat("+") {
+            self.unary()
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        self.skip_spaces();
+        let start = self.pos;
+        let rest = &self.src[start..];
+        match rest.first() {
+            None => Err("missing operand".to_string()),
+            Some(b'(') => {
+                self.pos += 1;
+                let e = self.compare()?;
+                if !self.eat(")") {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(e)
+            }
+            Some(b'"') => {
+                // a doubled quote stands for one quote
+                let mut text = Vec::new();
+                let mut i = 1;
+                loop {
+                    match rest.get(i) {
+                        None => return Err("missing closing '\"'".to_string()),
+                        Some(b'"') if rest.get(i + 1) == Some(&b'"') => {
+                            text.push(b'"');
+                            i += 2;
+                        }
+                        Some(b'"') => break,
+                        Some(&c) => {
+                            text.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                self.pos += i + 1;
+                Ok(Expr::Text(String::from_utf8_lossy(&text).into_owned()))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let len = rest
+                    .iter()
+                    .position(|c| !(c.is_ascii_digit() || *c == b'.'))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                let text = std::str::from_utf8(&rest[..len]).unwrap_or("");
+                match text.parse::<f64>() {
+                    Ok(x) if !text.ends_with('.') => Ok(Expr::Number(x)),
+                    _ => Err(format!("bad number {}", text)),
+                }
+            }
+            Some(c) if c.is_ascii_uppercase() => {
+                let len = rest
+                    .iter()
+                    .position(|c| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                let name = std::str::from_utf8(&rest[..len]).unwrap_or("");
+                if self.eat("(") {
+                    return self.call(name);
+                }
+                match name {
+                    "TRUE" => return Ok(Expr::Bool(true)),
+                    "FALSE" => return Ok(Expr::Bool(false)),
+                    _ => {}
+                }
+                let first = self.cell(name)?;
+                if !self.eat(":") {
+                    return Ok(Expr::Cell(first));
+                }
+                self.skip_spaces();
+                let rest = &self.src[self.pos..];
+                let len = rest
+                    .iter()
+                    .position(|c| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                let second = self.cell(std::str::from_utf8(&rest[..len]).unwrap_or(""))?;
+                let (start, end) = rectangle(first, second, self.cols);
+                Ok(Expr::Range(start, end))
+            }
+            Some(&c) => Err(format!("unexpected '{}'", c as char)),
+        }
+    }
+
+    /// The index of cell `name`.
+    fn cell(&self, name: &str) -> Result<usize, String> {
+        cell_index(name, self.cols, self.rows).ok_or(format!("invalid cell {}", name))
+    }
+
+    /// The arguments of function `name`, whose `(` has been read.
+    fn call(&mut self, name: &str) -> Result<Expr, String> {
+        let &(_, func, min, max) = FUNCTIONS
+            .iter()
+            .find(|(n, ..)| *n == name)
+            .ok_or(format!("unknown function {}", name))?;
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.compare()?);
+                if self.eat(")") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return Err(format!("missing ')' after {} arguments", name));
+                }
+            }
+        }
+        if args.len() < min || args.len() > max {
+            return Err(format!("wrong number of arguments to {}", name));
+        }
+        if matches!(name, "SUMIF" | "AVERAGEIF") {
+            let sum = if args.len() == 3 {
+                let sum = args.remove(2);
+                self.resize(&sum, &args[0])?
+            } else {
+                args[0].clone()
+            };
+            args.insert(0, sum);
+        }
+        // the arguments that must be a cell or range of the same shape
+        let areas: Vec<usize> = match func {
+            Func::CountBlank | Func::Index | Func::Offset => vec![0],
+            Func::VLookup | Func::HLookup | Func::Match => vec![1],
+            Func::XLookup => vec![1, 2],
+            Func::CountIfs if args.len() % 2 == 0 => (0..args.len()).step_by(2).collect(),
+            Func::SumIfs | Func::AverageIfs if args.len() % 2 == 1 => std::iter::once(0)
+                .chain((1..args.len()).step_by(2))
+                .collect(),
+            Func::CountIfs | Func::SumIfs | Func::AverageIfs => {
+                return Err(format!("{} takes ranges and criteria in pairs", name))
+            }
+            _ => Vec::new(),
+        };
+        let takes_ranges = matches!(
+            func,
+            Func::And
+                | Func::Or
+                | Func::Aggregate(_)
+                | Func::Count
+                | Func::CountA
+                | Func::Text(Text::Concat)
+                | Func::Date(Date::NetworkDays)
+        );
+        let mut shape = None;
+        for (i, a) in args.iter().enumerate() {
+            if areas.contains(&i) {
+                let (start, end) = corners(a).ok_or(format!("{} needs a range", name))?;
+                let dims = size(start, end, self.cols);
+                if *shape.get_or_insert(dims) != dims {
+                    return Err(format!("{} ranges differ in size", name));
+                }
+                if matches!(func, Func::Match | Func::XLookup) && dims.0 > 1 && dims.1 > 1 {
+                    return Err(format!("{} needs a single row or column", name));
+                }
+            } else if !takes_ranges && matches!(a, Expr::Range(..)) {
+                return Err(format!("{} does not take a range", name));
+            }
+        }
+        Ok(Expr::Call(func, args))
+    }
+
+    /// The range of `like`'s shape starting at the top-left cell of `sum`,
+    /// which is how `SUMIF` reads its sum range.
+    fn resize(&self, sum: &Expr, like: &Expr) -> Result<Expr, String> {
+        let (Some((top_left, _)), Some((start, end))) = (corners(sum), corners(like)) else {
+            return Err("SUMIF needs a range".to_string());
+        };
+        let (r, c) = (top_left / self.cols, top_left % self.cols);
+        let r_end = r + end / self.cols - start / self.cols;
+        let c_end = c + end % self.cols - start % self.cols;
+        if r_end >= self.rows || c_end >= self.cols {
+            return Err("sum range runs off the sheet".to_string());
+        }
+        Ok(Expr::Range(top_left, r_end * self.cols + c_end))
+    }
+}
+
+/// The cells of the rectangle from `start` to `end`, row by row.
+fn cells(start: usize, end: usize, cols: usize) -> impl Iterator<Item = usize> {
+    (start / cols..=end / cols)
+        .flat_map(move |r| (start % cols..=end % cols).map(move |c| r * cols + c))
+}
+
+/// The rows and columns of the rectangle from `start` to `end`.
+fn size(start: usize, end: usize, cols: usize) -> (usize, usize) {
+    (end / cols - start / cols + 1, end % cols - start % cols + 1)
+}
+
+/// `v` as a condition, or the error it holds; text is [`ERR`].
+fn truth(v: Value) -> Result<bool, Value> {
+    match v {
+        Value::Error(_) => Err(v),
+        Value::Text(_) => Err(ERR),
+        Value::Bool(b) => Ok(b),
+        v => Ok(v.number() != Some(0.0)),
+    }
+}
+
+impl Expr {
+    /// Every cell this expression names, in ascending order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::expr::parse;
+    ///
+    /// // both branches count, and the condition
+    /// let e = parse("IF(A1>0, SUM(B1:B2), C1)", 3, 3).unwrap();
+    /// assert_eq!(e.refs(3), vec![0, 1, 2, 4]);
+    /// ```
+    pub fn refs(&self, cols: usize) -> Vec<usize> {
+        let mut found = BTreeSet::new();
+        self.collect_refs(cols, false, &mut found);
+        found.into_iter().collect()
+    }
+
+    /// The cells whose errors can reach this expression's value: those of
+    /// [`Expr::refs`] except the ones only read where `IFERROR` catches an
+    /// error or `ISERROR`/`ISBLANK` test for one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::expr::parse;
+    ///
+    /// let e = parse("IFERROR(A1/B1, C1) + ISERROR(A2)", 3, 3).unwrap();
+    /// assert_eq!(e.error_refs(3), vec![2]);
+    /// ```
+    pub fn error_refs(&self, cols: usize) -> Vec<usize> {
+        let mut found = BTreeSet::new();
+        self.collect_refs(cols, true, &mut found);
+        found.into_iter().collect()
+    }
+
+    /// The references of [`Expr::refs`] before ranges are expanded: the
+    /// cells named on their own, and each range by its two corners.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::expr::parse;
+    ///
+    /// let e = parse("IF(SUM(A1:A3)>0, B1, SUM(A1:A3))", 3, 3).unwrap();
+    /// let (cells, ranges) = e.ref_parts();
+    /// assert_eq!(cells, vec![1]);
+    /// assert_eq!(ranges, vec![(0, 6)]);
+    /// ```
+    pub fn ref_parts(&self) -> (Vec<usize>, Vec<(usize, usize)>) {
+        fn walk(e: &Expr, cells: &mut BTreeSet<usize>, ranges: &mut BTreeSet<(usize, usize)>) {
+            match e {
+                Expr::Number(_) | Expr::Bool(_) | Expr::Text(_) => {}
+                Expr::Cell(c) => {
+                    cells.insert(*c);
+                }
+                Expr::Range(start, end) => {
+                    ranges.insert((*start, *end));
+                }
+                Expr::Neg(a) => walk(a, cells, ranges),
+                Expr::Arith(_, a, b) | Expr::Compare(_, a, b) | Expr::Concat(a, b) => {
+                    walk(a, cells, ranges);
+                    walk(b, cells, ranges);
+                }
+                // OFFSET only takes its reference's position
+                Expr::Call(Func::Offset, args) => {
+                    args[1..].iter().for_each(|a| walk(a, cells, ranges))
+                }
+                Expr::Call(_, args) => args.iter().for_each(|a| walk(a, cells, ranges)),
+            }
+        }
+        let (mut cells, mut ranges) = (BTreeSet::new(), BTreeSet::new());
+        walk(self, &mut cells, &mut ranges);
+        (cells.into_iter().collect(), ranges.into_iter().collect())
+    }
+
+    /// Whether this expression calls `INDIRECT` or `OFFSET`, whose
+    /// precedents [`Expr::refs`] cannot know.
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            Expr::Call(Func::Indirect | Func::Offset, _) => true,
+            Expr::Neg(a) => a.is_dynamic(),
+            Expr::Arith(_, a, b) | Expr::Compare(_, a, b) | Expr::Concat(a, b) => {
+                a.is_dynamic() || b.is_dynamic()
+            }
+            Expr::Call(_, args) => args.iter().any(Expr::is_dynamic),
+            _ => false,
+        }
+    }
+
+    /// Add the cells this expression names to `found`; with `uncaught`,
+    /// skip the arguments whose errors cannot get out.
+    fn collect_refs(&self, cols: usize, uncaught: bool, found: &mut BTreeSet<usize>) {
+        let each = |args: &[Expr], found: &mut BTreeSet<usize>| {
+            args.iter()
+                .for_each(|a| a.collect_refs(cols, uncaught, found))
+        };
+        match self {
+            Expr::Number(_) | Expr::Bool(_) | Expr::Text(_) => {}
+            Expr::Cell(c) => {
+                found.insert(*c);
+            }
+            Expr::Range(start, end) => found.extend(cells(*start, *end, cols)),
+            Expr::Neg(e) => e.collect_refs(cols, uncaught, found),
+            Expr::Arith(_, a, b) | Expr::Compare(_, a, b) | Expr::Concat(a, b) => {
+                a.collect_refs(cols, uncaught, found);
+                b.collect_refs(cols, uncaught, found);
+            }
+            // OFFSET only takes its reference's position
+            Expr::Call(Func::Offset, args) => each(&args[1..], found),
+            Expr::Call(Func::IfError, args) if uncaught => each(&args[1..], found),
+            Expr::Call(Func::IsError | Func::IsBlank, _) if uncaught => {}
+            Expr::Call(_, args) => each(args, found),
+        }
+    }
+
+    /// The value of this expression.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::expr::{parse, Env};
+    /// use lab1::graph::Formula;
+    /// use lab1::value::{NumberMode, Value, ERR};
+    ///
+    /// let arr = [Value::from(4), Value::from(0)];
+    /// let formulas = [Formula { op_type: 0, p1: 4, p2: 0 }; 2];
+    /// let env = Env {
+    ///     arr: &arr,
+    ///     formulas: &formulas,
+    ///     cols: 2,
+    ///     numbers: NumberMode::Float,
+    ///     now: 0.0,
+    ///     reads: Default::default(),
+    /// };
+    /// let eval = |text| parse(text, 2, 1).unwrap().eval(&env);
+    /// assert_eq!(eval("A1>3"), Value::Bool(true));
+    /// assert_eq!(eval("IF(B1=0, 0, A1/B1)"), 0);
+    /// assert_eq!(eval("IFERROR(A1/B1, -1)"), -1);
+    /// assert_eq!(eval("A1/B1>1"), ERR);
+    /// ```
+    pub fn eval(&self, env: &Env) -> Value {
+        match self {
+            Expr::Number(x) => env.numbers.float(*x),
+            Expr::Bool(b) => Value::Bool(*b),
+            Expr::Cell(c) => env.arr[*c].clone(),
+            Expr::Text(text) => Value::text(text),
+            Expr::Concat(a, b) => match (a.text(env), b.text(env)) {
+                (Ok(a), Ok(b)) => Value::text(&(a + &b)),
+                (Err(e), _) | (_, Err(e)) => e,
+            },
+            Expr::Range(..) => ERR,
+            Expr::Neg(e) => arith_in(env.numbers, env.numbers.whole(0), e.eval(env), '-'),
+            Expr::Arith(op, a, b) => arith_in(env.numbers, a.eval(env), b.eval(env), *op),
+            Expr::Compare(op, a, b) => {
+                let (a, b) = (a.eval(env), b.eval(env));
+                if let Some(e) = [&a, &b].into_iter().find(|v| v.is_error()) {
+                    return e.clone();
+                }
+                Value::Bool(op.holds(&a, &b))
+            }
+            Expr::Call(func, args) => call(*func, args, env),
+        }
+    }
+
+    /// The values of this argument: every cell of a range, or the one value.
+    fn values(&self, env: &Env) -> Vec<Value> {
+        self.entries(env).into_iter().map(|(v, _)| v).collect()
+    }
+
+    /// Like [`Expr::values`], with whether each value is an empty cell.
+    fn entries(&self, env: &Env) -> Vec<(Value, bool)> {
+        match self.reference(env) {
+            Some(Ok((start, end))) => cells(start, end, env.cols)
+                .map(|c| (env.arr[c].clone(), env.blank(c)))
+                .collect(),
+            Some(Err(e)) => vec![(e, false)],
+            None => vec![(self.eval(env), false)],
+        }
+    }
+
+    /// The corners of the cells this argument stands for, if it is a cell,
+    /// a range, `INDIRECT` or `OFFSET`, or the error finding them gave.
+    fn reference(&self, env: &Env) -> Option<Result<(usize, usize), Value>> {
+        match self {
+            Expr::Call(func @ (Func::Indirect | Func::Offset), args) => {
+                Some(dynamic(*func, args, env))
+            }
+            e => corners(e).map(Ok),
+        }
+    }
+
+    /// This expression as text (see [`as_text`]); an empty cell is the
+    /// empty text.
+    fn text(&self, env: &Env) -> Result<String, Value> {
+        match self {
+            Expr::Cell(c) if env.blank(*c) => Ok(String::new()),
+            e => operand(e, env).map(|v| as_text(&v)),
+        }
+    }
+}
+
+fn call(func: Func, args: &[Expr], env: &Env) -> Value {
+    let all = || args.iter().flat_map(|a| a.values(env));
+    let result = match func {
+        Func::If => match truth(args[0].eval(env)) {
+            Ok(true) => Ok(args[1].eval(env)),
+            Ok(false) => Ok(args.get(2).map_or(Value::Bool(false), |e| e.eval(env))),
+            Err(e) => Err(e),
+        },
+        Func::And => all()
+            .map(truth)
+            .collect::<Result<Vec<bool>, Value>>()
+            .map(|ts| Value::Bool(ts.iter().all(|&t| t))),
+        Func::Or => all()
+            .map(truth)
+            .collect::<Result<Vec<bool>, Value>>()
+            .map(|ts| Value::Bool(ts.iter().any(|&t| t))),
+        Func::Not => truth(args[0].eval(env)).map(|t| Value::Bool(!t)),
+        Func::IfError => Ok(match args[0].eval(env) {
+            v if v.is_error() => args[1].eval(env),
+            v => v,
+        }),
+        Func::IsError => Ok(Value::Bool(args[0].eval(env).is_error())),
+        Func::IsBlank => Ok(Value::Bool(match args[0] {
+            Expr::Cell(c) => env.blank(c),
+            _ => false,
+        })),
+        Func::Aggregate(op) => Ok(aggregate_in(env.numbers, op, &all().collect::<Vec<_>>())),
+        Func::Count | Func::CountA | Func::CountBlank => {
+            let counted = args
+                .iter()
+                .flat_map(|a| a.entries(env))
+                .filter(|(v, blank)| match func {
+                    Func::Count => !blank && is_number(v),
+                    Func::CountA => !blank,
+                    _ => *blank,
+                })
+                .count();
+            Ok(env.numbers.whole(counted as i64))
+        }
+        Func::CountIfs | Func::SumIfs | Func::AverageIfs => conditional(func, args, env),
+        Func::VLookup | Func::HLookup | Func::Index | Func::Match | Func::XLookup => {
+            lookup(func, args, env)
+        }
+        // a single cell, read like one
+        Func::Indirect | Func::Offset => dynamic(func, args, env).and_then(|(start, end)| {
+            if start == end {
+                Ok(env.arr[start].clone())
+            } else {
+                Err(ERR)
+            }
+        }),
+        Func::Math(f) => Ok(math::apply(
+            f,
+            &args.iter().map(|a| a.eval(env)).collect::<Vec<_>>(),
+            env.numbers,
+        )),
+        // empty cells are empty text, and CONCAT skips them in ranges
+        Func::Text(Text::Concat) => Ok(text::apply(
+            Text::Concat,
+            &args
+                .iter()
+                .flat_map(|a| a.entries(env))
+                .filter(|&(_, blank)| !blank)
+                .map(|(v, _)| v)
+                .collect::<Vec<_>>(),
+            env.numbers,
+        )),
+        Func::Text(f) => Ok(text::apply(
+            f,
+            &args
+                .iter()
+                .map(|a| match a {
+                    Expr::Cell(c) if env.blank(*c) => Value::text(""),
+                    a => a.eval(env),
+                })
+                .collect::<Vec<_>>(),
+            env.numbers,
+        )),
+        // holidays may be ranges, whose empty cells are skipped
+        Func::Date(f) => {
+            let split = match f {
+                Date::NetworkDays => 2,
+                _ => args.len(),
+            };
+            let (dates, holidays) = args.split_at(split);
+            let values: Vec<Value> = dates
+                .iter()
+                .map(|a| a.eval(env))
+                .chain(
+                    holidays
+                        .iter()
+                        .flat_map(|a| a.entries(env))
+                        .filter(|&(_, blank)| !blank)
+                        .map(|(v, _)| v),
+                )
+                .collect();
+            Ok(date::apply(f, &values, env.numbers, env.now))
+        }
+    };
+    result.unwrap_or_else(|e| e)
+}
+
+/// The corners of the cells `INDIRECT` or `OFFSET` refers to, recorded in
+/// [`Env::reads`]. A name that is not a cell or range of the sheet, or an
+/// offset or size leaving it, is [`ERR`].
+fn dynamic(func: Func, args: &[Expr], env: &Env) -> Result<(usize, usize), Value> {
+    let rows = env.arr.len() / env.cols;
+    let (start, end) = if func == Func::Indirect {
+        let name = args[0].text(env)?.trim().to_ascii_uppercase();
+        let (first, second) = name.split_once(':').unwrap_or((&name, &name));
+        match (
+            cell_index(first.trim(), env.cols, rows),
+            cell_index(second.trim(), env.cols, rows),
+        ) {
+            (Some(a), Some(b)) => rectangle(a, b, env.cols),
+            _ => return Err(ERR),
+        }
+    } else {
+        let (start, end) = corners(&args[0]).unwrap_or((0, 0));
+        let (height, width) = size(start, end, env.cols);
+        let down = whole_arg(&args[1], env)?;
+        let right = whole_arg(&args[2], env)?;
+        let height = args
+            .get(3)
+            .map_or(Ok(height as i64), |a| whole_arg(a, env))?;
+        let width = args
+            .get(4)
+            .map_or(Ok(width as i64), |a| whole_arg(a, env))?;
+        let top = (start / env.cols) as i64 + down;
+        let left = (start % env.cols) as i64 + right;
+        let inside = top >= 0 && left >= 0 && height >= 1 && width >= 1;
+        if !inside || top + height > rows as i64 || left + width > env.cols as i64 {
+            return Err(ERR);
+        }
+        let (top, left) = (top as usize, left as usize);
+        (
+            top * env.cols + left,
+            (top + height as usize - 1) * env.cols + left + width as usize - 1,
+        )
+    };
+    env.reads.borrow_mut().extend(cells(start, end, env.cols));
+    Ok((start, end))
+}
+
+/// The value of `e`, or the error it holds.
+fn operand(e: &Expr, env: &Env) -> Result<Value, Value> {
+    match e.eval(env) {
+        v if v.is_error() => Err(v),
+        v => Ok(v),
+    }
+}
+
+/// The value of `e` as a whole number, with any fraction cut off.
+fn whole_arg(e: &Expr, env: &Env) -> Result<i64, Value> {
+    operand(e, env)?
+        .number()
+        .map(|x| x.trunc() as i64)
+        .ok_or(ERR)
+}
+
+/// The lookup functions, whose ranges [`Parser::call`] has checked. A
+/// position outside the table, or an unknown mode, is [`ERR`].
+fn lookup(func: Func, args: &[Expr], env: &Env) -> Result<Value, Value> {
+    // every function but INDEX looks for its first argument
+    let key = || operand(&args[0], env);
+    let mode = |arg: Option<&Expr>, default| match arg.map_or(Ok(default), |a| whole_arg(a, env))? {
+        0 => Ok(MatchMode::Exact),
+        -1 => Ok(MatchMode::NextSmaller),
+        1 => Ok(MatchMode::NextLarger),
+        _ => Err(ERR),
+    };
+    match func {
+        Func::VLookup | Func::HLookup => {
+            let (start, end) = corners(&args[1]).unwrap_or((0, 0));
+            let (height, width) = size(start, end, env.cols);
+            let offset = whole_arg(&args[2], env)?;
+            let mode = if args.get(3).map_or(Ok(true), |a| truth(a.eval(env)))? {
+                MatchMode::NextSmaller
+            } else {
+                MatchMode::Exact
+            };
+            // i counts along the searched column (row), j across the table
+            let vertical = func == Func::VLookup;
+            let at = |i: usize, j: usize| {
+                if vertical {
+                    start + i * env.cols + j
+                } else {
+                    start + j * env.cols + i
+                }
+            };
+            let (len, across) = if vertical {
+                (height, width)
+            } else {
+                (width, height)
+            };
+            if offset < 1 || offset as usize > across {
+                return Err(ERR);
+            }
+            let entries: Vec<(Value, bool)> = (0..len)
+                .map(|i| (env.arr[at(i, 0)].clone(), env.blank(at(i, 0))))
+                .collect();
+            let i = find(&key()?, &entries, mode).ok_or(NA)?;
+            Ok(env.arr[at(i, offset as usize - 1)].clone())
+        }
+        Func::Index => {
+            let (start, end) = corners(&args[0]).unwrap_or((0, 0));
+            let (height, width) = size(start, end, env.cols);
+            let first = whole_arg(&args[1], env)?;
+            // one number picks along a single row or column
+            let (row, col) = match args.get(2) {
+                Some(a) => (first, whole_arg(a, env)?),
+                None if height == 1 => (1, first),
+                None if width == 1 => (first, 1),
+                None => return Err(ERR),
+            };
+            if row < 1 || col < 1 || row as usize > height || col as usize > width {
+                return Err(ERR);
+            }
+            Ok(env.arr[start + (row as usize - 1) * env.cols + col as usize - 1].clone())
+        }
+        Func::Match => {
+            // unlike XLOOKUP's mode, a positive type settles for smaller
+            let mode = match args.get(2).map_or(Ok(1), |a| whole_arg(a, env))?.signum() {
+                1 => MatchMode::NextSmaller,
+                0 => MatchMode::Exact,
+                _ => MatchMode::NextLarger,
+            };
+            let i = find(&key()?, &args[1].entries(env), mode).ok_or(NA)?;
+            Ok(env.numbers.whole(i as i64 + 1))
+        }
+        _ => match find(&key()?, &args[1].entries(env), mode(args.get(4), 0)?) {
+            Some(i) => Ok(args[2].values(env).swap_remove(i)),
+            None => args.get(3).map_or(Err(NA), |e| Ok(e.eval(env))),
+        },
+    }
+}
+
+fn is_number(v: &Value) -> bool {
+    matches!(v, Value::Number(_) | Value::Int(_) | Value::Decimal(_))
+}
+
+/// `COUNTIFS`, `SUMIFS` or `AVERAGEIFS`, whose ranges [`Parser::call`] has
+/// checked to be of one shape. Sums skip empty cells and booleans; an error
+/// in a summed cell, or a criterion that is one, is passed on.
+fn conditional(func: Func, args: &[Expr], env: &Env) -> Result<Value, Value> {
+    let (target, pairs) = match func {
+        Func::CountIfs => (None, args),
+        _ => (Some(&args[0]), &args[1..]),
+    };
+    let area = |e: &Expr| {
+        let (start, end) = corners(e).unwrap_or((0, 0));
+        cells(start, end, env.cols).collect::<Vec<usize>>()
+    };
+    let mut tests = Vec::new();
+    for pair in pairs.chunks(2) {
+        let criterion = Criterion::equal(operand(&pair[1], env)?);
+        tests.push((area(&pair[0]), criterion));
+    }
+    let matched = (0..tests[0].0.len()).filter(|&i| {
+        tests
+            .iter()
+            .all(|(cs, criterion)| criterion.matches(&env.arr[cs[i]], env.blank(cs[i])))
+    });
+    let Some(target) = target else {
+        return Ok(env.numbers.whole(matched.count() as i64));
+    };
+    let target = area(target);
+    let values: Vec<Value> = matched
+        .map(|i| target[i])
+        .filter(|&c| !env.blank(c) && !matches!(env.arr[c], Value::Bool(_)))
+        .map(|c| env.arr[c].clone())
+        .collect();
+    Ok(match func {
+        Func::SumIfs if values.is_empty() => env.numbers.whole(0),
+        Func::SumIfs => aggregate_in(env.numbers, 12, &values),
+        _ => aggregate_in(env.numbers, 11, &values),
+    })
+}
+
+/// Handle `dst = EXPR` for any right-hand side [`is_expr`] accepts: parse
+/// it, install it with an edge from every cell it names and evaluate it.
+/// Returns `0` on success and `1` if the text does not parse or the formula
+/// closes a loop, in which case the cell keeps its previous formula.
+pub fn expr_func(
+    txt: &str,
+    cols: i32,
+    rows: i32,
+    eq: usize,
+    arr: &mut [Value],
+    g: &mut Graph,
+    farr: &mut [Formula],
+) -> i32 {
+    let dst = cell_parser(txt[..eq].trim(), cols, rows);
+    if dst == -1 {
+        return 1;
+    }
+    let dst = dst as usize;
+    let expr = match parse(&txt[eq + 1..], cols as usize, rows as usize) {
+        Ok(e) => e,
+        Err(_) => return 1,
+    };
+
+    let old_value = arr[dst].clone();
+    let old = farr[dst];
+    if old.op_type > 0 {
+        delete_edge(g, dst, farr, cols as usize);
+    }
+    let old_expr = g.exprs.insert(dst, expr);
+    add_formula(g, dst, 0, 0, EXPR, farr, cols as usize);
+
+    if !recalculate(g, cols, arr, dst, farr) {
+        delete_edge(g, dst, farr, cols as usize);
+        match old_expr {
+            Some(e) => g.exprs.insert(dst, e),
+            None => g.exprs.remove(&dst),
+        };
+        arr[dst] = old_value;
+        add_formula(g, dst, old.p1, old.p2, old.op_type, farr, cols as usize);
+        return 1;
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::assign;
+    use crate::schedule::ManualClock;
+    use crate::spreadsheet::initialize_spreadsheet;
+    use crate::value::NUM;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_precedence_and_comparisons() {
+        let mut sheet = initialize_spreadsheet(2, 3);
+        assign(&mut sheet, "A1=2").unwrap();
+        assign(&mut sheet, "B1=(A1+1)*3-4/2").unwrap();
+        assert_eq!(sheet.arr[1], 7);
+        assign(&mut sheet, "C1=A1+1<>B1-4").unwrap();
+        assert_eq!(sheet.arr[2], Value::Bool(false));
+        assign(&mut sheet, "A2=2*-A1+10").unwrap();
+        assert_eq!(sheet.arr[3], 6);
+        assign(&mut sheet, "B2=NOT(OR(A1>5, A1<=1), TRUE)").unwrap_err();
+        assign(&mut sheet, "B2=AND(A1:B1, NOT(C1))").unwrap();
+        assert_eq!(sheet.arr[4], Value::Bool(true));
+        assert_eq!(sheet.arr[4].to_string(), "TRUE");
+    }
+
+    #[test]
+    fn test_if_is_lazy_but_depends_on_both_branches() {
+        let mut sheet = initialize_spreadsheet(2, 3);
+        assign(&mut sheet, "A1=1").unwrap();
+        assign(&mut sheet, "C1=IF(A1>0, B1*2, 1/0)").unwrap();
+        assert_eq!(sheet.arr[2], 0);
+        // the untaken branch still feeds the cell
+        assign(&mut sheet, "B1=5").unwrap();
+        assert_eq!(sheet.arr[2], 10);
+        assign(&mut sheet, "A1=0").unwrap();
+        assert_eq!(sheet.arr[2], ERR);
+        assign(&mut sheet, "A2=IFERROR(C1, -1)").unwrap();
+        assert_eq!(sheet.arr[3], -1);
+        // a loop through the untaken branch is still a loop
+        assert!(assign(&mut sheet, "B1=IF(FALSE, C1, 3)").is_err());
+        assert_eq!(sheet.arr[1], 5);
+    }
+
+    #[test]
+    fn test_error_and_blank_tests() {
+        let mut sheet = initialize_spreadsheet(2, 3);
+        assign(&mut sheet, "A1=1/0").unwrap();
+        assign(&mut sheet, "B1=ISERROR(A1)").unwrap();
+        assign(&mut sheet, "C1=ISBLANK(A2)").unwrap();
+        assert_eq!(sheet.arr[1], Value::Bool(true));
+        assert_eq!(sheet.arr[2], Value::Bool(true));
+        assign(&mut sheet, "A2=3").unwrap();
+        assert_eq!(sheet.arr[2], Value::Bool(false));
+        // comparing an error passes it on
+        assign(&mut sheet, "B2=A1>0").unwrap();
+        assert_eq!(sheet.arr[4], ERR);
+        assert!(assign(&mut sheet, "C2=ISBLANK(A1:A2)").is_err());
+        assert!(assign(&mut sheet, "C2=FOO(A1)").is_err());
+    }
+
+    #[test]
+    fn test_counts_and_criteria() {
+        let mut sheet = initialize_spreadsheet(4, 4);
+        for edit in ["A1=5", "A2=20", "A3=1/0", "B1=1", "B2=2", "B3=4", "B4=8"] {
+            assign(&mut sheet, edit).unwrap();
+        }
+        assign(&mut sheet, "C1=COUNT(A1:A4)").unwrap();
+        assign(&mut sheet, "C2=COUNTA(A1:A4, 7)").unwrap();
+        assign(&mut sheet, "C3=COUNTBLANK(A1:A4)").unwrap();
+        assert_eq!(sheet.arr[2], 2);
+        assert_eq!(sheet.arr[6], 4);
+        assert_eq!(sheet.arr[10], 1);
+        assign(&mut sheet, r#"D1=COUNTIF(A1:A4, ">4")"#).unwrap();
+        assign(&mut sheet, r#"D2=SUMIF(A1:A4, "<>", B1)"#).unwrap();
+        assign(&mut sheet, "D3=AVERAGEIF(A1:A4, A1, B1:B4)").unwrap();
+        assign(&mut sheet, r#"D4=SUMIFS(B1:B4, A1:A4, ">0", B1:B4, "<>2")"#).unwrap();
+        assert_eq!(sheet.arr[3], 2);
+        // the sum range takes the criteria range's shape
+        assert_eq!(sheet.arr[7], 7);
+        assert_eq!(sheet.arr[11], 1);
+        assert_eq!(sheet.arr[15], 1);
+        assign(&mut sheet, "A4=6").unwrap();
+        assert_eq!(sheet.arr[3], 3);
+        assert_eq!(sheet.arr[15], 9);
+        assign(&mut sheet, r#"C4=SUMIF(A1:A4, "=", B1:B4)"#).unwrap();
+        assert_eq!(sheet.arr[14], 0);
+        assign(&mut sheet, r#"C4=AVERAGEIF(A1:A4, ">100")"#).unwrap();
+        assert_eq!(sheet.arr[14], ERR);
+        assert!(assign(&mut sheet, r#"C4=COUNTIFS(A1:A4, ">1", B1:B3, ">1")"#).is_err());
+        assert!(assign(&mut sheet, "C4=SUMIFS(B1:B4, A1:A4)").is_err());
+        assert!(assign(&mut sheet, r#"C4=SUMIF(A1:A4, ">1", D4)"#).is_err());
+        assign(&mut sheet, r#"C4=COUNTIF(A1:A4, B1)"#).unwrap();
+        assert_eq!(sheet.arr[14], 0);
+    }
+
+    #[test]
+    fn test_lookups_follow_the_table() {
+        let mut sheet = initialize_spreadsheet(4, 5);
+        for edit in ["A1=10", "A2=20", "A3=30", "B1=1", "B2=2", "B3=3"] {
+            assign(&mut sheet, edit).unwrap();
+        }
+        assign(&mut sheet, "C1=VLOOKUP(25, A1:B3, 2)").unwrap();
+        assign(&mut sheet, "C2=VLOOKUP(25, A1:B3, 2, FALSE)").unwrap();
+        assign(&mut sheet, "C3=MATCH(30, A1:A3, 0)").unwrap();
+        assign(&mut sheet, "C4=INDEX(A1:B3, C3, 2)").unwrap();
+        assign(&mut sheet, "D1=XLOOKUP(25, A1:A3, B1:B3, -1, 1)").unwrap();
+        assign(&mut sheet, "D2=HLOOKUP(2, B1:B3, 3, FALSE)").unwrap();
+        assign(&mut sheet, "D3=VLOOKUP(10, A1:B3, 3)").unwrap();
+        assert_eq!(sheet.arr[2], 2);
+        assert_eq!(sheet.arr[7], NA);
+        assert_eq!(sheet.arr[7].to_string(), "#N/A");
+        assert_eq!(sheet.arr[12], 3);
+        assert_eq!(sheet.arr[17], 3);
+        assert_eq!(sheet.arr[3], 3);
+        assert_eq!(sheet.arr[8], NA);
+        assert_eq!(sheet.arr[13], ERR);
+        // any cell of the table feeds the result
+        assign(&mut sheet, "A2=25").unwrap();
+        assert_eq!(sheet.arr[7], 2);
+        assign(&mut sheet, "B3=7").unwrap();
+        assert_eq!(sheet.arr[17], 7);
+        assign(&mut sheet, "E4=MATCH(21, A1:A3, -1)").unwrap();
+        assert_eq!(sheet.arr[19], 2);
+        assign(&mut sheet, "E4=XLOOKUP(99, A1:A3, B1:B3)").unwrap();
+        assert_eq!(sheet.arr[19], NA);
+        assign(&mut sheet, "E4=INDEX(A1:A3, 4)").unwrap();
+        assert_eq!(sheet.arr[19], ERR);
+        assert!(assign(&mut sheet, "E4=MATCH(1, A1:B3)").is_err());
+        assert!(assign(&mut sheet, "E4=XLOOKUP(1, A1:A3, B1:B2)").is_err());
+        assert!(assign(&mut sheet, "E4=VLOOKUP(1, 2, 1)").is_err());
+    }
+
+    #[test]
+    fn test_indirect_follows_its_target() {
+        let mut sheet = initialize_spreadsheet(4, 3);
+        for edit in ["A1=2", "B1=10", "B2=20", "B3=30"] {
+            assign(&mut sheet, edit).unwrap();
+        }
+        assign(&mut sheet, r#"C1=INDIRECT("B"&A1)*2"#).unwrap();
+        assert_eq!(sheet.arr[2], 40);
+        assert_eq!(sheet.graph.dynamic[&2], vec![4]);
+        // the cell read is a precedent until the name moves
+        assign(&mut sheet, "B2=21").unwrap();
+        assert_eq!(sheet.arr[2], 42);
+        assign(&mut sheet, "A1=3").unwrap();
+        assert_eq!(sheet.arr[2], 60);
+        assign(&mut sheet, "B2=0").unwrap();
+        assert_eq!(sheet.arr[2], 60);
+        assert!(!sheet.graph.adj.contains_key(&4));
+        assign(&mut sheet, r#"C2=SUM(INDIRECT("b1:B"&A1))"#).unwrap();
+        assert_eq!(sheet.arr[5], 40);
+        assign(&mut sheet, "A1=9").unwrap();
+        assert_eq!(sheet.arr[2], ERR);
+        assert_eq!(sheet.arr[5], ERR);
+        // reading itself is a loop
+        assign(&mut sheet, "A1=3").unwrap();
+        assign(&mut sheet, r#"B3=INDIRECT("C"&A1-2)"#).unwrap();
+        assert_eq!(sheet.arr[7], ERR);
+        assert!(assign(&mut sheet, "C3=INDIRECT(A1)").is_ok());
+        assign(&mut sheet, r#"C3="B"&A1"#).unwrap();
+        assert_eq!(sheet.arr[8].to_string(), "B3");
+    }
+
+    #[test]
+    fn test_a_new_target_is_computed_first() {
+        let mut sheet = initialize_spreadsheet(2, 3);
+        assign(&mut sheet, "A1=1").unwrap();
+        assign(&mut sheet, r#"B1=INDIRECT("C"&A1)"#).unwrap();
+        assign(&mut sheet, "C2=A1*10").unwrap();
+        // B1 now reads C2, which the same edit changes
+        assign(&mut sheet, "A1=2").unwrap();
+        assert_eq!(sheet.arr[1], 20);
+        assign(&mut sheet, "A1=3").unwrap();
+        assert_eq!(sheet.arr[1], ERR);
+    }
+
+    #[test]
+    fn test_offset() {
+        let mut sheet = initialize_spreadsheet(4, 3);
+        for edit in ["A1=1", "A2=2", "A3=3", "A4=4", "B1=2"] {
+            assign(&mut sheet, edit).unwrap();
+        }
+        assign(&mut sheet, "C1=OFFSET(A1, B1, 0)").unwrap();
+        assign(&mut sheet, "C2=SUM(OFFSET(A1, 1, 0, B1, 1))").unwrap();
+        assign(&mut sheet, "C3=OFFSET(A1:A2, 3, 0)").unwrap();
+        assert_eq!(sheet.arr[2], 3);
+        assert_eq!(sheet.arr[5], 5);
+        assert_eq!(sheet.arr[8], ERR);
+        assign(&mut sheet, "B1=1").unwrap();
+        assert_eq!(sheet.arr[2], 2);
+        assert_eq!(sheet.arr[5], 2);
+        assign(&mut sheet, "A2=7").unwrap();
+        assert_eq!(sheet.arr[5], 7);
+        // its reference is only a position
+        assign(&mut sheet, "A1=OFFSET(A1, 3, 0)").unwrap();
+        assert_eq!(sheet.arr[0], 4);
+        assert!(assign(&mut sheet, "C4=OFFSET(7, 1, 1)").is_err());
+    }
+
+    #[test]
+    fn test_math_functions() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        assign(&mut sheet, "A1=-9").unwrap();
+        assign(&mut sheet, "B1=SQRT(ABS(A1)) + MOD(A1, 4)").unwrap();
+        assign(&mut sheet, "C1=ROUND(PI(), 2) * 100").unwrap();
+        assign(&mut sheet, "A2=SQRT(A1)").unwrap();
+        assign(&mut sheet, "B2=IFERROR(LN(A1 + 9), -1)").unwrap();
+        assign(&mut sheet, "C2=POWER(2, 10) - INT(SIN(0.5) * 10)").unwrap();
+        assert_eq!(sheet.arr[1], 6);
+        assert_eq!(sheet.arr[2], 314);
+        assert_eq!(sheet.arr[3], ERR);
+        assert_eq!(sheet.arr[4], -1);
+        assert_eq!(sheet.arr[5], 1020);
+        assign(&mut sheet, "A1=16").unwrap();
+        assert_eq!(sheet.arr[1], 4);
+        assert_eq!(sheet.arr[3], 4);
+        assert!(assign(&mut sheet, "A3=PI(1)").is_err());
+        assert!(assign(&mut sheet, "A3=ROUND(A1:B1)").is_err());
+    }
+
+    #[test]
+    fn test_text_cells_and_functions() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        assign(&mut sheet, "A1='  hello  world ").unwrap();
+        assign(&mut sheet, r#"B1=UPPER(TRIM(A1)) & "!""#).unwrap();
+        assign(&mut sheet, "C1=LEN(A1)").unwrap();
+        assign(&mut sheet, "A2='apple").unwrap();
+        assign(&mut sheet, "B2=7").unwrap();
+        assign(&mut sheet, r#"C2=VLOOKUP("APPLE", A2:B2, 2, FALSE)"#).unwrap();
+        assign(&mut sheet, r#"A3=COUNTIF(A1:A2, "*pp*")"#).unwrap();
+        assign(&mut sheet, "B3=A2+1").unwrap();
+        assign(&mut sheet, "C3=CONCAT(A2:B2)").unwrap();
+        assert_eq!(sheet.arr[1].to_string(), "HELLO WORLD!");
+        assert_eq!(sheet.arr[2], 15);
+        assert_eq!(sheet.arr[5], 7);
+        assert_eq!(sheet.arr[6], 1);
+        assert_eq!(sheet.arr[7], ERR);
+        assert_eq!(sheet.arr[8].to_string(), "apple7");
+        assign(&mut sheet, "B2=8").unwrap();
+        assert_eq!(sheet.arr[8].to_string(), "apple8");
+    }
+
+    #[test]
+    fn test_dates_read_the_sheet_clock() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        // 2024-05-01 09:00 UTC
+        let clock = ManualClock::default();
+        clock.advance(Duration::from_secs(19844 * 86400 + 9 * 3600));
+        sheet.graph.scheduler.set_clock(Arc::new(clock.clone()));
+        assign(&mut sheet, r#"A1=DATE("2024-02-29")"#).unwrap();
+        assign(&mut sheet, "B1=A1+366").unwrap();
+        assign(&mut sheet, "C1=TODAY()-A1").unwrap();
+        assign(&mut sheet, "A2=NOW()").unwrap();
+        assign(&mut sheet, r#"B2=DATEDIF(A1, TODAY(), "M")"#).unwrap();
+        assign(&mut sheet, "C2=MAX(A1:B1)").unwrap();
+        assign(&mut sheet, "A3=WEEKDAY(A1)").unwrap();
+        assign(&mut sheet, "B3=YEAR(EDATE(A1, 12))").unwrap();
+        assign(&mut sheet, r#"C3=TEXT(A1, "d mmmm")"#).unwrap();
+        let shown: Vec<String> = sheet.arr.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            shown,
+            [
+                "2024-02-29",
+                "2025-03-01",
+                "62",
+                "2024-05-01 09:00",
+                "2",
+                "2025-03-01",
+                "5",
+                "2025",
+                "29 February"
+            ]
+        );
+        clock.advance(Duration::from_secs(86400));
+        assign(&mut sheet, "A1=DATE(2024, 3, 1)").unwrap();
+        assert_eq!(sheet.arr[2], 62);
+    }
+
+    #[test]
+    fn test_literals_beyond_i32() {
+        let mut sheet = initialize_spreadsheet(2, 3);
+        for input in ["A1=3000000000", "B1=(3000000000)", "C1=-3000000000"] {
+            assign(&mut sheet, input).unwrap();
+        }
+        assert_eq!(
+            sheet.arr[..3],
+            [Value::from(3e9), Value::from(3e9), Value::from(-3e9)]
+        );
+        assign(&mut sheet, "A2=3000000000+1").unwrap();
+        assign(&mut sheet, "B2=1+2+3000000000").unwrap();
+        assign(&mut sheet, "C2=A1*4000000000").unwrap();
+        assert_eq!(
+            sheet.arr[3..],
+            [
+                Value::from(3000000001.0),
+                Value::from(3000000003.0),
+                Value::from(1.2e19)
+            ]
+        );
+        crate::command::execute(&mut sheet, "numbers int").unwrap();
+        assign(&mut sheet, "A1=5000000000").unwrap();
+        assert!(matches!(sheet.arr[0], Value::Int(5000000000)));
+    }
+
+    #[test]
+    fn test_dates_need_date() {
+        let mut sheet = initialize_spreadsheet(2, 3);
+        // without DATE these are plain arithmetic
+        assign(&mut sheet, "A1=2024-13-01").unwrap();
+        assign(&mut sheet, r#"B1=DATE("2024-05-01")"#).unwrap();
+        assign(&mut sheet, r#"C1=B1+DATE("2024-05-01")-2024-05-01"#).unwrap();
+        assert_eq!(sheet.arr[0], 2010);
+        assert_eq!(sheet.arr[1].to_string(), "2024-05-01");
+        assert_eq!(sheet.arr[2], 2 * 45413 - 2024 - 5 - 1);
+        // both forms agree on days out of range, and on bad text
+        for input in [r#"A2=DATE("1899-12-29")"#, "B2=DATE(1900,1,-2)"] {
+            assign(&mut sheet, input).unwrap();
+        }
+        assign(&mut sheet, r#"C2=DATE("2024-13-01")"#).unwrap();
+        assert_eq!(sheet.arr[3..], [NUM, NUM, ERR]);
+        assert!(assign(&mut sheet, "A2=DATE(2024, 5)").is_ok());
+        assert_eq!(sheet.arr[3], ERR);
+    }
+
+    #[test]
+    fn test_quoted_text() {
+        let e = parse(r#"COUNTIF(A1:A2, "say ""hi""")"#, 2, 2).unwrap();
+        assert_eq!(
+            e,
+            Expr::Call(
+                Func::CountIfs,
+                vec![Expr::Range(0, 2), Expr::Text("say \"hi\"".to_string())]
+            )
+        );
+        assert!(parse(r#"COUNTIF(A1:A2, "open)"#, 2, 2).is_err());
+    }
+
+    #[test]
+    fn test_replacing_an_expression_moves_its_edges() {
+        let mut sheet = initialize_spreadsheet(1, 3);
+        assign(&mut sheet, "C1=IF(A1>1, A1, B1)").unwrap();
+        assert_eq!(sheet.graph.adj[&0], vec![2]);
+        assign(&mut sheet, "C1=B1+1").unwrap();
+        assert!(!sheet.graph.adj.contains_key(&0));
+        assign(&mut sheet, "A1=5").unwrap();
+        assert_eq!(sheet.arr[2], 1);
+    }
+}
@@ -1,7 +1,8 @@
-use std::{thread, time::Duration};
+use std::time::Duration;
 
-use crate::graph::{add_formula, Formula, Graph};
+use crate::graph::{add_formula, aggregate_in, Formula, Graph};
 use crate::input_parser::cell_parser;
+use crate::value::Value;
 
 /// insert dest into the dependents list of src, deduplicating
 #[inline]
@@ -34,8 +35,9 @@ fn validate_range(start: i32, end: i32, cols: i32) -> bool {
 /// ```rust
 /// # use lab1::functions::min_func;
 /// # use lab1::graph::{Graph, Formula};
+/// # use lab1::value::Value;
 /// # use lab1::input_parser::cell_parser;
-/// let mut arr = vec![10, 3, 0, 7, 2,0];
+/// let mut arr = [10, 3, 0, 7, 2,0].map(Value::from).to_vec();
 /// let mut graph = Graph::new();
 /// let mut farr = vec![Formula{op_type:9,p1:0,p2:4}; arr.len()];
 /// let ok = min_func("C1=MIN(A1:B2)", 3, 2, 2, &mut arr, &mut graph, &mut farr);
@@ -48,7 +50,7 @@ pub fn min_func(
     rows: i32,
     eq: usize,
     // _end: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> bool {
@@ -79,10 +81,10 @@ pub fn min_func(
     /* register formula & dependencies */
     add_formula(g, dst as usize, start, end, 9, farr, cols as usize);
 
-    let mut min_val = arr[start as usize];
     let (sr, sc) = (start / cols, start % cols);
     let (er, ec) = (end / cols, end % cols);
 
+    let mut values = Vec::new();
     for r in sr..=er {
         for c0 in sc..=ec {
             let idx = (r * cols + c0) as usize;
@@ -91,13 +93,10 @@ pub fn min_func(
             } else {
                 return false;
             }
-
-            if arr[idx] < min_val {
-                min_val = arr[idx];
-            }
+            values.push(arr[idx].clone());
         }
     }
-    arr[dst as usize] = min_val;
+    arr[dst as usize] = aggregate_in(g.numbers, 9, &values);
     true
 }
 /// Compute the maximum value over a range
@@ -119,8 +118,9 @@ pub fn min_func(
 /// ```rust
 /// # use lab1::functions::max_func;
 /// # use lab1::graph::{Graph, Formula};
+/// # use lab1::value::Value;
 /// # use lab1::input_parser::cell_parser;
-/// let mut arr = vec![10, 3, 0, 7, 2,0];
+/// let mut arr = [10, 3, 0, 7, 2,0].map(Value::from).to_vec();
 /// let mut graph = Graph::new();
 /// let mut farr = vec![Formula{op_type:10,p1:0,p2:4}; arr.len()];
 /// let ok = max_func("C1=MAX(A1:B2)", 3, 2, 2, &mut arr, &mut graph, &mut farr);
@@ -133,7 +133,7 @@ pub fn max_func(
     rows: i32,
     eq: usize,
     // _end: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> bool {
@@ -162,10 +162,10 @@ pub fn max_func(
 
     add_formula(g, dst as usize, start, end, 10, farr, cols as usize);
 
-    let mut max_val = arr[start as usize];
     let (sr, sc) = (start / cols, start % cols);
     let (er, ec) = (end / cols, end % cols);
 
+    let mut values = Vec::new();
     for r in sr..=er {
         for c0 in sc..=ec {
             let idx = (r * cols + c0) as usize;
@@ -174,12 +174,10 @@ pub fn max_func(
             } else {
                 return false;
             }
-            if arr[idx] > max_val {
-                max_val = arr[idx];
-            }
+            values.push(arr[idx].clone());
         }
     }
-    arr[dst as usize] = max_val;
+    arr[dst as usize] = aggregate_in(g.numbers, 10, &values);
     true
 }
 /// Compute the average value over a range
@@ -201,8 +199,9 @@ pub fn max_func(
 /// ```rust
 /// # use lab1::functions::avg_func;
 /// # use lab1::graph::{Graph, Formula};
+/// # use lab1::value::Value;
 /// # use lab1::input_parser::cell_parser;
-/// let mut arr = vec![10, 12, 0, 14, 16,0];
+/// let mut arr = [10, 12, 0, 14, 16,0].map(Value::from).to_vec();
 /// let mut graph = Graph::new();
 /// let mut farr = vec![Formula{op_type:11,p1:0,p2:4}; arr.len()];
 /// let ok = avg_func("C1=AVG(A1:B2)", 3, 2, 2, &mut arr, &mut graph, &mut farr);
@@ -215,7 +214,7 @@ pub fn avg_func(
     rows: i32,
     eq: usize,
     // _end: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> bool {
@@ -247,8 +246,7 @@ pub fn avg_func(
     let (sr, sc) = (start / cols, start % cols);
     let (er, ec) = (end / cols, end % cols);
 
-    let mut sum = 0;
-    let mut cnt = 0;
+    let mut values = Vec::new();
     for r in sr..=er {
         for c0 in sc..=ec {
             let idx = (r * cols + c0) as usize;
@@ -257,11 +255,10 @@ pub fn avg_func(
             } else {
                 return false;
             }
-            sum += arr[idx];
-            cnt += 1;
+            values.push(arr[idx].clone());
         }
     }
-    arr[dst as usize] = if cnt == 0 { 0 } else { sum / cnt };
+    arr[dst as usize] = aggregate_in(g.numbers, 11, &values);
     true
 }
 /// Compute the sum over a range
@@ -283,8 +280,9 @@ pub fn avg_func(
 /// ```rust
 /// # use lab1::functions::sum_func;
 /// # use lab1::graph::{Graph, Formula};
+/// # use lab1::value::Value;
 /// # use lab1::input_parser::cell_parser;
-/// let mut arr = vec![10, 3, 0, 7, 2,0];
+/// let mut arr = [10, 3, 0, 7, 2,0].map(Value::from).to_vec();
 /// let mut graph = Graph::new();
 /// let mut farr = vec![Formula{op_type:12,p1:0,p2:4}; arr.len()];
 /// let ok = sum_func("C1=SUM(A1:B2)", 3, 2, 2, &mut arr, &mut graph, &mut farr);
@@ -297,7 +295,7 @@ pub fn sum_func(
     rows: i32,
     eq: usize,
     // _end: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> bool {
@@ -329,7 +327,7 @@ pub fn sum_func(
     let (sr, sc) = (start / cols, start % cols);
     let (er, ec) = (end / cols, end % cols);
 
-    let mut sum = 0;
+    let mut values = Vec::new();
     for r in sr..=er {
         for c0 in sc..=ec {
             let idx = (r * cols + c0) as usize;
@@ -338,10 +336,10 @@ pub fn sum_func(
             } else {
                 return false;
             }
-            sum += arr[idx];
+            values.push(arr[idx].clone());
         }
     }
-    arr[dst as usize] = sum;
+    arr[dst as usize] = aggregate_in(g.numbers, 12, &values);
     true
 }
 /// Compute the stdev over a range
@@ -363,8 +361,9 @@ pub fn sum_func(
 /// ```rust
 /// # use lab1::functions::standard_dev_func;
 /// # use lab1::graph::{Graph, Formula};
+/// # use lab1::value::Value;
 /// # use lab1::input_parser::cell_parser;
-/// let mut arr = vec![10, 10, 0, 10, 10,0];
+/// let mut arr = [10, 10, 0, 10, 10,0].map(Value::from).to_vec();
 /// let mut graph = Graph::new();
 /// let mut farr = vec![Formula{op_type:13,p1:0,p2:4}; arr.len()];
 /// let ok = standard_dev_func("C1=STDEV(A1:B2)", 3, 2, 2, &mut arr, &mut graph, &mut farr);
@@ -377,7 +376,7 @@ pub fn standard_dev_func(
     rows: i32,
     eq: usize,
     // _end: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> bool {
@@ -409,9 +408,7 @@ pub fn standard_dev_func(
     let (sr, sc) = (start / cols, start % cols);
     let (er, ec) = (end / cols, end % cols);
 
-    let mut sum = 0;
-    let mut cnt = 0;
-    let mut sum_sq = 0;
+    let mut values = Vec::new();
     for r in sr..=er {
         for c0 in sc..=ec {
             let idx = (r * cols + c0) as usize;
@@ -420,33 +417,27 @@ pub fn standard_dev_func(
             } else {
                 return false;
             }
-            sum += arr[idx];
-            cnt += 1;
-            sum_sq += arr[idx] * arr[idx];
+            values.push(arr[idx].clone());
         }
     }
-    if cnt <= 1 {
-        arr[dst as usize] = 0;
-        true
-    } else {
-        let avg = sum / cnt;
-        let var = ((sum_sq - 2 * sum * avg + avg * avg * cnt) as f64) / (cnt as f64);
-        arr[dst as usize] = var.sqrt().round() as i32;
-        true
-    }
+    arr[dst as usize] = aggregate_in(g.numbers, 13, &values);
+    true
 }
-/// Pause the thread for the specified number of seconds (literal or cell reference),
-/// record that as a “SLEEP” formula, and store the elapsed seconds in `dst`.
+/// Wait for the specified number of seconds (literal or cell reference) on the
+/// graph's [`Scheduler`](crate::schedule::Scheduler), record that as a “SLEEP”
+/// formula, and store the elapsed seconds in `dst`.
 ///
-/// Returns `true` on success or `false` on parse/range error.
+/// Returns `true` on success, or `false` on parse/range error or if the wait
+/// was cancelled or ran out of time budget (the graph is then left untouched).
 ///
 /// # Examples
 ///
 /// ```rust
 /// # use lab1::functions::sleep_func;
 /// # use lab1::graph::{Graph, Formula};
+/// # use lab1::value::Value;
 /// # use lab1::input_parser::cell_parser;
-/// let mut arr = vec![5];      // we’ll sleep for 5 seconds
+/// let mut arr = [5].map(Value::from).to_vec();      // we’ll sleep for 5 seconds
 /// let mut graph = Graph::new();
 /// let mut farr = vec![Formula{op_type:0,p1:0,p2:0}; 1];
 /// let ok = sleep_func("A1=SLEEP(1)", 1, 1, 2, &mut arr, &mut graph, &mut farr);
@@ -459,7 +450,7 @@ pub fn sleep_func(
     rows: i32,
     eq: usize,
     // _end: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> bool {
@@ -478,8 +469,8 @@ pub fn sleep_func(
     let maybe_ref = cell_parser(inside, cols, rows);
 
     let secs = if maybe_ref != -1 {
-        depend(g, maybe_ref as usize, dst as usize);
-        arr[maybe_ref as usize]
+        // whole seconds; an error cell sleeps for none
+        arr[maybe_ref as usize].number().unwrap_or(0.0) as i32
     } else {
         match inside.trim().parse::<i32>() {
             Ok(v) => v,
@@ -487,6 +478,13 @@ pub fn sleep_func(
         }
     };
 
+    // wait before touching the graph, so an interrupted SLEEP changes nothing
+    if secs > 0 && !g.scheduler.sleep(Duration::from_secs(secs as u64)) {
+        return false;
+    }
+    if maybe_ref != -1 {
+        depend(g, maybe_ref as usize, dst as usize);
+    }
     add_formula(
         g,
         dst as usize,
@@ -497,10 +495,11 @@ pub fn sleep_func(
         cols as usize,
     );
 
-    if secs > 0 {
-        thread::sleep(Duration::from_secs(secs as u64));
-    }
-    arr[dst as usize] = secs;
+    arr[dst as usize] = if maybe_ref != -1 {
+        arr[maybe_ref as usize].clone()
+    } else {
+        Value::from(secs)
+    };
     true
 }
 
@@ -513,7 +512,7 @@ mod tests {
     fn test_min_func_invalid_dst() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -540,7 +539,7 @@ mod tests {
     fn test_min_func_invalid_range_format() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -567,7 +566,7 @@ mod tests {
     fn test_min_func_invalid_range_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -594,7 +593,7 @@ mod tests {
     fn test_min_func_self_dependency() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -621,7 +620,7 @@ mod tests {
     fn test_max_func_invalid_dst() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -648,7 +647,7 @@ mod tests {
     fn test_max_func_invalid_range_format() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -675,7 +674,7 @@ mod tests {
     fn test_max_func_invalid_range_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -702,7 +701,7 @@ mod tests {
     fn test_avg_func_invalid_dst() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -729,7 +728,7 @@ mod tests {
     fn test_avg_func_invalid_range_format() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -756,7 +755,7 @@ mod tests {
     fn test_sum_func_invalid_dst() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -783,7 +782,7 @@ mod tests {
     fn test_sum_func_invalid_range_format() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -810,7 +809,7 @@ mod tests {
     fn test_standard_dev_func_invalid_dst() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -837,7 +836,7 @@ mod tests {
     fn test_standard_dev_func_invalid_range_format() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -864,7 +863,7 @@ mod tests {
     fn test_sleep_func_invalid_dst() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -891,7 +890,7 @@ mod tests {
     fn test_sleep_func_invalid_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
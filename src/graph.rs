@@ -1,7 +1,14 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::fmt;
+
+use crate::date;
+use crate::expr::{Env, Expr, EXPR};
+use crate::iteration::{self, Iteration};
+use crate::schedule::Scheduler;
+use crate::value::{NumberMode, Value, ERR, NUM};
 // use std::i32;
 /// graph_auto.rs is for terminal version
 /// A recorded formula:
@@ -11,6 +18,9 @@ use std::fmt;
 ///    5–8 = "cell ±/* cell"
 ///    9–13 = MIN, MAX, AVG, SUM, STDEV over a range [p1..p2]
 ///    14 = SLEEP
+///    15 = the literal p1 / p2 (a fraction or decimal constant)
+///    16–18 = the literal p1 +-* the literal p2 (kept when the result does not fit an i32)
+///    19 = the expression `Graph::exprs[cell]` (see [`crate::expr`])
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Formula {
     pub op_type: i32,
@@ -31,6 +41,18 @@ impl fmt::Display for Formula {
 /// A very simple adjacency list: for each cell index, a hashmap of its dependents.
 pub struct Graph {
     pub adj: HashMap<usize, Vec<usize>>,
+    /// Iterative-calculation settings; `None` rejects circular references.
+    pub iteration: Option<Iteration>,
+    /// Clock, cancel token and time budget that evaluation runs under.
+    pub scheduler: Scheduler,
+    /// Whether numbers are computed as `f64`, exact `i64` or fixed-point decimals.
+    pub numbers: NumberMode,
+    /// Parsed formulas of the cells with op type [`EXPR`].
+    pub exprs: HashMap<usize, Expr>,
+    /// For each expression cell, the cells its last evaluation read through
+    /// `INDIRECT` or `OFFSET`, in ascending order; each is an edge in `adj`
+    /// too.
+    pub dynamic: HashMap<usize, Vec<usize>>,
 }
 
 impl Graph {
@@ -47,6 +69,11 @@ impl Graph {
     pub fn new() -> Self {
         Graph {
             adj: HashMap::new(),
+            iteration: None,
+            scheduler: Scheduler::default(),
+            numbers: NumberMode::Float,
+            exprs: HashMap::new(),
+            dynamic: HashMap::new(),
         }
     }
 }
@@ -129,6 +156,12 @@ pub fn add_formula(
                 }
             }
         }
+        EXPR => {
+            let refs = graph.exprs.get(&cell).map(|e| e.refs(cols));
+            for src in refs.unwrap_or_default() {
+                graph.adj.entry(src).or_default().push(cell);
+            }
+        }
         _ => {}
     }
 }
@@ -216,35 +249,180 @@ pub fn delete_edge(graph: &mut Graph, cell: usize, formula_array: &[Formula], co
                 }
             }
         }
+        EXPR => {
+            let dynamic = graph.dynamic.remove(&cell).unwrap_or_default();
+            for src in graph
+                .exprs
+                .get(&cell)
+                .map(|e| e.refs(cols))
+                .unwrap_or_default()
+                .into_iter()
+                .chain(dynamic)
+            {
+                if let Some(dependents) = graph.adj.get_mut(&src) {
+                    dependents.retain(|&d| d != cell);
+                    if dependents.is_empty() {
+                        graph.adj.remove(&src);
+                    }
+                }
+            }
+        }
         _ => {}
     }
 }
-/// Perform a single arithmetic operation, *wrapping* on overflow (and signalling division-by-zero
-/// by returning `i32::MIN`).
+/// Perform a single arithmetic operation. Two exact integers give an exact result, or [`NUM`]
+/// if it leaves the `i64` range; otherwise the operation is done in floating point and a
+/// non-finite result is [`NUM`]. Division by zero (or an unknown operator) gives [`ERR`], as
+/// does a text operand, and an error operand is passed on.
 ///
 /// # Examples
 ///
 /// ```rust
 /// use lab1::graph::arith;
-/// assert_eq!(arith(5, 3, '+'), 8);
-/// assert_eq!(arith(5, 0, '/'), i32::MIN);
+/// use lab1::value::{Value, ERR, NUM};
+/// assert_eq!(arith(5.0, 3.0, '+'), 8);
+/// assert_eq!(arith(7.0, 2.0, '/'), 3.5);
+/// assert_eq!(arith(5.0, 0.0, '/'), ERR);
+/// assert_eq!(arith(Value::Int(i64::MAX), Value::Int(1), '+'), NUM);
+/// assert_eq!(arith(1e308, 10.0, '*'), NUM);
 /// ```
 #[inline]
-pub fn arith(v1: i32, v2: i32, op: char) -> i32 {
+pub fn arith(v1: impl Into<Value>, v2: impl Into<Value>, op: char) -> Value {
+    let (v1, v2) = match (v1.into(), v2.into()) {
+        (Value::Error(e), _) | (_, Value::Error(e)) => return Value::Error(e),
+        (Value::Text(_), _) | (_, Value::Text(_)) => return ERR,
+        (Value::Int(a), Value::Int(b)) => return int_arith(a, b, op),
+        (a, b) => (a.number().unwrap_or(0.0), b.number().unwrap_or(0.0)),
+    };
     match op {
-        '+' => v1.wrapping_add(v2),
-        '-' => v1.wrapping_sub(v2),
-        '*' => v1.wrapping_mul(v2),
+        '+' => Value::finite(v1 + v2),
+        '-' => Value::finite(v1 - v2),
+        '*' => Value::finite(v1 * v2),
         '/' => {
-            if v2 != 0 {
-                v1 / v2
+            if v2 != 0.0 {
+                Value::finite(v1 / v2)
             } else {
-                i32::MIN
+                ERR
+            }
+        }
+        _ => ERR,
+    }
+}
+
+/// [`arith`] on two exact integers.
+fn int_arith(a: i64, b: i64, op: char) -> Value {
+    let exact = match op {
+        '+' => a.checked_add(b),
+        '-' => a.checked_sub(b),
+        '*' => a.checked_mul(b),
+        '/' if b == 0 => return ERR,
+        '/' => match a.checked_rem(b) {
+            Some(0) | None => a.checked_div(b),
+            Some(_) => return Value::finite(a as f64 / b as f64),
+        },
+        _ => return ERR,
+    };
+    exact.map_or(NUM, Value::Int)
+}
+
+/// Combine the values of a range as range formula `op_type` does: MIN (9), MAX (10), AVG (11),
+/// SUM (12) or the population standard deviation STDEV (13). The first error in the range is
+/// passed on and an empty range gives [`ERR`]. A range of exact integers keeps MIN, MAX and SUM
+/// exact (see [`arith`]); everything else is computed in floating point.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::graph::aggregate;
+/// use lab1::value::{Value, NUM};
+/// assert_eq!(aggregate(11, &[1.0, 2.0].map(Value::from)), 1.5);
+/// assert_eq!(aggregate(13, &[2, 4, 4, 4, 5, 5, 7, 9].map(Value::from)), 2);
+/// assert_eq!(aggregate(12, &[Value::Int(i64::MAX), Value::Int(1)]), NUM);
+/// ```
+pub fn aggregate(op_type: i32, values: &[Value]) -> Value {
+    if let Some(e) = values.iter().find(|v| v.is_error()) {
+        return e.clone();
+    }
+    if values.is_empty() {
+        return ERR;
+    }
+    let ints = || {
+        values.iter().map(|v| match v {
+            Value::Int(i) => *i,
+            _ => 0,
+        })
+    };
+    if values.iter().all(|v| matches!(v, Value::Int(_))) {
+        match op_type {
+            9 => return ints().min().map_or(ERR, Value::Int),
+            10 => return ints().max().map_or(ERR, Value::Int),
+            12 => {
+                return ints()
+                    .try_fold(0i64, |sum, i| sum.checked_add(i))
+                    .map_or(NUM, Value::Int)
             }
+            _ => {}
         }
-        _ => i32::MIN,
     }
+    let numbers: Vec<f64> = values.iter().filter_map(|v| v.number()).collect();
+    let n = numbers.len() as f64;
+    let sum: f64 = numbers.iter().sum();
+    Value::finite(match op_type {
+        9 => numbers.iter().copied().fold(f64::INFINITY, f64::min),
+        10 => numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        11 => sum / n,
+        12 => sum,
+        13 => {
+            // two passes: subtracting the mean first keeps the squares small
+            let mean = sum / n;
+            let sq: f64 = numbers.iter().map(|v| (v - mean) * (v - mean)).sum();
+            (sq / n).sqrt()
+        }
+        _ => return ERR,
+    })
 }
+
+/// [`arith`] in number mode `numbers`: a decimal sheet rounds the result to its scale. A date
+/// plus or minus some days is a date (see [`date::shift`]).
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::decimal::{DecimalMode, Rounding};
+/// use lab1::graph::arith_in;
+/// use lab1::value::{NumberMode, Value};
+/// let cents = NumberMode::Decimal(DecimalMode::new(2, Rounding::HalfEven).unwrap());
+/// assert_eq!(arith_in(cents, Value::from(0.1), Value::from(0.2), '+').to_string(), "0.30");
+/// assert_eq!(arith_in(NumberMode::Float, Value::from(0.5), Value::from(2), '*'), 1);
+/// ```
+pub fn arith_in(numbers: NumberMode, v1: Value, v2: Value, op: char) -> Value {
+    if let Some(moved) = date::shift(&v1, &v2, op) {
+        return moved;
+    }
+    match numbers {
+        NumberMode::Decimal(mode) => mode.arith(v1, v2, op),
+        _ => arith(v1, v2, op),
+    }
+}
+
+/// [`aggregate`] in number mode `numbers`, skipping text. MIN and MAX of dates are dates.
+pub fn aggregate_in(numbers: NumberMode, op_type: i32, values: &[Value]) -> Value {
+    let values: Vec<Value> = values
+        .iter()
+        .filter(|v| !matches!(v, Value::Text(_)))
+        .cloned()
+        .collect();
+    let result = match numbers {
+        NumberMode::Decimal(mode) => mode.aggregate(op_type, &values),
+        _ => aggregate(op_type, &values),
+    };
+    let dates = !values.is_empty() && values.iter().all(|v| matches!(v, Value::Date(_)));
+    match result.number() {
+        Some(x) if dates && matches!(op_type, 9 | 10) => Value::Date(x),
+        _ => result,
+    }
+}
+
 /// Return a topological ordering of all nodes reachable *from* `start`.  If any cycle is found
 /// among those reachable nodes, returns `None`.
 ///
@@ -318,18 +496,20 @@ pub fn topological_sort(graph: &Graph, start: usize) -> Option<Vec<usize>> {
     }
 }
 /// Recompute (in topological‐sort order) **all** formulas downstream of `start_cell`, writing
-/// their values into `arr`.  Returns `false` (and leaves `arr` untouched) if a cycle is detected.
+/// their values into `arr`.  Returns `false` (and leaves `arr` untouched) if a cycle is detected
+/// (and the graph does not iterate) or the graph's scheduler interrupts the evaluation.
 ///
 /// # Examples
 ///
 /// ```rust
 /// use lab1::graph::{Graph, add_formula, recalculate};
 /// use lab1::spreadsheet::initialize_spreadsheet;
+/// use lab1::value::Value;
 ///
 /// // very small 1×3 sheet: cells 0,1,2
 /// let mut sheet = initialize_spreadsheet(1,3);
-/// sheet.arr[0] = 4;
-/// sheet.arr[1] = 2;
+/// sheet.arr[0] = Value::from(4);
+/// sheet.arr[1] = Value::from(2);
 ///
 /// // cell 2 = cell 0 + cell 1
 /// add_formula(&mut sheet.graph, 2, 0, 1, 5, &mut sheet.formula_array, 3);
@@ -341,134 +521,327 @@ pub fn topological_sort(graph: &Graph, start: usize) -> Option<Vec<usize>> {
 pub fn recalculate(
     graph: &mut Graph,
     cols: i32,
-    arr: &mut [i32],
+    arr: &mut [Value],
     start_cell: usize,
     formula_array: &[Formula],
 ) -> bool {
     let _total_size = arr.len();
+    let now = date::from_unix(graph.scheduler.wall());
     let sorted = match topological_sort(graph, start_cell) {
         Some(v) => v,
-        None => return false,
+        None => {
+            // a loop: only acceptable when the sheet iterates
+            let Some(settings) = graph.iteration else {
+                return false;
+            };
+            let scheduler = &mut graph.scheduler;
+            let numbers = graph.numbers;
+            let exprs = &graph.exprs;
+            let mut found = Vec::new();
+            let solved = iteration::solve(&graph.adj, start_cell, arr, settings, |c, arr| {
+                if !scheduler.check() {
+                    return false;
+                }
+                let reads = evaluate(c, formula_array, cols, arr, numbers, now, exprs);
+                if formula_array[c].op_type == EXPR {
+                    found.push((c, reads));
+                }
+                true
+            });
+            for (c, reads) in found {
+                relink(graph, c, reads, arr);
+            }
+            return solved;
+        }
     };
 
-    // make a working copy and zero out all dependents
+    // make a working copy and zero out all dependents, keeping the old
+    // values in case the evaluation is interrupted
+    let saved: Vec<Value> = sorted.iter().map(|&c| arr[c].clone()).collect();
     for &c in &sorted {
-        arr[c] = 0;
+        arr[c] = Value::default();
     }
 
-    // now re‑evaluate in topo order
-    for &c in &sorted {
-        let f = formula_array[c];
-        match f.op_type {
-            0 => {
-                // constant / direct value
-                arr[c] = if f.p1 == i32::MIN { i32::MIN } else { f.p1 };
-            }
-            1..=4 => {
-                let v1 = arr[f.p1 as usize];
-                let v2 = f.p2;
-                if v1 == i32::MIN {
-                    arr[c] = i32::MIN;
-                } else {
-                    let op = match f.op_type {
-                        1 => '+',
-                        2 => '-',
-                        3 => '*',
-                        4 => '/',
-                        _ => '+',
-                    };
-                    arr[c] = if op == '/' && v2 == 0 {
-                        i32::MIN
-                    } else {
-                        arith(v1, v2, op)
-                    };
+    // now re‑evaluate in topo order; a cell that INDIRECT or OFFSET newly
+    // reads before this pass reached it held a stale value, so the pass is
+    // repeated in the order its edges give (cells whose references keep
+    // moving give up after as many passes as there are cells)
+    let mut order = sorted.clone();
+    for _ in 0..=sorted.len() {
+        let position: HashMap<usize, usize> =
+            order.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+        let mut stale = false;
+        for (i, &c) in order.iter().enumerate() {
+            if !graph.scheduler.check() {
+                for (&c, v) in sorted.iter().zip(&saved) {
+                    arr[c] = v.clone();
                 }
+                return false;
             }
-            5..=8 => {
-                let v1 = arr[f.p1 as usize];
-                let v2 = arr[f.p2 as usize];
-                if v1 == i32::MIN || v2 == i32::MIN {
-                    arr[c] = i32::MIN;
-                } else {
-                    let op = match f.op_type {
-                        5 => '+',
-                        6 => '-',
-                        7 => '*',
-                        8 => '/',
-                        _ => '+',
-                    };
-                    arr[c] = if op == '/' && v2 == 0 {
-                        i32::MIN
-                    } else {
-                        arith(v1, v2, op)
-                    };
-                }
+            let reads = evaluate(
+                c,
+                formula_array,
+                cols,
+                arr,
+                graph.numbers,
+                now,
+                &graph.exprs,
+            );
+            if formula_array[c].op_type == EXPR {
+                let linked = relink(graph, c, reads, arr);
+                stale |= linked
+                    .iter()
+                    .any(|s| position.get(s).is_some_and(|&p| p > i));
             }
-            9..=13 => {
-                // ranges
-                let start = f.p1 as usize;
-                let end = f.p2 as usize;
-                let sr = start / cols as usize;
-                let sc = start % cols as usize;
-                let er = end / cols as usize;
-                let ec = end % cols as usize;
-
-                let mut cnt = 0;
-                let mut sum = 0;
-                let mut mn = i32::MAX;
-                let mut mx = i32::MIN;
-                let sd_acc = 0.0;
-                let mut err = false;
-                let mut sum_sq = 0;
-                for r in sr..=er {
-                    for col in sc..=ec {
-                        let idx = r * cols as usize + col;
-                        let v = arr[idx];
-                        if v == i32::MIN {
-                            err = true
-                        }
-                        cnt += 1;
-                        sum += v;
-                        sum_sq += v * v;
-                        mn = mn.min(v);
-                        mx = mx.max(v);
-                    }
-                }
+        }
+        if !stale {
+            break;
+        }
+        match topological_sort(graph, start_cell) {
+            Some(next) => order = next,
+            None => break,
+        }
+    }
+    true
+}
 
-                if err || cnt == 0 {
-                    arr[c] = i32::MIN;
-                } else {
-                    arr[c] = match f.op_type {
-                        9 => mn,
-                        10 => mx,
-                        11 => sum / cnt,
-                        12 => sum,
-                        13 => {
-                            let avg = sum / cnt;
-                            let variance =
-                                ((sum_sq - 2 * sum * avg + avg * avg * cnt) as f64) / (cnt as f64);
-                            variance.sqrt().round() as i32
-                        }
-                        _ => unreachable!(),
-                    };
+/// Whether `to` depends on `from`, directly or through other cells.
+fn reaches(adj: &HashMap<usize, Vec<usize>>, from: usize, to: usize) -> bool {
+    let mut seen = HashSet::from([from]);
+    let mut stack = vec![from];
+    while let Some(u) = stack.pop() {
+        for &v in adj.get(&u).into_iter().flatten() {
+            if v == to {
+                return true;
+            }
+            if seen.insert(v) {
+                stack.push(v);
+            }
+        }
+    }
+    false
+}
+
+/// Replace the edges into expression cell `cell` from the cells its previous
+/// evaluation read through `INDIRECT` or `OFFSET` ([`Graph::dynamic`]) with
+/// edges from `reads`. A read that would close a loop is not linked and makes
+/// the cell [`ERR`]. Returns the cells that were not linked before.
+fn relink(graph: &mut Graph, cell: usize, mut reads: Vec<usize>, arr: &mut [Value]) -> Vec<usize> {
+    let old = graph.dynamic.remove(&cell).unwrap_or_default();
+    if old.is_empty() && reads.is_empty() {
+        return Vec::new();
+    }
+    for src in &old {
+        if let Some(dependents) = graph.adj.get_mut(src) {
+            // only one edge: the formula may also name `src` itself
+            if let Some(i) = dependents.iter().position(|&d| d == cell) {
+                dependents.remove(i);
+            }
+            if dependents.is_empty() {
+                graph.adj.remove(src);
+            }
+        }
+    }
+    reads.sort_unstable();
+    reads.dedup();
+    let mut linked = Vec::new();
+    for src in reads {
+        if src == cell || reaches(&graph.adj, cell, src) {
+            arr[cell] = ERR;
+        } else {
+            graph.adj.entry(src).or_default().push(cell);
+            linked.push(src);
+        }
+    }
+    let added = linked
+        .iter()
+        .copied()
+        .filter(|s| old.binary_search(s).is_err())
+        .collect();
+    if !linked.is_empty() {
+        graph.dynamic.insert(cell, linked);
+    }
+    added
+}
+
+/// Recompute every cell of the sheet, precedents before dependents, e.g. after
+/// [`Graph::numbers`] changed. Returns `false` if a loop could not be evaluated (see
+/// [`recalculate`]); cells outside loops are still updated.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::graph::recalculate_all;
+/// use lab1::input_parser::parser;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+/// use lab1::value::{NumberMode, Value};
+///
+/// let mut sheet = initialize_spreadsheet(1, 2);
+/// parser(&mut sheet, "A1=5");
+/// parser(&mut sheet, "B1=A1*2");
+/// sheet.graph.numbers = NumberMode::Int;
+/// assert!(recalculate_all(&mut sheet.graph, 2, &mut sheet.arr, &sheet.formula_array));
+/// assert!(matches!(sheet.arr[1], Value::Int(10)));
+/// ```
+pub fn recalculate_all(
+    graph: &mut Graph,
+    cols: i32,
+    arr: &mut [Value],
+    formula_array: &[Formula],
+) -> bool {
+    // INDIRECT and OFFSET may find new precedents, so order first and repeat
+    // while they do (see `recalculate`)
+    let now = date::from_unix(graph.scheduler.wall());
+    let mut in_degree = vec![0usize; arr.len()];
+    for _ in 0..=arr.len() {
+        in_degree.fill(0);
+        for deps in graph.adj.values() {
+            for &d in deps {
+                in_degree[d] += 1;
+            }
+        }
+        let mut ready: VecDeque<usize> = (0..arr.len()).filter(|&c| in_degree[c] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(c) = ready.pop_front() {
+            order.push(c);
+            for &d in graph.adj.get(&c).into_iter().flatten() {
+                in_degree[d] -= 1;
+                if in_degree[d] == 0 {
+                    ready.push_back(d);
                 }
             }
-            14 => {
-                // Sleep / passthrough
-                let val = if f.p1 as usize == c {
-                    f.p2
-                } else {
-                    arr[f.p1 as usize]
-                };
-                arr[c] = val;
+        }
+        let mut moved = false;
+        for c in order {
+            let reads = evaluate(
+                c,
+                formula_array,
+                cols,
+                arr,
+                graph.numbers,
+                now,
+                &graph.exprs,
+            );
+            if formula_array[c].op_type == EXPR {
+                moved |= !relink(graph, c, reads, arr).is_empty();
             }
-            _ => {}
+        }
+        if !moved {
+            break;
         }
     }
-    true
+    // whatever is left sits on or below a loop
+    let mut ok = true;
+    for (c, _) in in_degree.iter().enumerate().filter(|&(_, &d)| d > 0) {
+        ok &= recalculate(graph, cols, arr, c, formula_array);
+    }
+    ok
+}
+
+/// Compute cell `c` from its formula and the current values in `arr`, reading literals as
+/// numbers of mode `numbers`; an expression cell evaluates its entry in `exprs` at date and
+/// time `now` (a serial, see [`crate::date`]). Returns the
+/// cells an expression read through `INDIRECT` or `OFFSET`.
+#[allow(unused_variables)]
+fn evaluate(
+    c: usize,
+    formula_array: &[Formula],
+    cols: i32,
+    arr: &mut [Value],
+    numbers: NumberMode,
+    now: f64,
+    exprs: &HashMap<usize, Expr>,
+) -> Vec<usize> {
+    let f = formula_array[c];
+    let lit = |n: i32| numbers.whole(n as i64);
+    match f.op_type {
+        0 => {
+            // constant / direct value
+            arr[c] = if f.p1 == i32::MIN { ERR } else { lit(f.p1) };
+        }
+        1..=4 => {
+            let op = match f.op_type {
+                1 => '+',
+                2 => '-',
+                3 => '*',
+                4 => '/',
+                _ => '+',
+            };
+            arr[c] = arith_in(numbers, arr[f.p1 as usize].clone(), lit(f.p2), op);
+        }
+        5..=8 => {
+            let op = match f.op_type {
+                5 => '+',
+                6 => '-',
+                7 => '*',
+                8 => '/',
+                _ => '+',
+            };
+            arr[c] = arith_in(
+                numbers,
+                arr[f.p1 as usize].clone(),
+                arr[f.p2 as usize].clone(),
+                op,
+            );
+        }
+        9..=13 => {
+            // ranges
+            let start = f.p1 as usize;
+            let end = f.p2 as usize;
+            let sr = start / cols as usize;
+            let sc = start % cols as usize;
+            let er = end / cols as usize;
+            let ec = end % cols as usize;
+
+            let mut values = Vec::new();
+            for r in sr..=er {
+                for col in sc..=ec {
+                    values.push(arr[r * cols as usize + col].clone());
+                }
+            }
+            arr[c] = aggregate_in(numbers, f.op_type, &values);
+        }
+        14 => {
+            // Sleep / passthrough
+            let val = if f.p1 as usize == c {
+                lit(f.p2)
+            } else {
+                arr[f.p1 as usize].clone()
+            };
+            arr[c] = val;
+        }
+        15 => {
+            arr[c] = arith_in(numbers, lit(f.p1), lit(f.p2), '/');
+        }
+        16..=18 => {
+            let op = match f.op_type {
+                16 => '+',
+                17 => '-',
+                _ => '*',
+            };
+            arr[c] = arith_in(numbers, lit(f.p1), lit(f.p2), op);
+        }
+        EXPR => {
+            let env = Env {
+                arr,
+                formulas: formula_array,
+                cols: cols as usize,
+                numbers,
+                now,
+                reads: RefCell::default(),
+            };
+            let value = exprs.get(&c).map_or(ERR, |e| e.eval(&env));
+            let reads = env.reads.into_inner();
+            arr[c] = value;
+            return reads;
+        }
+        _ => {}
+    }
+    Vec::new()
 }
 
 #[cfg(test)]
+#[allow(unused_mut, clippy::unnecessary_mut_passed)]
 mod tests {
     use super::*;
 
@@ -622,7 +995,7 @@ mod tests {
     #[test]
     fn test_recalculate_no_cycle() {
         let mut graph = Graph::new();
-        let mut arr = vec![0; 5];
+        let mut arr = vec![Value::default(); 5];
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -662,7 +1035,7 @@ mod tests {
     #[test]
     fn test_recalculate_with_cycle() {
         let mut graph = Graph::new();
-        let mut arr = vec![0; 3];
+        let mut arr = vec![Value::default(); 3];
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -809,35 +1182,65 @@ mod tests {
 
     #[test]
     fn test_arith_subtraction() {
-        assert_eq!(arith(10, 5, '-'), 5);
-        assert_eq!(arith(5, 10, '-'), -5);
-        assert_eq!(arith(i32::MIN, 1, '-'), i32::MAX); // Wrapping subtraction
+        assert_eq!(arith(10.0, 5.0, '-'), 5);
+        assert_eq!(arith(5.0, 10.0, '-'), -5);
+        assert_eq!(arith(i32::MIN as f64, 1.0, '-'), i32::MIN as f64 - 1.0); // no wrapping
     }
 
     #[test]
     fn test_arith_multiplication() {
-        assert_eq!(arith(3, 4, '*'), 12);
-        assert_eq!(arith(-3, 4, '*'), -12);
-        assert_eq!(arith(i32::MAX, 2, '*'), i32::MAX.wrapping_mul(2)); // Wrapping multiplication
-        assert_eq!(arith(i32::MIN, -1, '*'), i32::MIN.wrapping_mul(-1)); // Wrapping multiplication
+        assert_eq!(arith(3.0, 4.0, '*'), 12);
+        assert_eq!(arith(-3.0, 4.0, '*'), -12);
+        assert_eq!(arith(i32::MAX as f64, 2.0, '*'), i32::MAX as f64 * 2.0); // no wrapping
+        assert_eq!(arith(i32::MIN as f64, -1.0, '*'), -(i32::MIN as f64)); // no wrapping
     }
 
     #[test]
     fn test_arith_division() {
-        assert_eq!(arith(10, 2, '/'), 5);
-        assert_eq!(arith(10, 0, '/'), i32::MIN); // Division by zero
-        assert_eq!(arith(-10, 2, '/'), -5);
+        assert_eq!(arith(10.0, 2.0, '/'), 5);
+        assert_eq!(arith(10.0, 0.0, '/'), ERR); // Division by zero
+        assert_eq!(arith(-10.0, 2.0, '/'), -5);
+        assert_eq!(arith(7.0, 2.0, '/'), 3.5);
     }
 
     #[test]
     fn test_arith_invalid_operation() {
-        assert_eq!(arith(10, 5, '%'), i32::MIN); // Unsupported operator
+        assert_eq!(arith(10.0, 5.0, '%'), ERR); // Unsupported operator
+    }
+
+    #[test]
+    fn test_int_arith_is_exact_or_num() {
+        assert!(matches!(
+            arith(Value::Int(7), Value::Int(2), '*'),
+            Value::Int(14)
+        ));
+        assert!(matches!(
+            arith(Value::Int(9), Value::Int(3), '/'),
+            Value::Int(3)
+        ));
+        assert_eq!(arith(Value::Int(7), Value::Int(2), '/'), 3.5);
+        assert_eq!(arith(Value::Int(i64::MIN), Value::Int(-1), '/'), NUM);
+        assert_eq!(arith(Value::Int(i64::MIN), Value::Int(1), '-'), NUM);
+        assert_eq!(arith(Value::Int(3), 0.5, '*'), 1.5);
+        assert_eq!(arith(NUM, ERR, '+'), NUM);
+        assert_eq!(arith(f64::MAX, f64::MAX, '+'), NUM);
+    }
+
+    #[test]
+    fn test_aggregate_passes_errors_and_overflow() {
+        let big = [Value::Int(i64::MAX), Value::Int(-1), Value::Int(1)];
+        assert!(matches!(aggregate(12, &big), Value::Int(i64::MAX)));
+        assert_eq!(aggregate(12, &[big[0].clone(), big[0].clone()]), NUM);
+        assert!(matches!(aggregate(9, &big), Value::Int(-1)));
+        assert_eq!(aggregate(11, &[Value::Int(1), Value::Int(2)]), 1.5);
+        assert_eq!(aggregate(10, &[Value::from(1), NUM, ERR]), NUM);
+        assert_eq!(aggregate(12, &[]), ERR);
     }
 
     #[test]
     fn test_recalculate_addition() {
         let mut graph = Graph::new();
-        let mut arr = vec![10, 20, 0];
+        let mut arr = [10, 20, 0].map(Value::from).to_vec();
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -870,7 +1273,7 @@ mod tests {
     #[test]
     fn test_recalculate_subtraction() {
         let mut graph = Graph::new();
-        let mut arr = vec![30, 10, 0];
+        let mut arr = [30, 10, 0].map(Value::from).to_vec();
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -903,7 +1306,7 @@ mod tests {
     #[test]
     fn test_recalculate_multiplication() {
         let mut graph = Graph::new();
-        let mut arr = vec![3, 4, 0];
+        let mut arr = [3, 4, 0].map(Value::from).to_vec();
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -936,7 +1339,7 @@ mod tests {
     #[test]
     fn test_recalculate_division() {
         let mut graph = Graph::new();
-        let mut arr = vec![20, 4, 0];
+        let mut arr = [20, 4, 0].map(Value::from).to_vec();
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -969,7 +1372,7 @@ mod tests {
     #[test]
     fn test_recalculate_division_by_zero() {
         let mut graph = Graph::new();
-        let mut arr = vec![20, 0, 0];
+        let mut arr = [20, 0, 0].map(Value::from).to_vec();
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -996,13 +1399,13 @@ mod tests {
 
         let result = recalculate(&mut graph, 3, &mut arr, 2, &formula_array);
         assert!(result);
-        assert_eq!(arr[2], i32::MIN); // Division by zero results in i32::MIN
+        assert_eq!(arr[2], ERR); // Division by zero results in ERR
     }
 
     #[test]
     fn test_recalculate_with_error_value() {
         let mut graph = Graph::new();
-        let mut arr = vec![i32::MIN, 10, 0];
+        let mut arr = vec![ERR, Value::from(10), Value::from(0)];
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -1029,13 +1432,13 @@ mod tests {
 
         let result = recalculate(&mut graph, 3, &mut arr, 2, &formula_array);
         assert!(result);
-        assert_eq!(arr[2], i32::MIN); // Error propagates
+        assert_eq!(arr[2], ERR); // Error propagates
     }
 
     #[test]
     fn test_recalculate_min() {
         let mut graph = Graph::new();
-        let mut arr = vec![5, 3, 8, 2, 7, 6, 4, 9, 1];
+        let mut arr = [5, 3, 8, 2, 7, 6, 4, 9, 1].map(Value::from).to_vec();
         let mut formula_array = vec![
             Formula {
                 op_type: 0,
@@ -1053,7 +1456,7 @@ mod tests {
     #[test]
     fn test_recalculate_max() {
         let mut graph = Graph::new();
-        let mut arr = vec![5, 3, 8, 2, 7, 6, 4, 9, 1];
+        let mut arr = [5, 3, 8, 2, 7, 6, 4, 9, 1].map(Value::from).to_vec();
         let mut formula_array = vec![
             Formula {
                 op_type: 0,
@@ -1232,7 +1635,7 @@ mod tests {
     #[test]
     fn test_recalculate_division_by_zero2() {
         let mut graph = Graph::new();
-        let mut arr = vec![10, 0, 0]; // A1 = 10, B1 = 0
+        let mut arr = [10, 0, 0].map(Value::from).to_vec(); // A1 = 10, B1 = 0
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -1260,13 +1663,13 @@ mod tests {
 
         // Assert
         assert!(result);
-        assert_eq!(arr[2], i32::MIN); // Division by zero should result in i32::MIN
+        assert_eq!(arr[2], ERR); // Division by zero should result in ERR
     }
 
     #[test]
     fn test_recalculate_invalid_operation() {
         let mut graph = Graph::new();
-        let mut arr = vec![i32::MIN, 5, 0]; // A1 = i32::MIN, B1 = 5
+        let mut arr = vec![ERR, Value::from(5), Value::from(0)]; // A1 = i32::MIN, B1 = 5
         let formula_array = vec![
             Formula {
                 op_type: 0,
@@ -1294,6 +1697,6 @@ mod tests {
 
         // Assert
         assert!(result);
-        assert_eq!(arr[2], i32::MIN); // Invalid operation should propagate i32::MIN
+        assert_eq!(arr[2], ERR); // Invalid operation should propagate ERR
     }
 }
@@ -2,21 +2,28 @@
 //!
 //! Parses and executes cell assignment commands of the form `A1=EXPR`,
 //! where `EXPR` can be:
-//! - A literal integer (e.g. `42`)
+//! - A literal number (e.g. `42` or `2.5`)
 //! - A single cell reference (e.g. `B2`)
 //! - An arithmetic expression combining cells and/or literals with `+`, `-`, `*`, `/` (e.g. `A1+5`, `B2*C3`)
 //! - A function call: `MIN(range)`, `MAX(range)`, `AVG(range)`, `SUM(range)`, `STDEV(range)`, or `SLEEP(duration)`
+//! - Anything larger — nested arithmetic, comparisons, `IF`, `AND`, ... — which is handed to
+//!   [`expr_func`](crate::expr::expr_func)
 //!
 //! The entry point is [`parser`], which returns:
 //! - `0` on successful parse and evaluation
 //! - `1` on any error (parse error, invalid cell, cycle detection, etc.)
 
+use std::mem;
+use std::ptr::addr_of_mut;
+
+use crate::expr::{expr_func, is_expr};
 use crate::functions::{avg_func, max_func, min_func, sleep_func, standard_dev_func, sum_func};
-use crate::graph::{add_formula, arith, delete_edge, recalculate, Formula, Graph};
+use crate::graph::{add_formula, delete_edge, recalculate, Formula, Graph};
 use crate::spreadsheet::Spreadsheet;
+use crate::value::{decimal_parts, Value};
 
 /// Save+restore on rollback
-static mut OLD_VALUE: i32 = 0;
+static mut OLD_VALUE: Value = Value::Number(0.0);
 static mut OLD_OP_TYPE: i32 = 0;
 static mut OLD_P1: i32 = 0;
 static mut OLD_P2: i32 = 0;
@@ -39,17 +46,30 @@ fn depend(g: &mut Graph, src: usize, dst: usize) {
 
 /// A1 → 0,0; B3 → col=B (1)*,row=3 (2) → index = row*cols+col
 pub fn cell_parser(s: &str, cols: i32, rows: i32) -> i32 {
-    let mut col = 0;
-    let mut row = 0;
+    let mut col: i32 = 0;
+    let mut row: i32 = 0;
     let mut seen_digit = false;
     for ch in s.chars() {
         if is_alpha(ch) {
             if seen_digit {
                 return -1;
             }
-            col = col * 26 + (ch as i32 - 'A' as i32 + 1);
+            // a name too long for an i32 is not on any sheet
+            col = match col
+                .checked_mul(26)
+                .and_then(|c| c.checked_add(ch as i32 - 'A' as i32 + 1))
+            {
+                Some(c) => c,
+                None => return -1,
+            };
         } else if is_digit(ch) {
-            row = row * 10 + (ch as i32 - '0' as i32);
+            row = match row
+                .checked_mul(10)
+                .and_then(|r| r.checked_add(ch as i32 - '0' as i32))
+            {
+                Some(r) => r,
+                None => return -1,
+            };
             seen_digit = true;
         } else {
             return -1;
@@ -110,7 +130,7 @@ fn value_func(
     cols: i32,
     rows: i32,
     eq: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> i32 {
@@ -120,7 +140,7 @@ fn value_func(
     }
     /* save old */
     unsafe {
-        OLD_VALUE = arr[dst as usize];
+        OLD_VALUE = arr[dst as usize].clone();
         OLD_OP_TYPE = farr[dst as usize].op_type;
         OLD_P1 = farr[dst as usize].p1;
         OLD_P2 = farr[dst as usize].p2;
@@ -154,10 +174,9 @@ fn value_func(
         }
         depend(g, src as usize, dst as usize);
 
-        val = if neg {
-            -arr[src as usize]
-        } else {
-            arr[src as usize]
+        val = match arr[src as usize] {
+            Value::Number(x) if neg => Value::Number(-x),
+            ref v => v.clone(),
         };
         add_formula(
             g,
@@ -169,13 +188,18 @@ fn value_func(
             cols as usize,
         );
     } else {
-        match rhs.parse::<i32>() {
-            Ok(v) => val = if neg { -v } else { v },
-            Err(_) => {
-                return 1;
-            }
+        // whole numbers are plain constants, decimals the fraction p1 / p2
+        let (num, den) = match decimal_parts(rhs) {
+            Some(parts) => parts,
+            None => return 1,
+        };
+        let num = if neg { -num } else { num };
+        if den == 1 {
+            add_formula(g, dst as usize, num, 0, 0, farr, cols as usize);
+        } else {
+            add_formula(g, dst as usize, num, den, 15, farr, cols as usize);
         }
-        add_formula(g, dst as usize, val, 0, 0, farr, cols as usize);
+        val = Value::Number(num as f64 / den as f64);
     }
 
     arr[dst as usize] = val;
@@ -184,7 +208,7 @@ fn value_func(
         /* rollback */
         delete_edge(g, dst as usize, farr, cols as usize);
         unsafe {
-            arr[dst as usize] = OLD_VALUE;
+            arr[dst as usize] = mem::take(&mut *addr_of_mut!(OLD_VALUE));
             farr[dst as usize] = Formula {
                 op_type: OLD_OP_TYPE,
                 p1: OLD_P1,
@@ -210,7 +234,7 @@ fn arth_op(
     cols: i32,
     rows: i32,
     eq: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> i32 {
@@ -280,7 +304,7 @@ fn arth_op(
 
     // stash/rollback
     unsafe {
-        OLD_VALUE = arr[dst];
+        OLD_VALUE = arr[dst].clone();
         OLD_OP_TYPE = farr[dst].op_type;
         OLD_P1 = farr[dst].p1;
         OLD_P2 = farr[dst].p2;
@@ -289,9 +313,6 @@ fn arth_op(
         delete_edge(g, dst, farr, cols as usize);
     }
 
-    // evaluate
-    let result = arith(left_val, right_val, op_ch);
-
     // record dependencies & formula
     let base_op = return_optype(op_ch);
     let (p1, p2, op_type) = match (left_is_cell, right_is_cell) {
@@ -315,9 +336,27 @@ fn arth_op(
             (p1, p2, base_op + 4) // lit+cell treat as cell+cell
         }
         (false, false) => {
-            // pure literal+literal → constant
-            arr[dst] = result;
-            add_formula(g, dst, result, 0, 0, farr, cols as usize);
+            // pure literal+literal → constant; a quotient that is not whole
+            // keeps both literals as the fraction p1 / p2, and a result that
+            // does not fit a constant keeps both literals and the operator
+            let (p1, p2, op_type) = match op_ch {
+                '/' if right_val == 0 => (i32::MIN, 0, 0),
+                '/' if left_val % right_val != 0 => (left_val, right_val, 15),
+                '/' => (left_val / right_val, 0, 0),
+                _ => {
+                    let folded = match op_ch {
+                        '+' => left_val.checked_add(right_val),
+                        '-' => left_val.checked_sub(right_val),
+                        _ => left_val.checked_mul(right_val),
+                    };
+                    match folded {
+                        // i32::MIN is the ERR marker of a constant
+                        Some(v) if v != i32::MIN => (v, 0, 0),
+                        _ => (left_val, right_val, 15 + base_op), // ops 16..18
+                    }
+                }
+            };
+            add_formula(g, dst, p1, p2, op_type, farr, cols as usize);
             return if recalculate(g, cols, arr, dst, farr) {
                 0
             } else {
@@ -325,7 +364,6 @@ fn arth_op(
             };
         }
     };
-    arr[dst] = result;
     add_formula(g, dst, p1, p2, op_type, farr, cols as usize);
 
     // recalc / rollback
@@ -333,7 +371,7 @@ fn arth_op(
         delete_edge(g, dst, farr, cols as usize);
         // 2) restore the old cell value and formula record
         unsafe {
-            arr[dst] = OLD_VALUE;
+            arr[dst] = mem::take(&mut *addr_of_mut!(OLD_VALUE));
             farr[dst] = Formula {
                 op_type: OLD_OP_TYPE,
                 p1: OLD_P1,
@@ -353,7 +391,7 @@ fn funct(
     cols: i32,
     rows: i32,
     eq: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> i32 {
@@ -381,7 +419,7 @@ fn funct(
         delete_edge(g, dst, farr, cols as usize);
         // 2) restore the old cell value and formula record
         unsafe {
-            arr[dst] = OLD_VALUE;
+            arr[dst] = mem::take(&mut *addr_of_mut!(OLD_VALUE));
             farr[dst] = Formula {
                 op_type: OLD_OP_TYPE,
                 p1: OLD_P1,
@@ -407,6 +445,9 @@ pub fn parser(sheet: &mut Spreadsheet, txt: &str) -> i32 {
     if eq == usize::MAX {
         return -1;
     }
+    if is_expr(&txt[eq + 1..]) {
+        return expr_func(txt, cols, rows, eq, arr, g, farr);
+    }
 
     // classify
     let rhs = &txt[eq + 1..].trim();
@@ -420,7 +461,7 @@ pub fn parser(sheet: &mut Spreadsheet, txt: &str) -> i32 {
                 '+' | '-' => 1, // skip optional sign
                 _ => 0,
             })
-            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase());
+            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase() || c == '.');
 
         is_single_term && !is_func
     } else {
@@ -441,16 +482,23 @@ pub fn parser(sheet: &mut Spreadsheet, txt: &str) -> i32 {
 }
 
 #[cfg(test)]
+#[allow(
+    unused_imports,
+    unused_variables,
+    unused_unsafe,
+    clippy::unnecessary_mut_passed
+)]
 mod tests {
     use super::*;
     use crate::graph::{Formula, Graph};
     use crate::spreadsheet::{initialize_spreadsheet, Spreadsheet};
+    use crate::value::ERR;
 
     #[test]
     fn test_value_func_with_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -486,7 +534,7 @@ mod tests {
     fn test_value_func_with_negative_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -522,8 +570,8 @@ mod tests {
     fn test_value_func_with_cell_reference() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[1] = 50; // B1 = 50
+        let mut arr = vec![Value::default(); 100];
+        arr[1] = Value::from(50); // B1 = 50
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -560,8 +608,8 @@ mod tests {
     fn test_value_func_with_negative_cell_reference() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[1] = 50; // B1 = 50
+        let mut arr = vec![Value::default(); 100];
+        arr[1] = Value::from(50); // B1 = 50
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -598,7 +646,7 @@ mod tests {
     fn test_value_func_with_invalid_cell_reference() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -634,7 +682,7 @@ mod tests {
     fn test_value_func_with_invalid_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -670,8 +718,8 @@ mod tests {
     fn test_value_func_with_existing_formula() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[1] = 50; // B1 = 50
+        let mut arr = vec![Value::default(); 100];
+        arr[1] = Value::from(50); // B1 = 50
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -708,9 +756,9 @@ mod tests {
     fn test_arth_op_addition_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 10; // A1 = 10
-        arr[1] = 20; // B1 = 20
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(10); // A1 = 10
+        arr[1] = Value::from(20); // B1 = 20
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -748,9 +796,9 @@ mod tests {
     fn test_arth_op_subtraction_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 30; // A1 = 30
-        arr[1] = 10; // B1 = 10
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(30); // A1 = 30
+        arr[1] = Value::from(10); // B1 = 10
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -788,9 +836,9 @@ mod tests {
     fn test_arth_op_multiplication_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1 = 5
-        arr[1] = 4; // B1 = 4
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1 = 5
+        arr[1] = Value::from(4); // B1 = 4
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -828,9 +876,9 @@ mod tests {
     fn test_arth_op_division_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 20; // A1 = 20
-        arr[1] = 4; // B1 = 4
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(20); // A1 = 20
+        arr[1] = Value::from(4); // B1 = 4
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -868,9 +916,9 @@ mod tests {
     fn test_arth_op_division_by_zero() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 20; // A1 = 20
-        arr[1] = 0; // B1 = 0
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(20); // A1 = 20
+        arr[1] = Value::from(0); // B1 = 0
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -891,7 +939,7 @@ mod tests {
             &mut formula_array,
         );
         assert_eq!(result, 0);
-        assert_eq!(arr[2], i32::MIN); // Division by zero results in i32::MIN
+        assert_eq!(arr[2], ERR); // Division by zero results in ERR
         assert_eq!(
             formula_array[2],
             Formula {
@@ -908,8 +956,8 @@ mod tests {
     fn test_arth_op_addition_cell_and_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 10; // A1 = 10
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(10); // A1 = 10
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -946,7 +994,7 @@ mod tests {
     fn test_arth_op_invalid_input() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -982,10 +1030,10 @@ mod tests {
     fn test_funct_min() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1013,10 +1061,10 @@ mod tests {
     fn test_funct_max() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1044,10 +1092,10 @@ mod tests {
     fn test_funct_avg() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1068,17 +1116,17 @@ mod tests {
             &mut formula_array,
         );
         assert_eq!(result, 0);
-        assert_eq!(arr[3], 5); // D1 = AVG(A1:C1) = (5 + 3 + 8) / 3
+        assert_eq!(arr[3], 16.0 / 3.0); // D1 = AVG(A1:C1) = (5 + 3 + 8) / 3
     }
 
     #[test]
     fn test_funct_sum() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1106,10 +1154,10 @@ mod tests {
     fn test_funct_stdev() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1130,14 +1178,14 @@ mod tests {
             &mut formula_array,
         );
         assert_eq!(result, 0);
-        assert_eq!(arr[3], 2); // D1 = STDEV(A1:C1) (rounded down)
+        assert!((arr[3].number().unwrap() - 2.0548).abs() < 1e-4); // D1 = STDEV(A1:C1)
     }
 
     #[test]
     fn test_funct_sleep_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1165,8 +1213,8 @@ mod tests {
     fn test_funct_sleep_cell_reference() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 2; // A1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(2); // A1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1194,7 +1242,7 @@ mod tests {
     fn test_funct_invalid_function() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1252,7 +1300,7 @@ mod tests {
     #[test]
     fn test_parser_with_cell_reference() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[1] = 50; // B1 = 50
+        sheet.arr[1] = Value::from(50); // B1 = 50
         let result = parser(&mut sheet, "A1=B1");
         assert_eq!(result, 0);
         assert_eq!(sheet.arr[0], 50);
@@ -1269,8 +1317,8 @@ mod tests {
     #[test]
     fn test_parser_with_arithmetic_operation() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[0] = 10; // A1 = 10
-        sheet.arr[1] = 20; // B1 = 20
+        sheet.arr[0] = Value::from(10); // A1 = 10
+        sheet.arr[1] = Value::from(20); // B1 = 20
         let result = parser(&mut sheet, "C1=A1+B1");
         assert_eq!(result, 0);
         assert_eq!(sheet.arr[2], 30); // C1 = A1 + B1
@@ -1287,9 +1335,9 @@ mod tests {
     #[test]
     fn test_parser_with_function_min() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[0] = 5; // A1
-        sheet.arr[1] = 3; // B1
-        sheet.arr[2] = 8; // C1
+        sheet.arr[0] = Value::from(5); // A1
+        sheet.arr[1] = Value::from(3); // B1
+        sheet.arr[2] = Value::from(8); // C1
         let result = parser(&mut sheet, "D1=MIN(A1:C1)");
         assert_eq!(result, 0);
         assert_eq!(sheet.arr[3], 3); // D1 = MIN(A1:C1)
@@ -1298,9 +1346,9 @@ mod tests {
     #[test]
     fn test_parser_with_function_sum() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[0] = 5; // A1
-        sheet.arr[1] = 3; // B1
-        sheet.arr[2] = 8; // C1
+        sheet.arr[0] = Value::from(5); // A1
+        sheet.arr[1] = Value::from(3); // B1
+        sheet.arr[2] = Value::from(8); // C1
         let result = parser(&mut sheet, "D1=SUM(A1:C1)");
         assert_eq!(result, 0);
         assert_eq!(sheet.arr[3], 16); // D1 = SUM(A1:C1)
@@ -1334,8 +1382,8 @@ mod tests {
     fn test_arth_op_literal_plus_cell() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[1] = 20; // B1 = 20
+        let mut arr = vec![Value::default(); 100];
+        arr[1] = Value::from(20); // B1 = 20
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1372,7 +1420,7 @@ mod tests {
     fn test_arth_op_literal_plus_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1441,7 +1489,7 @@ mod tests {
     fn test_rollback_on_recalculate_failure() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1454,7 +1502,7 @@ mod tests {
 
         // Set up initial state
         let dst = 5; // Target cell
-        arr[dst] = 42;
+        arr[dst] = Value::from(42);
         formula_array[dst] = Formula {
             op_type: 1,
             p1: 2,
@@ -1462,7 +1510,7 @@ mod tests {
         };
 
         unsafe {
-            OLD_VALUE = arr[dst];
+            OLD_VALUE = arr[dst].clone();
             OLD_OP_TYPE = formula_array[dst].op_type;
             OLD_P1 = formula_array[dst].p1;
             OLD_P2 = formula_array[dst].p2;
@@ -1475,7 +1523,7 @@ mod tests {
             // Rollback logic
             delete_edge(&mut graph, dst, &mut formula_array, cols);
             unsafe {
-                arr[dst] = OLD_VALUE;
+                arr[dst] = mem::take(&mut *addr_of_mut!(OLD_VALUE));
                 formula_array[dst] = Formula {
                     op_type: OLD_OP_TYPE,
                     p1: OLD_P1,
@@ -1509,7 +1557,7 @@ mod tests {
     fn test_recalculate_failure_triggers_rollback() {
         let mut graph = Graph::new();
         let cols = 3;
-        let mut arr = vec![0; 3];
+        let mut arr = vec![Value::default(); 3];
         let mut formula_array = vec![
             Formula {
                 op_type: 0,
@@ -1534,7 +1582,7 @@ mod tests {
         depend(&mut graph, 2, 0);
 
         // Set old values for rollback
-        const OLD_VALUE: i32 = 42;
+        const OLD_VALUE: Value = Value::Number(42.0);
         const OLD_OP_TYPE: i32 = 0;
         const OLD_P1: i32 = 0;
         const OLD_P2: i32 = 0;
@@ -1575,4 +1623,23 @@ mod tests {
         assert_eq!(formula_array[2].p1, OLD_P1);
         assert_eq!(formula_array[2].p2, OLD_P2);
     }
+
+    #[test]
+    fn test_literal_overflow_keeps_operands() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        assert_eq!(parser(&mut sheet, "A1=2147483647+1"), 0);
+        assert_eq!(
+            sheet.formula_array[0],
+            Formula {
+                op_type: 16,
+                p1: 2147483647,
+                p2: 1
+            }
+        );
+        assert_eq!(sheet.arr[0], 2147483648.0);
+        // the product is i32::MIN, which as a constant would read as ERR
+        assert_eq!(parser(&mut sheet, "B1=65536*-32768"), 0);
+        assert_eq!(sheet.formula_array[1].op_type, 18);
+        assert_eq!(sheet.arr[1], i32::MIN);
+    }
 }
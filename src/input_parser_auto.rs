@@ -2,21 +2,28 @@
 //!
 //! Parses and executes cell assignment commands of the form `A1=EXPR`,
 //! where `EXPR` can be:
-//! - A literal integer (e.g. `42`)
+//! - A literal number (e.g. `42` or `2.5`)
 //! - A single cell reference (e.g. `B2`)
 //! - An arithmetic expression combining cells and/or literals with `+`, `-`, `*`, `/` (e.g. `A1+5`, `B2*C3`)
 //! - A function call: `MIN(range)`, `MAX(range)`, `AVG(range)`, `SUM(range)`, `STDEV(range)`, or `SLEEP(duration)`
+//! - Anything larger — nested arithmetic, comparisons, `IF`, `AND`, ... — which is handed to
+//!   [`expr_func`](crate::expr::expr_func)
 //!
 //! The entry point is [`parser`], which returns:
 //! - `0` on successful parse and evaluation
 //! - `1` on any error (parse error, invalid cell, cycle detection, etc.)
 
+use std::mem;
+use std::ptr::addr_of_mut;
+
+use crate::expr::{expr_func, is_expr};
 use crate::functions::{avg_func, max_func, min_func, sleep_func, standard_dev_func, sum_func};
-use crate::graph::{add_formula, arith, delete_edge, recalculate, Formula, Graph};
+use crate::graph::{add_formula, delete_edge, recalculate, Formula, Graph};
 use crate::spreadsheet::Spreadsheet;
+use crate::value::{decimal_parts, Value};
 
 /// Save+restore on rollback
-static mut OLD_VALUE: i32 = 0;
+static mut OLD_VALUE: Value = Value::Number(0.0);
 static mut OLD_OP_TYPE: i32 = 0;
 static mut OLD_P1: i32 = 0;
 static mut OLD_P2: i32 = 0;
@@ -39,17 +46,30 @@ fn depend(g: &mut Graph, src: usize, dst: usize) {
 
 /// A1 → 0,0; B3 → col=B (1)*,row=3 (2) → index = row*cols+col
 pub fn cell_parser(s: &str, cols: i32, rows: i32) -> i32 {
-    let mut col = 0;
-    let mut row = 0;
+    let mut col: i32 = 0;
+    let mut row: i32 = 0;
     let mut seen_digit = false;
     for ch in s.chars() {
         if is_alpha(ch) {
             if seen_digit {
                 return -1;
             }
-            col = col * 26 + (ch as i32 - 'A' as i32 + 1);
+            // a name too long for an i32 is not on any sheet
+            col = match col
+                .checked_mul(26)
+                .and_then(|c| c.checked_add(ch as i32 - 'A' as i32 + 1))
+            {
+                Some(c) => c,
+                None => return -1,
+            };
         } else if is_digit(ch) {
-            row = row * 10 + (ch as i32 - '0' as i32);
+            row = match row
+                .checked_mul(10)
+                .and_then(|r| r.checked_add(ch as i32 - '0' as i32))
+            {
+                Some(r) => r,
+                None => return -1,
+            };
             seen_digit = true;
         } else {
             return -1;
@@ -82,7 +102,7 @@ fn value_func(
     cols: i32,
     rows: i32,
     eq: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> i32 {
@@ -92,7 +112,7 @@ fn value_func(
     }
     /* save old */
     unsafe {
-        OLD_VALUE = arr[dst as usize];
+        OLD_VALUE = arr[dst as usize].clone();
         OLD_OP_TYPE = farr[dst as usize].op_type;
         OLD_P1 = farr[dst as usize].p1;
         OLD_P2 = farr[dst as usize].p2;
@@ -126,10 +146,9 @@ fn value_func(
         }
         depend(g, src as usize, dst as usize);
 
-        val = if neg {
-            -arr[src as usize]
-        } else {
-            arr[src as usize]
+        val = match arr[src as usize] {
+            Value::Number(x) if neg => Value::Number(-x),
+            ref v => v.clone(),
         };
         add_formula(
             g,
@@ -141,13 +160,18 @@ fn value_func(
             cols as usize,
         );
     } else {
-        match rhs.parse::<i32>() {
-            Ok(v) => val = if neg { -v } else { v },
-            Err(_) => {
-                return 1;
-            }
+        // whole numbers are plain constants, decimals the fraction p1 / p2
+        let (num, den) = match decimal_parts(rhs) {
+            Some(parts) => parts,
+            None => return 1,
+        };
+        let num = if neg { -num } else { num };
+        if den == 1 {
+            add_formula(g, dst as usize, num, 0, 0, farr, cols as usize);
+        } else {
+            add_formula(g, dst as usize, num, den, 15, farr, cols as usize);
         }
-        add_formula(g, dst as usize, val, 0, 0, farr, cols as usize);
+        val = Value::Number(num as f64 / den as f64);
     }
 
     arr[dst as usize] = val;
@@ -156,7 +180,7 @@ fn value_func(
         /* rollback */
         delete_edge(g, dst as usize, farr, cols as usize);
         unsafe {
-            arr[dst as usize] = OLD_VALUE;
+            arr[dst as usize] = mem::take(&mut *addr_of_mut!(OLD_VALUE));
             farr[dst as usize] = Formula {
                 op_type: OLD_OP_TYPE,
                 p1: OLD_P1,
@@ -182,7 +206,7 @@ fn arth_op(
     cols: i32,
     rows: i32,
     eq: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> i32 {
@@ -275,7 +299,7 @@ fn arth_op(
 
     // stash/rollback
     unsafe {
-        OLD_VALUE = arr[dst];
+        OLD_VALUE = arr[dst].clone();
         OLD_OP_TYPE = farr[dst].op_type;
         OLD_P1 = farr[dst].p1;
         OLD_P2 = farr[dst].p2;
@@ -284,9 +308,6 @@ fn arth_op(
         delete_edge(g, dst, farr, cols as usize);
     }
 
-    // evaluate
-    let result = arith(left_val, right_val, op_ch);
-
     // record dependencies & formula
     let base_op = return_optype(op_ch);
     if base_op == i32::MIN {
@@ -313,9 +334,27 @@ fn arth_op(
             (p1, p2, base_op) // ❗ Use base_op, NOT base_op + 4
         }
         (false, false) => {
-            // pure literal+literal → constant
-            arr[dst] = result;
-            add_formula(g, dst, result, 0, 0, farr, cols as usize);
+            // pure literal+literal → constant; a quotient that is not whole
+            // keeps both literals as the fraction p1 / p2, and a result that
+            // does not fit a constant keeps both literals and the operator
+            let (p1, p2, op_type) = match op_ch {
+                '/' if right_val == 0 => (i32::MIN, 0, 0),
+                '/' if left_val % right_val != 0 => (left_val, right_val, 15),
+                '/' => (left_val / right_val, 0, 0),
+                _ => {
+                    let folded = match op_ch {
+                        '+' => left_val.checked_add(right_val),
+                        '-' => left_val.checked_sub(right_val),
+                        _ => left_val.checked_mul(right_val),
+                    };
+                    match folded {
+                        // i32::MIN is the ERR marker of a constant
+                        Some(v) if v != i32::MIN => (v, 0, 0),
+                        _ => (left_val, right_val, 15 + base_op), // ops 16..18
+                    }
+                }
+            };
+            add_formula(g, dst, p1, p2, op_type, farr, cols as usize);
             return if recalculate(g, cols, arr, dst, farr) {
                 0
             } else {
@@ -323,14 +362,13 @@ fn arth_op(
             };
         }
     };
-    arr[dst] = result;
     add_formula(g, dst, p1, p2, op_type, farr, cols as usize);
 
     // recalc / rollback
     if !recalculate(g, cols, arr, dst, farr) {
         delete_edge(g, dst, farr, cols as usize);
         unsafe {
-            arr[dst] = OLD_VALUE;
+            arr[dst] = mem::take(&mut *addr_of_mut!(OLD_VALUE));
             farr[dst] = Formula {
                 op_type: OLD_OP_TYPE,
                 p1: OLD_P1,
@@ -350,7 +388,7 @@ fn funct(
     cols: i32,
     rows: i32,
     eq: usize,
-    arr: &mut [i32],
+    arr: &mut [Value],
     g: &mut Graph,
     farr: &mut [Formula],
 ) -> i32 {
@@ -358,7 +396,7 @@ fn funct(
 
     let (func_name, func_handler): (
         &str,
-        fn(&str, i32, i32, usize, &mut [i32], &mut Graph, &mut [Formula]) -> bool,
+        fn(&str, i32, i32, usize, &mut [Value], &mut Graph, &mut [Formula]) -> bool,
     ) = if rhs.starts_with("MIN(") && rhs.ends_with(')') {
         ("MIN", min_func)
     } else if rhs.starts_with("MAX(") && rhs.ends_with(')') {
@@ -385,7 +423,7 @@ fn funct(
     if !recalculate(g, cols, arr, dst, farr) {
         delete_edge(g, dst, farr, cols as usize);
         unsafe {
-            arr[dst] = OLD_VALUE;
+            arr[dst] = mem::take(&mut *addr_of_mut!(OLD_VALUE));
             farr[dst] = Formula {
                 op_type: OLD_OP_TYPE,
                 p1: OLD_P1,
@@ -406,6 +444,12 @@ pub fn parser(sheet: &mut Spreadsheet, txt: &str) -> i32 {
     let g = &mut sheet.graph;
     let farr = &mut sheet.formula_array;
 
+    // comparisons bring more '=' signs; the formula's own comes first
+    if let Some(eq) = txt.find('=') {
+        if !txt[eq..].starts_with("==") && is_expr(&txt[eq + 1..]) {
+            return expr_func(txt, cols, rows, eq, arr, g, farr);
+        }
+    }
     let eq_indices: Vec<_> = txt.match_indices('=').collect();
     if eq_indices.len() != 1 {
         return -1; // invalid if not exactly one '='
@@ -434,7 +478,7 @@ pub fn parser(sheet: &mut Spreadsheet, txt: &str) -> i32 {
                 '+' | '-' => 1, // skip optional sign
                 _ => 0,
             })
-            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase());
+            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase() || c == '.');
 
         is_single_term && !is_func
     } else {
@@ -492,12 +536,13 @@ mod tests {
     use super::*;
     use crate::graph::{Formula, Graph};
     use crate::spreadsheet::{initialize_spreadsheet, Spreadsheet};
+    use crate::value::ERR;
 
     #[test]
     fn test_value_func_with_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -533,7 +578,7 @@ mod tests {
     fn test_value_func_with_negative_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -569,8 +614,8 @@ mod tests {
     fn test_value_func_with_cell_reference() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[1] = 50; // B1 = 50
+        let mut arr = vec![Value::default(); 100];
+        arr[1] = Value::from(50); // B1 = 50
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -607,8 +652,8 @@ mod tests {
     fn test_value_func_with_negative_cell_reference() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[1] = 50; // B1 = 50
+        let mut arr = vec![Value::default(); 100];
+        arr[1] = Value::from(50); // B1 = 50
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -645,7 +690,7 @@ mod tests {
     fn test_value_func_with_invalid_cell_reference() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -681,7 +726,7 @@ mod tests {
     fn test_value_func_with_invalid_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -717,8 +762,8 @@ mod tests {
     fn test_value_func_with_existing_formula() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[1] = 50; // B1 = 50
+        let mut arr = vec![Value::default(); 100];
+        arr[1] = Value::from(50); // B1 = 50
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -755,9 +800,9 @@ mod tests {
     fn test_arth_op_addition_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 10; // A1 = 10
-        arr[1] = 20; // B1 = 20
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(10); // A1 = 10
+        arr[1] = Value::from(20); // B1 = 20
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -795,9 +840,9 @@ mod tests {
     fn test_arth_op_subtraction_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 30; // A1 = 30
-        arr[1] = 10; // B1 = 10
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(30); // A1 = 30
+        arr[1] = Value::from(10); // B1 = 10
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -835,9 +880,9 @@ mod tests {
     fn test_arth_op_multiplication_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1 = 5
-        arr[1] = 4; // B1 = 4
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1 = 5
+        arr[1] = Value::from(4); // B1 = 4
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -875,9 +920,9 @@ mod tests {
     fn test_arth_op_division_cells() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 20; // A1 = 20
-        arr[1] = 4; // B1 = 4
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(20); // A1 = 20
+        arr[1] = Value::from(4); // B1 = 4
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -915,9 +960,9 @@ mod tests {
     fn test_arth_op_division_by_zero() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 20; // A1 = 20
-        arr[1] = 0; // B1 = 0
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(20); // A1 = 20
+        arr[1] = Value::from(0); // B1 = 0
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -938,7 +983,7 @@ mod tests {
             &mut formula_array,
         );
         assert_eq!(result, 0);
-        assert_eq!(arr[2], i32::MIN); // Division by zero results in i32::MIN
+        assert_eq!(arr[2], ERR); // Division by zero results in ERR
         assert_eq!(
             formula_array[2],
             Formula {
@@ -955,8 +1000,8 @@ mod tests {
     fn test_arth_op_addition_cell_and_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 10; // A1 = 10
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(10); // A1 = 10
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -993,7 +1038,7 @@ mod tests {
     fn test_arth_op_invalid_input() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1029,10 +1074,10 @@ mod tests {
     fn test_funct_min() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1060,10 +1105,10 @@ mod tests {
     fn test_funct_max() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1091,10 +1136,10 @@ mod tests {
     fn test_funct_avg() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1115,17 +1160,17 @@ mod tests {
             &mut formula_array,
         );
         assert_eq!(result, 0);
-        assert_eq!(arr[3], 5); // D1 = AVG(A1:C1) = (5 + 3 + 8) / 3
+        assert_eq!(arr[3], 16.0 / 3.0); // D1 = AVG(A1:C1) = (5 + 3 + 8) / 3
     }
 
     #[test]
     fn test_funct_sum() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1153,10 +1198,10 @@ mod tests {
     fn test_funct_stdev() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 5; // A1
-        arr[1] = 3; // B1
-        arr[2] = 8; // C1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(5); // A1
+        arr[1] = Value::from(3); // B1
+        arr[2] = Value::from(8); // C1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1177,14 +1222,14 @@ mod tests {
             &mut formula_array,
         );
         assert_eq!(result, 0);
-        assert_eq!(arr[3], 2); // D1 = STDEV(A1:C1) (rounded down)
+        assert!((arr[3].number().unwrap() - 2.0548).abs() < 1e-4); // D1 = STDEV(A1:C1)
     }
 
     #[test]
     fn test_funct_sleep_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1212,8 +1257,8 @@ mod tests {
     fn test_funct_sleep_cell_reference() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[0] = 2; // A1
+        let mut arr = vec![Value::default(); 100];
+        arr[0] = Value::from(2); // A1
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1241,7 +1286,7 @@ mod tests {
     fn test_funct_invalid_function() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1299,7 +1344,7 @@ mod tests {
     #[test]
     fn test_parser_with_cell_reference() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[1] = 50; // B1 = 50
+        sheet.arr[1] = Value::from(50); // B1 = 50
         let result = parser(&mut sheet, "A1=B1");
         assert_eq!(result, 0);
         assert_eq!(sheet.arr[0], 50);
@@ -1316,8 +1361,8 @@ mod tests {
     #[test]
     fn test_parser_with_arithmetic_operation() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[0] = 10; // A1 = 10
-        sheet.arr[1] = 20; // B1 = 20
+        sheet.arr[0] = Value::from(10); // A1 = 10
+        sheet.arr[1] = Value::from(20); // B1 = 20
         let result = parser(&mut sheet, "C1=A1+B1");
         assert_eq!(result, 0);
         assert_eq!(sheet.arr[2], 30); // C1 = A1 + B1
@@ -1334,9 +1379,9 @@ mod tests {
     #[test]
     fn test_parser_with_function_min() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[0] = 5; // A1
-        sheet.arr[1] = 3; // B1
-        sheet.arr[2] = 8; // C1
+        sheet.arr[0] = Value::from(5); // A1
+        sheet.arr[1] = Value::from(3); // B1
+        sheet.arr[2] = Value::from(8); // C1
         let result = parser(&mut sheet, "D1=MIN(A1:C1)");
         assert_eq!(result, 0);
         assert_eq!(sheet.arr[3], 3); // D1 = MIN(A1:C1)
@@ -1345,9 +1390,9 @@ mod tests {
     #[test]
     fn test_parser_with_function_sum() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[0] = 5; // A1
-        sheet.arr[1] = 3; // B1
-        sheet.arr[2] = 8; // C1
+        sheet.arr[0] = Value::from(5); // A1
+        sheet.arr[1] = Value::from(3); // B1
+        sheet.arr[2] = Value::from(8); // C1
         let result = parser(&mut sheet, "D1=SUM(A1:C1)");
         assert_eq!(result, 0);
         assert_eq!(sheet.arr[3], 16); // D1 = SUM(A1:C1)
@@ -1381,8 +1426,8 @@ mod tests {
     fn test_arth_op_literal_plus_cell() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
-        arr[1] = 20; // B1 = 20
+        let mut arr = vec![Value::default(); 100];
+        arr[1] = Value::from(20); // B1 = 20
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1419,7 +1464,7 @@ mod tests {
     fn test_arth_op_literal_plus_literal() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1488,7 +1533,7 @@ mod tests {
     fn test_rollback_on_recalculate_failure() {
         let cols = 10;
         let rows = 10;
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut formula_array = vec![
             Formula {
@@ -1501,7 +1546,7 @@ mod tests {
 
         // Set up initial state
         let dst = 5; // Target cell
-        arr[dst] = 42;
+        arr[dst] = Value::from(42);
         formula_array[dst] = Formula {
             op_type: 1,
             p1: 2,
@@ -1509,7 +1554,7 @@ mod tests {
         };
 
         unsafe {
-            OLD_VALUE = arr[dst];
+            OLD_VALUE = arr[dst].clone();
             OLD_OP_TYPE = formula_array[dst].op_type;
             OLD_P1 = formula_array[dst].p1;
             OLD_P2 = formula_array[dst].p2;
@@ -1522,7 +1567,7 @@ mod tests {
             // Rollback logic
             delete_edge(&mut graph, dst, &mut formula_array, cols);
             unsafe {
-                arr[dst] = OLD_VALUE;
+                arr[dst] = mem::take(&mut *addr_of_mut!(OLD_VALUE));
                 formula_array[dst] = Formula {
                     op_type: OLD_OP_TYPE,
                     p1: OLD_P1,
@@ -1556,7 +1601,7 @@ mod tests {
     fn test_recalculate_failure_triggers_rollback() {
         let mut graph = Graph::new();
         let cols = 3;
-        let mut arr = vec![0; 3];
+        let mut arr = vec![Value::default(); 3];
         let mut formula_array = vec![
             Formula {
                 op_type: 0,
@@ -1581,7 +1626,7 @@ mod tests {
         depend(&mut graph, 2, 0);
 
         // Set old values for rollback
-        const OLD_VALUE: i32 = 42;
+        const OLD_VALUE: Value = Value::Number(42.0);
         const OLD_OP_TYPE: i32 = 0;
         const OLD_P1: i32 = 0;
         const OLD_P2: i32 = 0;
@@ -1665,7 +1710,7 @@ mod tests {
     #[test]
     fn test_value_func_unary_plus_cell() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[1] = 7; // B1 = 7
+        sheet.arr[1] = Value::from(7); // B1 = 7
         let res = parser(&mut sheet, "A1=+B1");
         assert_eq!(res, 0);
         assert_eq!(sheet.arr[0], 7);
@@ -1690,7 +1735,7 @@ mod tests {
     #[test]
     fn test_arth_op_reject_unary_minus_right_cell() {
         let mut sheet = initialize_spreadsheet(10, 10);
-        sheet.arr[0] = 1;
+        sheet.arr[0] = Value::from(1);
         let ret = arth_op(
             "C1=A1+-B1",
             10,
@@ -1705,7 +1750,7 @@ mod tests {
 
     #[test]
     fn test_arth_op_invalid_operator() {
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut farr = vec![
             Formula {
@@ -1715,15 +1760,15 @@ mod tests {
             };
             100
         ];
-        arr[0] = 1;
-        arr[1] = 2;
+        arr[0] = Value::from(1);
+        arr[1] = Value::from(2);
         let ret = arth_op("C1=A1^B1", 10, 10, 2, &mut arr, &mut graph, &mut farr);
         assert_eq!(ret, 1);
     }
 
     #[test]
     fn test_arth_op_literal_minus_literal() {
-        let mut arr = vec![0; 100];
+        let mut arr = vec![Value::default(); 100];
         let mut graph = Graph::new();
         let mut farr = vec![
             Formula {
@@ -1781,4 +1826,23 @@ mod tests {
         // contains '(' → treated as funct → invalid
         assert_eq!(parser(&mut sheet, "A1=(A1+B1)"), 1);
     }
+
+    #[test]
+    fn test_literal_overflow_keeps_operands() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        assert_eq!(parser(&mut sheet, "A1=2147483647+1"), 0);
+        assert_eq!(
+            sheet.formula_array[0],
+            Formula {
+                op_type: 16,
+                p1: 2147483647,
+                p2: 1
+            }
+        );
+        assert_eq!(sheet.arr[0], 2147483648.0);
+        // the product is i32::MIN, which as a constant would read as ERR
+        assert_eq!(parser(&mut sheet, "B1=65536*-32768"), 0);
+        assert_eq!(sheet.formula_array[1].op_type, 18);
+        assert_eq!(sheet.arr[1], i32::MIN);
+    }
 }
@@ -0,0 +1,247 @@
+//! Module `inspect`.
+//!
+//! Human-readable answers to "what is in this cell and why": the formula
+//! behind a value, and for an error cell (`ERR`, `#NUM!`, `#N/A`) the chain of
+//! references leading back to the cell where the error started.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+use crate::csv::value_text;
+use crate::expr::EXPR;
+use crate::json::cell_name;
+use crate::spreadsheet::Spreadsheet;
+use crate::trace::precedents;
+use crate::value::{NA, NUM};
+
+/// Comma-separated cell names, or `none`.
+pub fn cell_list(sheet: &Spreadsheet, cells: &[usize]) -> String {
+    if cells.is_empty() {
+        return "none".to_string();
+    }
+    cells
+        .iter()
+        .map(|&c| cell_name(c, sheet.cols))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The formula text and value of `cell`, e.g. `B3: A1+2 = 5`.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::inspect::show;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// assign(&mut sheet, "A1=3").unwrap();
+/// assign(&mut sheet, "B3=A1+2").unwrap();
+/// assert_eq!(show(&sheet, 7), "B3: A1+2 = 5");
+/// assert_eq!(show(&sheet, 8), "C3: (empty) = 0");
+/// ```
+pub fn show(sheet: &Spreadsheet, cell: usize) -> String {
+    let formula = sheet
+        .formula(cell)
+        .split_once('=')
+        .map_or("(empty)", |(_, rhs)| rhs);
+    format!(
+        "{}: {} = {}",
+        cell_name(cell, sheet.cols),
+        formula,
+        value_text(&sheet.arr[cell])
+    )
+}
+
+/// One-line reason for an error that `cell`'s own formula produced rather
+/// than inherited from a precedent.
+fn origin(sheet: &Spreadsheet, cell: usize) -> String {
+    let f = &sheet.formula_array[cell];
+    match f.op_type {
+        _ if sheet.arr[cell] == NUM => "the result is too large".to_string(),
+        _ if sheet.arr[cell] == NA => "the lookup found no match".to_string(),
+        0 => "its formula evaluates to an error".to_string(),
+        4 if f.p2 == 0 => "divides by zero".to_string(),
+        8 if sheet.arr[f.p2 as usize] == 0 => {
+            format!(
+                "divides by {}, which is 0",
+                cell_name(f.p2 as usize, sheet.cols)
+            )
+        }
+        _ => "evaluated to an error".to_string(),
+    }
+}
+
+/// The precedents of `cell` that hand it its error: they hold the same
+/// error, and are not read only where the error would be caught.
+fn carriers(sheet: &Spreadsheet, cell: usize) -> Vec<usize> {
+    let cells = match sheet.formula_array[cell].op_type {
+        EXPR => {
+            let graph = &sheet.graph;
+            let mut cells = graph
+                .exprs
+                .get(&cell)
+                .map_or_else(Vec::new, |e| e.error_refs(sheet.cols));
+            cells.extend(graph.dynamic.get(&cell).into_iter().flatten());
+            cells.sort_unstable();
+            cells.dedup();
+            cells
+        }
+        _ => precedents(sheet, cell),
+    };
+    cells
+        .into_iter()
+        .filter(|&p| sheet.arr[p] == sheet.arr[cell])
+        .collect()
+}
+
+/// Explain an error in `cell` by following the precedents that carry it back
+/// to the cells whose own formulas produced it.
+///
+/// Each origin is reported with the reference path that carries its error to
+/// `cell`, e.g. `B3 ← B2 ← A2: divides by zero`.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::inspect::why;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// assign(&mut sheet, "A2=A1/0").unwrap();
+/// assign(&mut sheet, "B2=A2+1").unwrap();
+/// assign(&mut sheet, "B3=B2*2").unwrap();
+/// assert_eq!(why(&sheet, 7), "B3 is ERR\nB3 ← B2 ← A2: divides by zero");
+/// assert_eq!(why(&sheet, 0), "A1 = 0 (not an error)");
+/// ```
+pub fn why(sheet: &Spreadsheet, cell: usize) -> String {
+    let name = |c: usize| cell_name(c, sheet.cols);
+    if !sheet.arr[cell].is_error() {
+        return format!(
+            "{} = {} (not an error)",
+            name(cell),
+            value_text(&sheet.arr[cell])
+        );
+    }
+
+    // breadth-first, so every origin is reached by a shortest path
+    let mut via: HashMap<usize, usize> = HashMap::new();
+    let mut queue = VecDeque::from([cell]);
+    let mut origins = Vec::new();
+    via.insert(cell, cell);
+    while let Some(c) = queue.pop_front() {
+        let failed = carriers(sheet, c);
+        if failed.is_empty() {
+            origins.push(c);
+        }
+        for p in failed {
+            if let Entry::Vacant(e) = via.entry(p) {
+                e.insert(c);
+                queue.push_back(p);
+            }
+        }
+    }
+    origins.sort_unstable();
+
+    let mut lines = vec![format!(
+        "{} is {}",
+        name(cell),
+        value_text(&sheet.arr[cell])
+    )];
+    for o in origins {
+        let mut path = vec![o];
+        while *path.last().unwrap() != cell {
+            path.push(via[path.last().unwrap()]);
+        }
+        let chain: Vec<String> = path.iter().rev().map(|&c| name(c)).collect();
+        lines.push(format!("{}: {}", chain.join(" ← "), origin(sheet, o)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::assign;
+    use crate::spreadsheet::initialize_spreadsheet;
+
+    #[test]
+    fn test_why_reports_every_origin() {
+        let mut sheet = initialize_spreadsheet(4, 4);
+        for input in ["B1=A1/0", "C1=A2", "D1=A1/C1", "A4=B1+D1"] {
+            assign(&mut sheet, input).unwrap();
+        }
+        assert_eq!(
+            why(&sheet, 12),
+            "A4 is ERR\nA4 ← B1: divides by zero\nA4 ← D1: divides by C1, which is 0"
+        );
+    }
+
+    #[test]
+    fn test_why_on_origin_itself() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        assign(&mut sheet, "A2=B1/0").unwrap();
+        assert_eq!(why(&sheet, 2), "A2 is ERR\nA2: divides by zero");
+    }
+
+    #[test]
+    fn test_why_names_overflow() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        sheet.graph.numbers = crate::value::NumberMode::Int;
+        assign(&mut sheet, "A1=2147483647*2147483647").unwrap();
+        assign(&mut sheet, "B1=A1*4").unwrap();
+        assign(&mut sheet, "A2=B1+1").unwrap();
+        assert_eq!(
+            why(&sheet, 2),
+            "A2 is #NUM!\nA2 ← B1: the result is too large"
+        );
+    }
+
+    #[test]
+    fn test_why_follows_only_the_same_error() {
+        let mut sheet = initialize_spreadsheet(4, 5);
+        for input in [
+            "A1=1/0",
+            "A2=10",
+            "B2=20",
+            "D1=VLOOKUP(5, A2:B2, 2)",
+            "C1=IFERROR(A1, 0)+D1",
+            "B1=A1+D1",
+            "E1=C1*2",
+        ] {
+            assign(&mut sheet, input).unwrap();
+        }
+        assert_eq!(
+            why(&sheet, 4),
+            "E1 is #N/A\nE1 ← C1 ← D1: the lookup found no match"
+        );
+        // B1 inherits A1's ERR, not D1's #N/A
+        assert_eq!(
+            why(&sheet, 1),
+            "B1 is ERR\nB1 ← A1: its formula evaluates to an error"
+        );
+    }
+
+    #[test]
+    fn test_why_skips_caught_errors() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        for input in [
+            "A1=1/0",
+            "B1=0",
+            "C1=IFERROR(A1, 1)/B1",
+            "C2=C1+ISERROR(A1)",
+        ] {
+            assign(&mut sheet, input).unwrap();
+        }
+        assert_eq!(why(&sheet, 5), "C2 is ERR\nC2 ← C1: evaluated to an error");
+    }
+
+    #[test]
+    fn test_cell_list() {
+        let sheet = initialize_spreadsheet(2, 2);
+        assert_eq!(cell_list(&sheet, &[]), "none");
+        assert_eq!(cell_list(&sheet, &[0, 3]), "A1, B2");
+    }
+}
@@ -0,0 +1,280 @@
+//! Module `iteration`.
+//!
+//! Opt-in iterative calculation for sheets that contain circular references
+//! on purpose (interest that depends on a balance that includes the
+//! interest). With [`Iteration`] settings installed in a sheet's
+//! `graph.iteration`, [`recalculate`](crate::graph::recalculate) no longer
+//! rejects a loop: it splits the cells downstream of the edit into strongly
+//! connected components, evaluates the acyclic ones once in dependency order,
+//! and re-evaluates each loop until no member changes by more than the
+//! tolerance. A loop that has not settled after the maximum number of passes
+//! is filled with `ERR`, which then flows to its dependents as usual.
+//!
+//! Switching iteration off again leaves existing loops in place; edits that
+//! feed one are rejected until the loop is broken.
+
+use std::collections::HashMap;
+
+use crate::value::{Value, ERR};
+
+/// Passes over a loop used when no limit is given.
+pub const DEFAULT_MAX_ITERATIONS: usize = 100;
+/// Largest change between passes that counts as settled, by default.
+pub const DEFAULT_TOLERANCE: f64 = 0.001;
+/// Upper bound for `max_iterations`.
+pub const MAX_ITERATIONS: usize = 10_000;
+
+/// Limits for evaluating a loop of cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Iteration {
+    /// Most passes over a loop before it is declared non-convergent.
+    pub max_iterations: usize,
+    /// A loop has converged once no member moves by more than this.
+    pub tolerance: f64,
+}
+
+impl Default for Iteration {
+    fn default() -> Iteration {
+        Iteration {
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+}
+
+impl Iteration {
+    /// Validated settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::iteration::Iteration;
+    ///
+    /// assert!(Iteration::new(50, 0.01).is_ok());
+    /// assert!(Iteration::new(0, 0.01).is_err());
+    /// assert!(Iteration::new(50, -1.0).is_err());
+    /// ```
+    pub fn new(max_iterations: usize, tolerance: f64) -> Result<Iteration, String> {
+        if !(1..=MAX_ITERATIONS).contains(&max_iterations) {
+            return Err(format!(
+                "max iterations must be between 1 and {}",
+                MAX_ITERATIONS
+            ));
+        }
+        if !(tolerance >= 0.0 && tolerance.is_finite()) {
+            return Err("tolerance must be a non-negative number".to_string());
+        }
+        Ok(Iteration {
+            max_iterations,
+            tolerance,
+        })
+    }
+}
+
+/// Strongly connected components of the part of `adj` reachable from
+/// `start`, ordered so that every edge between components points forward.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use lab1::iteration::components;
+///
+/// // 0 → 1 ⇄ 2 → 3
+/// let adj = HashMap::from([(0, vec![1]), (1, vec![2]), (2, vec![1, 3])]);
+/// let comps = components(&adj, 0);
+/// assert_eq!(comps.len(), 3);
+/// assert_eq!(comps[0], vec![0]);
+/// assert_eq!(comps[2], vec![3]);
+/// ```
+pub fn components(adj: &HashMap<usize, Vec<usize>>, start: usize) -> Vec<Vec<usize>> {
+    // Tarjan's algorithm with an explicit stack; components come out sinks
+    // first, so the result is reversed at the end.
+    let none = Vec::new();
+    let next = |v: usize| adj.get(&v).unwrap_or(&none);
+    let mut index: HashMap<usize, usize> = HashMap::new();
+    let mut low: HashMap<usize, usize> = HashMap::new();
+    let mut on_stack: HashMap<usize, bool> = HashMap::new();
+    let mut stack = Vec::new();
+    let mut comps = Vec::new();
+    let mut work = vec![(start, 0usize)];
+    index.insert(start, 0);
+    low.insert(start, 0);
+    stack.push(start);
+    on_stack.insert(start, true);
+
+    while let Some(&(v, i)) = work.last() {
+        if let Some(&w) = next(v).get(i) {
+            if let Some(top) = work.last_mut() {
+                top.1 += 1;
+            }
+            if let Some(&wi) = index.get(&w) {
+                if on_stack.get(&w) == Some(&true) {
+                    let lv = low[&v].min(wi);
+                    low.insert(v, lv);
+                }
+            } else {
+                let n = index.len();
+                index.insert(w, n);
+                low.insert(w, n);
+                stack.push(w);
+                on_stack.insert(w, true);
+                work.push((w, 0));
+            }
+            continue;
+        }
+        work.pop();
+        if let Some(&(parent, _)) = work.last() {
+            let lp = low[&parent].min(low[&v]);
+            low.insert(parent, lp);
+        }
+        if low[&v] == index[&v] {
+            let mut comp = Vec::new();
+            while let Some(w) = stack.pop() {
+                on_stack.insert(w, false);
+                comp.push(w);
+                if w == v {
+                    break;
+                }
+            }
+            comp.sort_unstable();
+            comps.push(comp);
+        }
+    }
+    comps.reverse();
+    comps
+}
+
+/// How far a cell moved between two passes.
+fn change(old: &Value, new: &Value) -> f64 {
+    match (old, new) {
+        (Value::Error(old), Value::Error(new)) if old == new => 0.0,
+        (Value::Text(old), Value::Text(new)) if old == new => 0.0,
+        _ => match (old.number(), new.number()) {
+            (Some(old), Some(new)) => (new - old).abs(),
+            _ => f64::INFINITY,
+        },
+    }
+}
+
+/// Re-evaluate everything reachable from `start` with `eval`, iterating over
+/// loops as described in the module docs. `eval(cell, arr)` must compute
+/// `cell` from the current contents of `arr` and store it there, or return
+/// `false` to abandon the evaluation; every cell then gets its old value back
+/// and `solve` returns `false`.
+pub fn solve(
+    adj: &HashMap<usize, Vec<usize>>,
+    start: usize,
+    arr: &mut [Value],
+    settings: Iteration,
+    mut eval: impl FnMut(usize, &mut [Value]) -> bool,
+) -> bool {
+    let comps = components(adj, start);
+    let saved: Vec<(usize, Value)> = comps
+        .iter()
+        .flatten()
+        .map(|&c| (c, arr[c].clone()))
+        .collect();
+    let abandon = |arr: &mut [Value]| {
+        for (c, v) in &saved {
+            arr[*c] = v.clone();
+        }
+        false
+    };
+    for comp in comps {
+        let looped = comp.len() > 1 || adj.get(&comp[0]).is_some_and(|d| d.contains(&comp[0]));
+        if !looped {
+            if !eval(comp[0], arr) {
+                return abandon(arr);
+            }
+            continue;
+        }
+        let mut settled = false;
+        for _ in 0..settings.max_iterations {
+            let mut moved: f64 = 0.0;
+            for &c in &comp {
+                let old = arr[c].clone();
+                if !eval(c, arr) {
+                    return abandon(arr);
+                }
+                moved = moved.max(change(&old, &arr[c]));
+            }
+            if moved <= settings.tolerance {
+                settled = true;
+                break;
+            }
+        }
+        if !settled {
+            for &c in &comp {
+                arr[c] = ERR;
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_components_order_and_self_loop() {
+        // 0 → 1 → 2 → 1, 0 → 3 → 3, 2 → 4
+        let adj = HashMap::from([(0, vec![1, 3]), (1, vec![2]), (2, vec![1, 4]), (3, vec![3])]);
+        let comps = components(&adj, 0);
+        let pos = |c: usize| comps.iter().position(|comp| comp.contains(&c)).unwrap();
+        assert_eq!(comps.len(), 4);
+        assert_eq!(comps[pos(1)], vec![1, 2]);
+        assert!(pos(0) < pos(1) && pos(1) < pos(4) && pos(0) < pos(3));
+    }
+
+    #[test]
+    fn test_solve_converges_and_feeds_dependents() {
+        // cell 1 = cell 2 / 2 + 10, cell 2 = cell 1, cell 3 = cell 2 + 1
+        let adj = HashMap::from([(0, vec![1]), (1, vec![2]), (2, vec![1, 3])]);
+        let mut arr = vec![Value::default(); 4];
+        let num = |v: &Value| v.number().unwrap();
+        let done = solve(&adj, 0, &mut arr, Iteration::default(), |c, arr| {
+            arr[c] = match c {
+                1 => Value::from(num(&arr[2]) / 2.0 + 10.0),
+                2 => arr[1].clone(),
+                3 => Value::from(num(&arr[2]) + 1.0),
+                _ => arr[c].clone(),
+            };
+            true
+        });
+        assert!(done);
+        // the loop settles within the tolerance of its fixed point 20
+        assert!((num(&arr[1]) - 20.0).abs() <= 0.001);
+        assert_eq!(arr[3], num(&arr[2]) + 1.0);
+    }
+
+    #[test]
+    fn test_solve_marks_divergent_loop() {
+        let adj = HashMap::from([(0, vec![0, 1])]);
+        let mut arr = vec![Value::from(1), Value::from(0)];
+        let settings = Iteration::new(5, 0.0).unwrap();
+        solve(&adj, 0, &mut arr, settings, |c, arr| {
+            arr[c] = match arr[0] {
+                Value::Number(x) if c == 0 => Value::from(x + 1.0),
+                ref v => v.clone(),
+            };
+            true
+        });
+        assert_eq!(arr, vec![ERR, ERR]);
+    }
+
+    #[test]
+    fn test_abandoned_solve_restores_values() {
+        let adj = HashMap::from([(0, vec![1]), (1, vec![1, 2])]);
+        let mut arr = [5, 6, 7].map(Value::from).to_vec();
+        let mut calls = 0;
+        let done = solve(&adj, 0, &mut arr, Iteration::default(), |c, arr| {
+            calls += 1;
+            arr[c] = Value::from(100);
+            calls < 4
+        });
+        assert!(!done);
+        assert_eq!(arr, vec![5, 6, 7]);
+    }
+}
@@ -0,0 +1,351 @@
+//! Module `journal`.
+//!
+//! Crash-safe autosave for the terminal front end. Every accepted edit is
+//! appended to a plain-text journal (one `A1=EXPR` line per edit) and flushed
+//! immediately, so the work survives if the process dies. Settings that
+//! change what formulas evaluate to (`iterate ...`, `numbers ...`) are
+//! journaled the same way, and the snapshot keeps them too. Every
+//! [`Journal::compact_every`] edits the whole sheet is written to a
+//! [`snapshot`](crate::snapshot) next to the journal and the journal is
+//! truncated.
+//!
+//! [`recover`] rebuilds a sheet by loading the snapshot (if any) and replaying
+//! the journal on top of it. A torn last line (no trailing newline) is ignored;
+//! any other line that cannot be replayed is reported.
+//! Replaying a line that is already part of the snapshot is harmless, because
+//! every line is a full assignment of one cell.
+//!
+//! A session that ends normally [closes](Journal::close) its journal, which
+//! deletes it. A new session never overwrites one that crashed by accident:
+//! [`Journal::create`] fails while a previous journal or snapshot is still
+//! there, until it is recovered or explicitly [discarded](Journal::discard).
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::command::{execute, Outcome};
+use crate::snapshot;
+use crate::spreadsheet::{initialize_spreadsheet, Spreadsheet};
+
+/// Default number of journaled edits between two compactions.
+pub const DEFAULT_COMPACT_EVERY: usize = 1000;
+
+/// Path of the snapshot that belongs to the journal at `path`.
+pub fn snapshot_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".snap");
+    PathBuf::from(name)
+}
+
+/// An open, append-only edit journal.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    pending: usize,
+    /// Compact after this many recorded edits (`0` disables compaction).
+    pub compact_every: usize,
+}
+
+impl Journal {
+    /// Start a fresh session at `path`. Fails with
+    /// [`io::ErrorKind::AlreadyExists`] if a previous session left a
+    /// non-empty journal or a snapshot there, so that its edits are not lost
+    /// by simply starting again; see [`Journal::discard`].
+    pub fn create(path: &Path) -> io::Result<Journal> {
+        let journaled = fs::metadata(path).is_ok_and(|m| m.len() > 0);
+        if journaled || snapshot_path(path).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "a previous session has not been recovered",
+            ));
+        }
+        File::create(path)?;
+        Journal::resume(path)
+    }
+
+    /// Delete the session saved at `path`: its journal and its snapshot.
+    pub fn discard(path: &Path) -> io::Result<()> {
+        for file in [path.to_path_buf(), snapshot_path(path)] {
+            match fs::remove_file(file) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// End the session cleanly: nothing needs recovering any more, so the
+    /// journal and its snapshot are deleted and the next [`Journal::create`]
+    /// starts afresh.
+    pub fn close(self) -> io::Result<()> {
+        drop(self.file);
+        Journal::discard(&self.path)
+    }
+
+    /// Keep appending to an existing journal, e.g. after [`recover`].
+    pub fn resume(path: &Path) -> io::Result<Journal> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Journal {
+            path: path.to_path_buf(),
+            file,
+            pending: 0,
+            compact_every: DEFAULT_COMPACT_EVERY,
+        })
+    }
+
+    /// Append one accepted edit or setting (see [`Outcome::Configured`]) and
+    /// hand it to the OS right away; compacts
+    /// into a snapshot of `sheet` once enough edits have piled up.
+    pub fn record(&mut self, sheet: &Spreadsheet, input: &str) -> io::Result<()> {
+        writeln!(self.file, "{}", input.trim())?;
+        self.file.flush()?;
+        self.pending += 1;
+        if self.compact_every > 0 && self.pending >= self.compact_every {
+            self.compact(sheet)?;
+        }
+        Ok(())
+    }
+
+    /// Write `sheet` to the snapshot file and truncate the journal.
+    ///
+    /// The snapshot goes to a temporary file that is synced and renamed over
+    /// the old one, so a crash at any point leaves a loadable snapshot.
+    pub fn compact(&mut self, sheet: &Spreadsheet) -> io::Result<()> {
+        let target = snapshot_path(&self.path);
+        let mut tmp_name = target.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp = PathBuf::from(tmp_name);
+        {
+            let mut out = File::create(&tmp)?;
+            out.write_all(&snapshot::save(sheet, true))?;
+            out.sync_all()?;
+        }
+        fs::rename(&tmp, &target)?;
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.pending = 0;
+        Ok(())
+    }
+}
+
+/// Rebuild the sheet saved by the journal at `path`.
+///
+/// Starts from the journal's snapshot if there is one, otherwise from an empty
+/// `rows` × `cols` sheet, and replays every complete journal line. Returns the
+/// sheet, the number of replayed lines and a message for each line that was
+/// skipped because it failed or is not an edit or a setting.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::journal::{recover, Journal};
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let path = std::env::temp_dir().join(format!("lab1-doc-{}.journal", std::process::id()));
+/// # Journal::discard(&path).unwrap();
+/// let mut sheet = initialize_spreadsheet(5, 5);
+/// let mut journal = Journal::create(&path).unwrap();
+/// for input in ["A1=2", "B1=A1*3"] {
+///     assign(&mut sheet, input).unwrap();
+///     journal.record(&sheet, input).unwrap();
+/// }
+///
+/// let (restored, replayed, skipped) = recover(&path, 5, 5).unwrap();
+/// assert_eq!((replayed, skipped.len()), (2, 0));
+/// assert_eq!(restored.arr[1], 6);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn recover(
+    path: &Path,
+    rows: usize,
+    cols: usize,
+) -> Result<(Spreadsheet, usize, Vec<String>), String> {
+    let snap = snapshot_path(path);
+    let mut sheet = if snap.exists() {
+        snapshot::load_file(&snap)?
+    } else {
+        initialize_spreadsheet(rows, cols)
+    };
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((sheet, 0, Vec::new())),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    let mut replayed = 0;
+    let mut skipped = Vec::new();
+    for number in 1.. {
+        line.clear();
+        let n = reader
+            .read_line(&mut line)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        // stop at EOF or at a torn final write
+        if n == 0 || !line.ends_with('\n') {
+            break;
+        }
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+        match execute(&mut sheet, input) {
+            Ok(Outcome::Edited(_) | Outcome::Configured) => replayed += 1,
+            Ok(_) => skipped.push(format!("line {}: not an edit ({})", number, input)),
+            Err(e) => skipped.push(format!("line {}: {} ({})", number, e, input)),
+        }
+    }
+    Ok((sheet, replayed, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::assign;
+    use crate::value::NumberMode;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lab1-{}-{}.journal", name, std::process::id()))
+    }
+
+    fn cleanup(path: &Path) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(snapshot_path(path));
+    }
+
+    #[test]
+    fn test_snapshot_path() {
+        assert_eq!(
+            snapshot_path(Path::new("/tmp/x.journal")),
+            PathBuf::from("/tmp/x.journal.snap")
+        );
+    }
+
+    #[test]
+    fn test_recover_with_compaction() {
+        let path = temp_path("compact");
+        cleanup(&path);
+        let mut sheet = initialize_spreadsheet(4, 4);
+        let mut journal = Journal::create(&path).unwrap();
+        journal.compact_every = 3;
+        for input in ["A1=1", "A2=A1+1", "A3=A2+1", "A4=SUM(A1:A3)", "B1=A4*2"] {
+            assign(&mut sheet, input).unwrap();
+            journal.record(&sheet, input).unwrap();
+        }
+        assert!(snapshot_path(&path).exists());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "A4=SUM(A1:A3)\nB1=A4*2\n"
+        );
+
+        let (restored, replayed, _) = recover(&path, 1, 1).unwrap();
+        cleanup(&path);
+        assert_eq!(replayed, 2);
+        assert_eq!(restored.arr, sheet.arr);
+        assert_eq!(restored.formula_strings, sheet.formula_strings);
+    }
+
+    #[test]
+    fn test_recover_ignores_torn_line_and_reports_rejected_edits() {
+        let path = temp_path("torn");
+        fs::write(&path, "A1=4\nB1=A1+\nA2=A1*2\nprint A1\nB2=9").unwrap();
+        let (sheet, replayed, skipped) = recover(&path, 3, 3).unwrap();
+        cleanup(&path);
+        assert_eq!(replayed, 2);
+        assert_eq!(
+            skipped,
+            [
+                "line 2: Formula error: cycle or invalid input. (B1=A1+)",
+                "line 4: not an edit (print A1)",
+            ]
+        );
+        assert_eq!(sheet.arr[0], 4);
+        assert_eq!(sheet.arr[3], 8);
+        assert_eq!(sheet.arr[4], 0);
+    }
+
+    #[test]
+    fn test_create_keeps_previous_session_until_discarded() {
+        let path = temp_path("fresh");
+        cleanup(&path);
+        let mut sheet = initialize_spreadsheet(2, 2);
+        let mut journal = Journal::create(&path).unwrap();
+        assign(&mut sheet, "A1=3").unwrap();
+        journal.record(&sheet, "A1=3").unwrap();
+        let kind = |r: io::Result<Journal>| r.err().map(|e| e.kind());
+        assert_eq!(
+            kind(Journal::create(&path)),
+            Some(io::ErrorKind::AlreadyExists)
+        );
+        journal.compact(&sheet).unwrap();
+        // an empty journal next to a snapshot still holds a session
+        assert_eq!(
+            kind(Journal::create(&path)),
+            Some(io::ErrorKind::AlreadyExists)
+        );
+        assert_eq!(recover(&path, 2, 2).unwrap().0.arr[0], 3);
+
+        Journal::discard(&path).unwrap();
+        let mut journal = Journal::create(&path).unwrap();
+        assert!(!snapshot_path(&path).exists());
+        journal.record(&sheet, "B1=1").unwrap();
+        let (restored, _, _) = recover(&path, 2, 2).unwrap();
+        cleanup(&path);
+        assert_eq!(restored.arr, vec![0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_clean_close_lets_the_next_session_start() {
+        let path = temp_path("close");
+        cleanup(&path);
+        let mut sheet = initialize_spreadsheet(2, 2);
+        let mut journal = Journal::create(&path).unwrap();
+        journal.compact_every = 1;
+        for input in ["A1=3", "B1=A1+1"] {
+            assign(&mut sheet, input).unwrap();
+            journal.record(&sheet, input).unwrap();
+        }
+        assert!(snapshot_path(&path).exists());
+        journal.close().unwrap();
+        assert!(!path.exists() && !snapshot_path(&path).exists());
+        let journal = Journal::create(&path).unwrap();
+        journal.close().unwrap();
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_recover_replays_settings() {
+        let path = temp_path("settings");
+        cleanup(&path);
+        let mut sheet = initialize_spreadsheet(2, 2);
+        let mut journal = Journal::create(&path).unwrap();
+        journal.compact_every = 4;
+        for input in [
+            "numbers int",
+            "iterate on 20",
+            "A1=B1/2+8",
+            "B1=A1",
+            "A2=B1+1",
+        ] {
+            execute(&mut sheet, input).unwrap();
+            journal.record(&sheet, input).unwrap();
+        }
+
+        let (restored, replayed, skipped) = recover(&path, 2, 2).unwrap();
+        cleanup(&path);
+        assert_eq!((replayed, skipped.len()), (1, 0));
+        assert_eq!(restored.graph.numbers, NumberMode::Int);
+        assert_eq!(restored.graph.iteration, sheet.graph.iteration);
+        assert_eq!(restored.arr, sheet.arr);
+    }
+
+    #[test]
+    fn test_recover_missing_journal() {
+        let path = temp_path("missing");
+        cleanup(&path);
+        let (sheet, replayed, _) = recover(&path, 2, 3).unwrap();
+        assert_eq!((sheet.rows, sheet.cols, replayed), (2, 3, 0));
+    }
+}
@@ -0,0 +1,364 @@
+//! Module `json`.
+//!
+//! JSON interchange for whole sheets, so other programs can post a sheet and
+//! read back the computed values. Two shapes are supported:
+//!
+//! - **values**: an array of rows, e.g. `[[1, 2], [3, null]]`, where `null`
+//!   marks an error cell (`ERR`, `#NUM!`, `#N/A`) on export and an empty cell on
+//!   import.
+//! - **cells**: a sparse map of cell name → `{formula, value}`:
+//!
+//! ```json
+//! {"rows": 2, "cols": 2,
+//!  "cells": {"A1": {"value": 4}, "B1": {"formula": "A1*2", "value": 8}}}
+//! ```
+//!
+//! On import a cell entry may also be a bare number (a constant), a boolean,
+//! or a bare string (a formula right-hand side such as `"A1*2"`). A string
+//! `value` without a formula becomes a date cell if it is a date such as
+//! `"2024-05-01"`, and a text cell otherwise. Dates are exported as such
+//! strings.
+
+use serde_json::{json, Map, Value};
+
+use crate::date;
+use crate::display::column_index_to_name;
+use crate::edit::assign;
+use crate::spreadsheet::{initialize_spreadsheet, Spreadsheet, MAX_COLS, MAX_ROWS};
+use crate::value::{decimal_parts, Value as CellValue};
+
+/// JSON form of a single cell value: a number (written as an integer when
+/// whole), a boolean, a string for text or a date, or `null` for an error.
+pub fn cell_value(v: &CellValue) -> Value {
+    match *v {
+        CellValue::Int(i) => return json!(i),
+        CellValue::Bool(b) => return json!(b),
+        CellValue::Text(ref t) => return json!(t.as_str()),
+        CellValue::Date(_) => return json!(v.to_string()),
+        _ => {}
+    }
+    match v.number() {
+        None => Value::Null,
+        Some(x) if x.fract() == 0.0 && x.abs() < 1e15 => json!(x as i64),
+        Some(x) => json!(x),
+    }
+}
+
+/// Name of the cell at `index`, e.g. `0 → "A1"`.
+pub(crate) fn cell_name(index: usize, cols: usize) -> String {
+    format!("{}{}", column_index_to_name(index % cols), index / cols + 1)
+}
+
+/// Split a cell name like `"AB12"` into zero-based `(row, col)` without any
+/// bounds check, so an import can size the sheet from the names it sees.
+pub(crate) fn split_cell_name(name: &str) -> Option<(usize, usize)> {
+    let digits = name.find(|c: char| c.is_ascii_digit())?;
+    let (letters, number) = name.split_at(digits);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    let mut col = 0usize;
+    for ch in letters.chars() {
+        col = col
+            .checked_mul(26)?
+            .checked_add(ch as usize - 'A' as usize + 1)?;
+    }
+    let row: usize = number.parse().ok()?;
+    if row == 0 {
+        return None;
+    }
+    Some((row - 1, col - 1))
+}
+
+/// Export every cell value as an array of rows.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::json::export_values;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(2, 2);
+/// assign(&mut sheet, "A1=4").unwrap();
+/// assign(&mut sheet, "B2=A1/0").unwrap();
+/// assert_eq!(export_values(&sheet), "[[4,0],[0,null]]");
+/// ```
+pub fn export_values(sheet: &Spreadsheet) -> String {
+    let rows: Vec<Value> = (0..sheet.rows)
+        .map(|r| {
+            Value::Array(
+                (0..sheet.cols)
+                    .map(|c| cell_value(&sheet.arr[r * sheet.cols + c]))
+                    .collect(),
+            )
+        })
+        .collect();
+    Value::Array(rows).to_string()
+}
+
+/// Export the sheet as a sparse map of non-empty cells with their formula
+/// text (without the `A1=` prefix) and computed value.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::json::export_cells;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(1, 2);
+/// assign(&mut sheet, "B1=A1+3").unwrap();
+/// assert_eq!(
+///     export_cells(&sheet),
+///     r#"{"cells":{"B1":{"formula":"A1+3","value":3}},"cols":2,"rows":1}"#
+/// );
+/// ```
+pub fn export_cells(sheet: &Spreadsheet) -> String {
+    let mut cells = Map::new();
+    for idx in 0..sheet.rows * sheet.cols {
+        let formula = sheet
+            .formula(idx)
+            .split_once('=')
+            .map(|(_, rhs)| rhs.to_string())
+            .unwrap_or_default();
+        let value = &sheet.arr[idx];
+        if formula.is_empty() && *value == 0 {
+            continue;
+        }
+        let mut entry = Map::new();
+        if !formula.is_empty() {
+            entry.insert("formula".to_string(), json!(formula));
+        }
+        entry.insert("value".to_string(), cell_value(value));
+        if value.is_error() {
+            entry.insert("error".to_string(), json!(value.to_string()));
+        }
+        cells.insert(cell_name(idx, sheet.cols), Value::Object(entry));
+    }
+    json!({ "rows": sheet.rows, "cols": sheet.cols, "cells": cells }).to_string()
+}
+
+/// Build a new sheet from either JSON shape described in the module docs.
+///
+/// Formulas are installed through [`assign`], so the returned sheet is fully
+/// recalculated and its undo history records the import.
+///
+/// # Errors
+///
+/// Returns a message naming the offending cell if the document is malformed
+/// or a formula is rejected.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::json::from_json;
+///
+/// let sheet = from_json(r#"{"cells": {"A1": 2, "A2": "A1*5"}}"#).unwrap();
+/// assert_eq!((sheet.rows, sheet.cols), (2, 1));
+/// assert_eq!(sheet.arr, vec![2, 10]);
+///
+/// let grid = from_json("[[1, 2], [3, null]]").unwrap();
+/// assert_eq!(grid.arr, vec![1, 2, 3, 0]);
+/// ```
+pub fn from_json(text: &str) -> Result<Spreadsheet, String> {
+    let doc: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    match doc {
+        Value::Array(rows) => import_rows(&rows),
+        Value::Object(obj) => import_cells(&obj),
+        _ => Err("Expected an array of rows or an object with \"cells\"".to_string()),
+    }
+}
+
+fn import_rows(rows: &[Value]) -> Result<Spreadsheet, String> {
+    let mut grid = Vec::with_capacity(rows.len());
+    for (r, row) in rows.iter().enumerate() {
+        match row {
+            Value::Array(cells) => grid.push(cells),
+            _ => return Err(format!("Row {} is not an array", r + 1)),
+        }
+    }
+    let cols = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+    if grid.is_empty() || cols == 0 {
+        return Err("Cannot import an empty grid".to_string());
+    }
+    if grid.len() > MAX_ROWS || cols > MAX_COLS {
+        return Err(format!(
+            "Grid exceeds the largest sheet ({} rows × {} columns)",
+            MAX_ROWS, MAX_COLS
+        ));
+    }
+    let mut sheet = initialize_spreadsheet(grid.len(), cols);
+    for (r, row) in grid.iter().enumerate() {
+        for (c, cell) in row.iter().enumerate() {
+            set_cell(&mut sheet, &cell_name(r * cols + c, cols), cell)?;
+        }
+    }
+    Ok(sheet)
+}
+
+fn import_cells(obj: &Map<String, Value>) -> Result<Spreadsheet, String> {
+    let cells = match obj.get("cells") {
+        Some(Value::Object(cells)) => cells,
+        _ => return Err("Missing \"cells\" object".to_string()),
+    };
+
+    let mut rows = 1;
+    let mut cols = 1;
+    for name in cells.keys() {
+        let (r, c) = split_cell_name(name).ok_or(format!("Invalid cell name {}", name))?;
+        if r >= MAX_ROWS || c >= MAX_COLS {
+            return Err(format!("Cell {} is outside the largest sheet", name));
+        }
+        rows = rows.max(r + 1);
+        cols = cols.max(c + 1);
+    }
+    let dimension = |key: &str, seen: usize, max: usize| -> Result<usize, String> {
+        match obj.get(key) {
+            None => Ok(seen),
+            Some(v) => match v.as_u64() {
+                Some(n) if n > max as u64 => Err(format!("\"{}\" must be at most {}", key, max)),
+                Some(n) if n as usize >= seen => Ok(n as usize),
+                _ => Err(format!("\"{}\" must be a number covering every cell", key)),
+            },
+        }
+    };
+    let rows = dimension("rows", rows, MAX_ROWS)?;
+    let cols = dimension("cols", cols, MAX_COLS)?;
+
+    let mut sheet = initialize_spreadsheet(rows, cols);
+    for (name, cell) in cells {
+        set_cell(&mut sheet, name, cell)?;
+    }
+    Ok(sheet)
+}
+
+/// Install one imported cell: a number, a formula string, or an object with
+/// `formula` and/or `value` (the formula wins when both are present).
+fn set_cell(sheet: &mut Spreadsheet, name: &str, cell: &Value) -> Result<(), String> {
+    let rhs = match cell {
+        Value::Null => return Ok(()),
+        Value::Number(n) => number_text(n).ok_or(format!(
+            "{}: value must be an integer or a short decimal",
+            name
+        ))?,
+        Value::String(formula) => formula.clone(),
+        Value::Bool(b) => CellValue::Bool(*b).to_string(),
+        Value::Object(entry) => match (entry.get("formula"), entry.get("value")) {
+            (Some(Value::String(formula)), _) => formula.clone(),
+            (_, Some(Value::Number(n))) => number_text(n).ok_or(format!(
+                "{}: value must be an integer or a short decimal",
+                name
+            ))?,
+            (_, Some(Value::String(text))) if date::parse(text).is_some() => {
+                format!("DATE(\"{}\")", text.trim())
+            }
+            (_, Some(Value::String(text))) => format!("'{}", text),
+            (_, Some(Value::Null)) | (None, None) => return Ok(()),
+            _ => return Err(format!("{}: expected a formula string or a number", name)),
+        },
+        _ => return Err(format!("{}: expected a number, formula or object", name)),
+    };
+    let rhs = rhs.trim().trim_start_matches('=');
+    assign(sheet, &format!("{}={}", name, rhs))
+        .map(|_| ())
+        .map_err(|e| format!("{}: {}", name, e))
+}
+
+/// The literal for an imported number, if a formula can hold it.
+fn number_text(n: &serde_json::Number) -> Option<String> {
+    let text = n.to_string();
+    decimal_parts(text.strip_prefix('-').unwrap_or(&text)).map(|_| text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_cell_name() {
+        assert_eq!(split_cell_name("A1"), Some((0, 0)));
+        assert_eq!(split_cell_name("AA10"), Some((9, 26)));
+        assert_eq!(split_cell_name("A0"), None);
+        assert_eq!(split_cell_name("a1"), None);
+        assert_eq!(split_cell_name("12"), None);
+    }
+
+    #[test]
+    fn test_round_trip_cells() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        assign(&mut sheet, "A1=5").unwrap();
+        assign(&mut sheet, "A2=7").unwrap();
+        assign(&mut sheet, "C3=SUM(A1:A2)").unwrap();
+        let text = export_cells(&sheet);
+
+        let copy = from_json(&text).unwrap();
+        assert_eq!((copy.rows, copy.cols), (3, 3));
+        assert_eq!(copy.arr, sheet.arr);
+        assert_eq!(copy.formula(8), "C3=SUM(A1:A2)");
+    }
+
+    #[test]
+    fn test_import_order_independent() {
+        let sheet = from_json(r#"{"cells": {"A1": "B1+1", "B1": {"value": 9}}}"#).unwrap();
+        assert_eq!(sheet.arr, vec![10, 9]);
+    }
+
+    #[test]
+    fn test_fractions_round_trip() {
+        let sheet = from_json(r#"[[1.25, -0.5, "A1/B1"]]"#).unwrap();
+        assert_eq!(sheet.arr, vec![1.25, -0.5, -2.5]);
+        assert_eq!(export_values(&sheet), "[[1.25,-0.5,-2.5]]");
+    }
+
+    #[test]
+    fn test_booleans_round_trip() {
+        let sheet = from_json(r#"[[3, true, "IF(A1>2, B1, 0)"]]"#).unwrap();
+        assert_eq!(sheet.arr[1], CellValue::Bool(true));
+        assert_eq!(export_values(&sheet), "[[3,true,true]]");
+    }
+
+    #[test]
+    fn test_dates_round_trip() {
+        let doc = r#"{"cells": {"A1": {"value": "2024-05-01"}, "B1": "EOMONTH(A1, 0)"}}"#;
+        let sheet = from_json(doc).unwrap();
+        assert!(matches!(sheet.arr[0], CellValue::Date(_)));
+        assert_eq!(export_values(&sheet), r#"[["2024-05-01","2024-05-31"]]"#);
+        assert_eq!(from_json(&export_cells(&sheet)).unwrap().arr[0], 45413);
+    }
+
+    #[test]
+    fn test_export_error_cells() {
+        let mut sheet = initialize_spreadsheet(1, 2);
+        assign(&mut sheet, "B1=A1/0").unwrap();
+        let doc: Value = serde_json::from_str(&export_cells(&sheet)).unwrap();
+        assert_eq!(doc["cells"]["B1"]["value"], Value::Null);
+        assert_eq!(doc["cells"]["B1"]["error"], "ERR");
+        assert_eq!(export_values(&sheet), "[[0,null]]");
+    }
+
+    #[test]
+    fn test_import_errors() {
+        assert!(from_json("not json").is_err());
+        assert!(from_json("42").is_err());
+        assert!(from_json("[]").is_err());
+        assert!(from_json(r#"{"cells": {"A1": 1e20}}"#).is_err());
+        assert!(from_json(r#"{"cells": {"A1": "B1+"}}"#).is_err());
+        assert!(from_json(r#"{"rows": 1, "cells": {"A2": 1}}"#).is_err());
+        let err = from_json(r#"{"cells": {"A1": "A1+1"}}"#).err().unwrap();
+        assert!(err.starts_with("A1:"));
+    }
+
+    #[test]
+    fn test_import_too_large() {
+        assert!(from_json(r#"{"cells":{"ZZZZZZZ99999999":1}}"#).is_err());
+        assert!(from_json(r#"{"cells":{"A1001":1}}"#).is_err());
+        assert!(from_json(r#"{"cells":{"AAAA1":1}}"#).is_err());
+        assert!(from_json(r#"{"rows":100000000,"cells":{}}"#).is_err());
+        assert!(from_json(r#"{"cols":18279,"cells":{}}"#).is_err());
+        let wide = format!("[[{}]]", vec!["0"; MAX_COLS + 1].join(","));
+        assert!(from_json(&wide).is_err());
+        let sheet = from_json(r#"{"rows":1000,"cols":2,"cells":{"B1000":1}}"#).unwrap();
+        assert_eq!((sheet.rows, sheet.cols), (1000, 2));
+    }
+}
@@ -11,13 +11,11 @@
 //! assert_eq!(sheet.rows, 5);
 //! assert_eq!(sheet.cols, 10);
 //! assert_eq!(sheet.arr.len(), 5 * 10);
-//! assert!(sheet.arr.iter().all(|&cell| cell == 0));
+//! assert!(sheet.arr.iter().all(|cell| *cell == 0));
 //! ```
 // use crate::input_parser::cell_parser;
 use std::cell::RefCell;
 
-#[cfg(feature = "wasm")]
-use crate::input_parser::cell_parser;
 // Only include wasm-bindgen if the "wasm" feature is enabled
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
@@ -73,6 +71,35 @@ pub mod scrolling;
 pub mod scrolling;
 mod spreadsheet_auto;
 
+// ────────────────────────────────────────────────────────────────
+// Shared modules (same source for both builds)
+// ────────────────────────────────────────────────────────────────
+pub mod command;
+pub mod completion;
+pub mod criteria;
+pub mod csv;
+pub mod date;
+pub mod decimal;
+pub mod depgraph;
+pub mod edit;
+pub mod expr;
+pub mod inspect;
+pub mod iteration;
+pub mod journal;
+pub mod json;
+pub mod lookup;
+pub mod math;
+pub mod query;
+pub mod rpc;
+pub mod schedule;
+pub mod script;
+pub mod snapshot;
+pub mod text;
+pub mod trace;
+pub mod tui;
+pub mod value;
+pub mod view;
+
 // ────────────────────────────────────────────────────────────────
 // Re-exports at the crate root
 // ────────────────────────────────────────────────────────────────
@@ -102,6 +129,7 @@ pub fn render_initial_spreadsheet() -> String {
             &sheet.arr,
             sheet.cols,
             sheet.rows,
+            sheet.view.precision,
         )
     })
 }
@@ -131,6 +159,7 @@ pub fn update_formula(input: &str) -> Result<String, wasm_bindgen::prelude::JsVa
                             &sheet.arr,
                             sheet.cols,
                             sheet.rows,
+                            sheet.view.precision,
                         ));
                     }
                 }
@@ -154,10 +183,10 @@ pub fn update_formula(input: &str) -> Result<String, wasm_bindgen::prelude::JsVa
                         let mut column_data = Vec::new();
                         for row in cells.start_row..=cells.end_row {
                             let index = row * sheet.cols + col;
-                            if sheet.arr[index] == i32::MIN {
-                                return Err(JsValue::from_str("ERR in Graph"));
+                            match sheet.arr[index].number() {
+                                Some(x) => column_data.push(x),
+                                None => return Err(JsValue::from_str("ERR in Graph")),
                             }
-                            column_data.push(sheet.arr[index]);
                         }
                         graph_data.push(column_data);
                     }
@@ -168,37 +197,10 @@ pub fn update_formula(input: &str) -> Result<String, wasm_bindgen::prelude::JsVa
         }
 
         // Handle A1=... or A1=B1+C1
-        // Handle A1=... or A1=B1+C1
-        if let Some(eq) = input.find('=') {
-            let cell_index =
-                cell_parser(&input[..eq], sheet.cols as i32, sheet.rows as i32) as usize;
-
-            let old_formula1 = sheet.formula_strings[cell_index].clone();
-
-            // run parser (0 = OK, non-zero = cycle/error)
-            let code = input_parser::parser(&mut sheet, input);
-            if code != 0 {
-                // error → restore old formula & re-parse it
-                sheet.formula_strings[cell_index] = old_formula1.clone();
-                let _ = input_parser::parser(&mut sheet, &old_formula1);
-                return Err(JsValue::from_str("Formula error: cycle or invalid input."));
-            }
-            if sheet.formula_strings[cell_index].is_empty() {
-                let default_formula = format!(
-                    "{}=0",
-                    display::column_index_to_name(cell_index % sheet.cols)
-                        + &((cell_index / sheet.cols) + 1).to_string()
-                );
-                sheet.undo_stack.push_back((cell_index, default_formula));
-            } else {
-                let old_formula = sheet.formula_strings[cell_index].clone();
-                sheet.undo_stack.push_back((cell_index, old_formula));
-            }
-            sheet.formula_strings[cell_index] = input.to_string();
-            sheet.redo_stack.clear();
-        } else {
+        if !input.contains('=') {
             return Err(JsValue::from_str("Invalid formula input"));
         }
+        edit::assign(&mut sheet, input).map_err(|e| JsValue::from_str(&e))?;
 
         Ok(display::render_spreadsheet(
             sheet.curr_x,
@@ -206,6 +208,7 @@ pub fn update_formula(input: &str) -> Result<String, wasm_bindgen::prelude::JsVa
             &sheet.arr,
             sheet.cols,
             sheet.rows,
+            sheet.view.precision,
         ))
     })
 }
@@ -221,8 +224,7 @@ pub fn get_formula(cell_id: &str) -> Result<String, wasm_bindgen::prelude::JsVal
         if cell_index == -1 {
             return Err(JsValue::from_str("Invalid cell ID"));
         }
-        let formula = &sheet.formula_strings[cell_index as usize];
-        Ok(formula.clone()) // Return the formula string
+        Ok(sheet.formula(cell_index as usize).to_string()) // Return the formula string
     })
 }
 
@@ -231,18 +233,14 @@ pub fn get_formula(cell_id: &str) -> Result<String, wasm_bindgen::prelude::JsVal
 pub fn undo() -> Result<String, wasm_bindgen::prelude::JsValue> {
     SPREADSHEET.with(|s| {
         let mut sheet = s.borrow_mut();
-        if let Some((idx, old_formula)) = sheet.undo_stack.pop_back() {
-            let current_formula = sheet.formula_strings[idx].clone();
-            sheet.redo_stack.push_back((idx, current_formula));
-            sheet.formula_strings[idx] = old_formula.clone();
-            input_parser::parser(&mut sheet, &old_formula);
-        }
+        edit::undo(&mut sheet);
         Ok(display::render_spreadsheet(
             sheet.curr_x,
             sheet.curry,
             &sheet.arr,
             sheet.cols,
             sheet.rows,
+            sheet.view.precision,
         ))
     })
 }
@@ -252,18 +250,14 @@ pub fn undo() -> Result<String, wasm_bindgen::prelude::JsValue> {
 pub fn redo() -> Result<String, wasm_bindgen::prelude::JsValue> {
     SPREADSHEET.with(|s| {
         let mut sheet = s.borrow_mut();
-        if let Some((idx, redo_formula)) = sheet.redo_stack.pop_back() {
-            let current_formula = sheet.formula_strings[idx].clone();
-            sheet.undo_stack.push_back((idx, current_formula));
-            sheet.formula_strings[idx] = redo_formula.clone();
-            input_parser::parser(&mut sheet, &redo_formula);
-        }
+        edit::redo(&mut sheet);
         Ok(display::render_spreadsheet(
             sheet.curr_x,
             sheet.curry,
             &sheet.arr,
             sheet.cols,
             sheet.rows,
+            sheet.view.precision,
         ))
     })
 }
@@ -271,20 +265,138 @@ pub fn redo() -> Result<String, wasm_bindgen::prelude::JsValue> {
 #[cfg(feature = "wasm")]
 #[wasm_bindgen]
 pub fn export_csv() -> String {
+    SPREADSHEET.with(|s| csv::to_csv(&s.borrow()))
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn export_json() -> String {
+    SPREADSHEET.with(|s| json::export_cells(&s.borrow()))
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn import_json(text: &str) -> Result<String, wasm_bindgen::prelude::JsValue> {
+    SPREADSHEET.with(|s| {
+        let mut sheet = s.borrow_mut();
+        *sheet = json::from_json(text).map_err(|e| JsValue::from_str(&e))?;
+        Ok(display::render_spreadsheet(
+            sheet.curr_x,
+            sheet.curry,
+            &sheet.arr,
+            sheet.cols,
+            sheet.rows,
+            sheet.view.precision,
+        ))
+    })
+}
+
+/// Cells related to `spec` (a cell or range) for highlighting, as JSON:
+/// `{"precedents": ["A1", ...], "dependents": [...]}`, both transitive.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn related_cells(spec: &str) -> Result<String, wasm_bindgen::prelude::JsValue> {
+    use trace::{trace_dependents, trace_precedents, Depth};
+
     SPREADSHEET.with(|s| {
         let sheet = s.borrow();
-        let mut csv_data = String::new();
+        let cells = command::cell_indices(spec, &sheet).map_err(|e| JsValue::from_str(&e))?;
+        let names = |found: Vec<usize>| -> Vec<String> {
+            found
+                .into_iter()
+                .map(|c| json::cell_name(c, sheet.cols))
+                .collect()
+        };
+        Ok(serde_json::json!({
+            "precedents": names(trace_precedents(&sheet, &cells, Depth::Transitive)),
+            "dependents": names(trace_dependents(&sheet, &cells, Depth::Transitive)),
+        })
+        .to_string())
+    })
+}
 
-        for row in 0..sheet.rows {
-            let mut row_data = Vec::new();
-            for col in 0..sheet.cols {
-                let cell_value = &sheet.arr[row * sheet.cols + col];
-                row_data.push(cell_value.to_string());
+/// The dependency graph as `dot` or `json` (see [`depgraph`]), limited to the
+/// cone around `cell` when one is given.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn export_graph(
+    format: &str,
+    cell: Option<String>,
+) -> Result<String, wasm_bindgen::prelude::JsValue> {
+    SPREADSHEET.with(|s| {
+        let sheet = s.borrow();
+        let focus = match cell {
+            Some(name) => {
+                Some(command::one_cell(&name, &sheet).map_err(|e| JsValue::from_str(&e))?)
             }
-            csv_data.push_str(&row_data.join(","));
-            csv_data.push('\n');
-        }
+            None => None,
+        };
+        depgraph::export(&sheet, format, focus).map_err(|e| JsValue::from_str(&e))
+    })
+}
+
+/// Allow circular references, evaluated iteratively up to `max_iterations`
+/// passes until no cell moves by more than `tolerance`; `enabled = false`
+/// rejects them again.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn set_iteration(
+    enabled: bool,
+    max_iterations: usize,
+    tolerance: f64,
+) -> Result<(), wasm_bindgen::prelude::JsValue> {
+    SPREADSHEET.with(|s| {
+        let mut sheet = s.borrow_mut();
+        sheet.graph.iteration = if enabled {
+            Some(
+                iteration::Iteration::new(max_iterations, tolerance)
+                    .map_err(|e| JsValue::from_str(&e))?,
+            )
+        } else {
+            None
+        };
+        Ok(())
+    })
+}
+
+/// Compute numbers as `"float"` (`f64`), `"int"` (exact `i64`, overflowing
+/// to `#NUM!`) or `"decimal SCALE [half-even|half-up]"` (fixed-point) and
+/// return the recomputed sheet's HTML.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn set_numbers(mode: &str) -> Result<String, wasm_bindgen::prelude::JsValue> {
+    SPREADSHEET.with(|s| {
+        let mut sheet = s.borrow_mut();
+        command::set_numbers(&mut sheet, mode).map_err(|e| JsValue::from_str(&e))?;
+        Ok(display::render_spreadsheet(
+            sheet.curr_x,
+            sheet.curry,
+            &sheet.arr,
+            sheet.cols,
+            sheet.rows,
+            sheet.view.precision,
+        ))
+    })
+}
 
-        csv_data
+/// Show numbers with `decimals` decimal places, or automatically (whole
+/// numbers as integers, fractions rounded) when `None`.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn set_precision(decimals: Option<usize>) -> Result<String, wasm_bindgen::prelude::JsValue> {
+    SPREADSHEET.with(|s| {
+        let mut sheet = s.borrow_mut();
+        sheet
+            .view
+            .set_precision(decimals)
+            .map_err(|e| JsValue::from_str(&e))?;
+        Ok(display::render_spreadsheet(
+            sheet.curr_x,
+            sheet.curry,
+            &sheet.arr,
+            sheet.cols,
+            sheet.rows,
+            sheet.view.precision,
+        ))
     })
 }
@@ -0,0 +1,117 @@
+//! Module `lookup`.
+//!
+//! Finding a key in one row or column of a table, for `VLOOKUP`, `HLOOKUP`,
+//! `MATCH` and `XLOOKUP`.
+//!
+//! A key only matches values of its own kind, so the number 1 never matches
+//! `TRUE` or the text `"1"`; empty cells and errors never match. Texts match
+//! and order ignoring case. The approximate modes do not
+//! need the table to be sorted: they pick the closest value on the allowed
+//! side of the key, the first one if it occurs more than once.
+
+use std::cmp::Ordering;
+
+use crate::value::Value;
+
+/// Which value a lookup settles for when the key itself is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// None: only the key.
+    Exact,
+    /// The largest value below the key.
+    NextSmaller,
+    /// The smallest value above the key.
+    NextLarger,
+}
+
+/// The position of `key` in `entries`, each a value and whether its cell is
+/// empty, or of the closest value `mode` allows.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::lookup::{find, MatchMode};
+/// use lab1::value::Value;
+///
+/// let rates = [10, 20, 30].map(|x| (Value::from(x), false));
+/// assert_eq!(find(&Value::from(20), &rates, MatchMode::Exact), Some(1));
+/// assert_eq!(find(&Value::from(25), &rates, MatchMode::Exact), None);
+/// assert_eq!(find(&Value::from(25), &rates, MatchMode::NextSmaller), Some(1));
+/// assert_eq!(find(&Value::from(25), &rates, MatchMode::NextLarger), Some(2));
+/// assert_eq!(find(&Value::from(5), &rates, MatchMode::NextSmaller), None);
+/// ```
+pub fn find(key: &Value, entries: &[(Value, bool)], mode: MatchMode) -> Option<usize> {
+    let mut best: Option<(usize, &Value)> = None;
+    for (i, (v, blank)) in entries.iter().enumerate() {
+        if *blank || !v.same_kind(key) {
+            continue;
+        }
+        // errors are unordered and fall through here
+        let closer = match (mode, v.collate(key)) {
+            (_, Some(Ordering::Equal)) => return Some(i),
+            (MatchMode::NextSmaller, Some(Ordering::Less)) => {
+                best.is_none_or(|(_, b)| v.collate(b) == Some(Ordering::Greater))
+            }
+            (MatchMode::NextLarger, Some(Ordering::Greater)) => {
+                best.is_none_or(|(_, b)| v.collate(b) == Some(Ordering::Less))
+            }
+            _ => false,
+        };
+        if closer {
+            best = Some((i, v));
+        }
+    }
+    best.map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ERR;
+
+    #[test]
+    fn test_kinds_blanks_and_ties() {
+        let entries = [
+            (Value::from(0), true),
+            (ERR, false),
+            (Value::Bool(true), false),
+            (Value::from(3), false),
+            (Value::from(1), false),
+            (Value::Int(3), false),
+        ];
+        assert_eq!(find(&Value::from(0), &entries, MatchMode::Exact), None);
+        assert_eq!(find(&Value::from(1), &entries, MatchMode::Exact), Some(4));
+        assert_eq!(
+            find(&Value::Bool(true), &entries, MatchMode::Exact),
+            Some(2)
+        );
+        // unsorted, and the first of equal values wins
+        assert_eq!(
+            find(&Value::from(9), &entries, MatchMode::NextSmaller),
+            Some(3)
+        );
+        assert_eq!(
+            find(&Value::from(2), &entries, MatchMode::NextLarger),
+            Some(3)
+        );
+        assert_eq!(find(&ERR, &entries, MatchMode::NextLarger), None);
+    }
+
+    #[test]
+    fn test_texts_ignore_case() {
+        let names = ["pear", "Apple", "fig"].map(|t| (Value::text(t), false));
+        assert_eq!(
+            find(&Value::text("APPLE"), &names, MatchMode::Exact),
+            Some(1)
+        );
+        assert_eq!(
+            find(&Value::text("b"), &names, MatchMode::NextLarger),
+            Some(2)
+        );
+        assert_eq!(
+            find(&Value::text("z"), &names, MatchMode::NextSmaller),
+            Some(0)
+        );
+        assert_eq!(find(&Value::from(1), &names, MatchMode::NextLarger), None);
+    }
+}
@@ -0,0 +1,272 @@
+//! Module `math`.
+//!
+//! The math functions of expressions: `ABS`, `SIGN`, `INT`, `MOD`,
+//! `ROUND`, `ROUNDUP`, `ROUNDDOWN`, `POWER`, `SQRT`, `EXP`, `LN`, `LOG10`,
+//! `PI` and the trigonometric functions, with angles in radians.
+//!
+//! An argument outside a function's domain, such as `SQRT(-1)`, `LN(0)`,
+//! `ASIN(2)` or `MOD(5, 0)`, gives `ERR`; a result too large to represent
+//! gives `#NUM!`. `ABS`, `SIGN`, `INT` and `MOD` are exact for exact
+//! numbers, and the `ROUND` family rounds the decimal digits of a number
+//! rather than its binary approximation, so `ROUND(2.675, 2)` is `2.68`.
+
+use crate::graph::arith_in;
+use crate::value::{NumberMode, Value, ERR, NUM};
+
+/// A math function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Math {
+    Abs,
+    Sign,
+    /// Rounding down to a whole number.
+    Int,
+    /// The remainder, with the sign of the divisor.
+    Mod,
+    /// Half away from zero.
+    Round,
+    /// Away from zero.
+    RoundUp,
+    /// Toward zero.
+    RoundDown,
+    Power,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Pi,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    /// `ATAN2(x, y)`: the angle of the point `(x, y)`.
+    Atan2,
+    Degrees,
+    Radians,
+}
+
+/// `f` applied to `args`, whose count the parser has checked, on a sheet
+/// of mode `numbers`. The first error among the arguments is passed on;
+/// booleans count as 1 and 0, and text is [`ERR`].
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::math::{apply, Math};
+/// use lab1::value::{NumberMode, Value, ERR};
+///
+/// let float = NumberMode::Float;
+/// assert_eq!(apply(Math::Round, &[Value::from(2.675), Value::from(2)], float), 2.68);
+/// assert_eq!(apply(Math::Mod, &[Value::from(-7), Value::from(3)], float), 2);
+/// assert_eq!(apply(Math::Sqrt, &[Value::from(-1)], float), ERR);
+/// ```
+pub fn apply(f: Math, args: &[Value], numbers: NumberMode) -> Value {
+    if let Some(e) = args.iter().find(|v| v.is_error()) {
+        return e.clone();
+    }
+    if args.iter().any(|v| matches!(v, Value::Text(_))) {
+        return ERR;
+    }
+    let args: Vec<Value> = args
+        .iter()
+        .map(|v| match *v {
+            Value::Bool(b) => numbers.whole(b as i64),
+            _ => v.clone(),
+        })
+        .collect();
+    let x = args.first().and_then(|v| v.number()).unwrap_or(0.0);
+    let y = args.get(1).and_then(|v| v.number()).unwrap_or(0.0);
+    let float = |x: f64| numbers.float(x);
+    match f {
+        Math::Abs if x < 0.0 => arith_in(numbers, numbers.whole(0), args[0].clone(), '-'),
+        Math::Abs => args[0].clone(),
+        Math::Sign => numbers.whole(x.partial_cmp(&0.0).map_or(0, |o| o as i64)),
+        Math::Int => floor(&args[0]),
+        Math::Mod => modulo(&args[0], &args[1], numbers),
+        Math::Round | Math::RoundUp | Math::RoundDown => {
+            let digits = args.get(1).map_or(0.0, |_| y.trunc());
+            round(&args[0], digits.clamp(-300.0, 300.0) as i32, f).map_or(ERR, float)
+        }
+        Math::Power => power(&args[0], &args[1], numbers),
+        Math::Sqrt if x < 0.0 => ERR,
+        Math::Sqrt => float(x.sqrt()),
+        Math::Exp => float(x.exp()),
+        Math::Ln | Math::Log10 if x <= 0.0 => ERR,
+        Math::Ln => float(x.ln()),
+        Math::Log10 => float(x.log10()),
+        Math::Pi => float(std::f64::consts::PI),
+        Math::Sin => float(x.sin()),
+        Math::Cos => float(x.cos()),
+        Math::Tan => float(x.tan()),
+        Math::Asin | Math::Acos if x.abs() > 1.0 => ERR,
+        Math::Asin => float(x.asin()),
+        Math::Acos => float(x.acos()),
+        Math::Atan => float(x.atan()),
+        Math::Atan2 if x == 0.0 && y == 0.0 => ERR,
+        Math::Atan2 => float(y.atan2(x)),
+        Math::Degrees => float(x.to_degrees()),
+        Math::Radians => float(x.to_radians()),
+    }
+}
+
+/// `v` rounded down to a whole number, kept in its own kind.
+fn floor(v: &Value) -> Value {
+    match *v {
+        Value::Decimal(mut d) => {
+            let one = 10i64.pow(d.scale);
+            d.units = d.units.div_euclid(one) * one;
+            Value::Decimal(d)
+        }
+        Value::Number(x) => Value::Number(x.floor()),
+        _ => v.clone(),
+    }
+}
+
+/// `a` modulo `b`: what remains of `a` after taking away a whole multiple
+/// of `b`, with the sign of `b`. A zero divisor is [`ERR`].
+fn modulo(a: &Value, b: &Value, numbers: NumberMode) -> Value {
+    // exact numbers as whole counts of a common unit
+    let units = match (a, b, numbers) {
+        (&Value::Int(a), &Value::Int(b), _) => Some((a, b, None)),
+        (_, _, NumberMode::Decimal(mode)) => {
+            match (mode.convert(a.clone()), mode.convert(b.clone())) {
+                (Value::Decimal(a), Value::Decimal(b)) => Some((a.units, b.units, Some(a))),
+                (e, _) | (_, e) if e.is_error() => return e,
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    match units {
+        Some((_, 0, _)) => ERR,
+        Some((a, b, unit)) => {
+            let r = a.checked_rem(b).unwrap_or(0);
+            let r = if r != 0 && (r < 0) != (b < 0) {
+                r + b
+            } else {
+                r
+            };
+            match unit {
+                Some(mut d) => {
+                    d.units = r;
+                    Value::Decimal(d)
+                }
+                None => Value::Int(r),
+            }
+        }
+        None => {
+            let (x, y) = (a.number().unwrap_or(0.0), b.number().unwrap_or(0.0));
+            if y == 0.0 {
+                return ERR;
+            }
+            numbers.float(x - y * (x / y).floor())
+        }
+    }
+}
+
+/// `v` rounded to `digits` decimals (tens, hundreds, ... for negative
+/// `digits`) the way `f` rounds. The decimal exponent is shifted in the
+/// number's text, so no binary error creeps into the digit rounded.
+fn round(v: &Value, digits: i32, f: Math) -> Option<f64> {
+    let shifted: f64 = format!("{}e{}", v, digits).parse().ok()?;
+    if !shifted.is_finite() {
+        return v.number();
+    }
+    let rounded = match f {
+        Math::Round => shifted.round(),
+        Math::RoundUp if shifted < 0.0 => shifted.floor(),
+        Math::RoundUp => shifted.ceil(),
+        _ => shifted.trunc(),
+    };
+    format!("{}e{}", rounded, -digits).parse().ok()
+}
+
+/// `a` to the power `b`: exact for whole numbers of an integer sheet.
+/// Zero to a negative power, or a negative number to a fractional one, is
+/// [`ERR`].
+fn power(a: &Value, b: &Value, numbers: NumberMode) -> Value {
+    if let (&Value::Int(a), &Value::Int(b)) = (a, b) {
+        if let Some(p) = u32::try_from(b).ok().and_then(|b| a.checked_pow(b)) {
+            return Value::Int(p);
+        }
+    }
+    let (x, y) = (a.number().unwrap_or(0.0), b.number().unwrap_or(0.0));
+    if (x == 0.0 && y < 0.0) || (x < 0.0 && y.fract() != 0.0) {
+        return ERR;
+    }
+    match x.powf(y) {
+        p if p.is_infinite() => NUM,
+        p => numbers.float(p),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decimal::{DecimalMode, Rounding};
+
+    fn call(f: Math, args: &[f64]) -> Value {
+        let args: Vec<Value> = args.iter().map(|&x| Value::from(x)).collect();
+        apply(f, &args, NumberMode::Float)
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(call(Math::Round, &[-2.5]), -3);
+        assert_eq!(call(Math::Round, &[1234.5, -2.0]), 1200);
+        assert_eq!(call(Math::Round, &[1.005, 2.0]), 1.01);
+        assert_eq!(call(Math::RoundUp, &[-3.111, 1.0]), -3.2);
+        assert_eq!(call(Math::RoundDown, &[3.999, 2.0]), 3.99);
+        assert_eq!(call(Math::Round, &[1e300, 300.0]), 1e300);
+        assert_eq!(call(Math::Int, &[-2.5]), -3);
+    }
+
+    #[test]
+    fn test_domains() {
+        assert_eq!(call(Math::Ln, &[0.0]), ERR);
+        assert_eq!(call(Math::Log10, &[1000.0]), 3);
+        assert_eq!(call(Math::Asin, &[1.5]), ERR);
+        assert_eq!(call(Math::Mod, &[5.0, 0.0]), ERR);
+        assert_eq!(call(Math::Power, &[0.0, -1.0]), ERR);
+        assert_eq!(call(Math::Power, &[-8.0, 1.0 / 3.0]), ERR);
+        assert_eq!(call(Math::Power, &[10.0, 400.0]), NUM);
+        assert_eq!(call(Math::Exp, &[1000.0]), NUM);
+        assert_eq!(call(Math::Atan2, &[0.0, 0.0]), ERR);
+        assert_eq!(call(Math::Sign, &[-0.5]), -1);
+        assert_eq!(apply(Math::Abs, &[ERR], NumberMode::Float), ERR);
+        assert_eq!(
+            apply(Math::Abs, &[Value::text("1")], NumberMode::Float),
+            ERR
+        );
+    }
+
+    #[test]
+    fn test_exact_modes() {
+        let int = |f, args: &[i64]| {
+            let args: Vec<Value> = args.iter().map(|&n| Value::Int(n)).collect();
+            apply(f, &args, NumberMode::Int)
+        };
+        assert!(matches!(int(Math::Mod, &[-7, 3]), Value::Int(2)));
+        assert!(matches!(int(Math::Mod, &[7, -3]), Value::Int(-2)));
+        assert!(matches!(
+            int(Math::Power, &[3, 39]),
+            Value::Int(4052555153018976267)
+        ));
+        assert_eq!(int(Math::Abs, &[i64::MIN]), NUM);
+        let cents: NumberMode = DecimalMode::new(2, Rounding::HalfEven).unwrap().into();
+        let d = |x: f64| match cents {
+            NumberMode::Decimal(mode) => mode.convert(Value::from(x)),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            apply(Math::Mod, &[d(5.3), d(1.1)], cents).to_string(),
+            "0.90"
+        );
+        assert_eq!(apply(Math::Int, &[d(-1.25)], cents).to_string(), "-2.00");
+        assert_eq!(
+            apply(Math::Round, &[d(2.25), d(1.0)], cents).to_string(),
+            "2.30"
+        );
+    }
+}
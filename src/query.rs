@@ -0,0 +1,222 @@
+//! Module `query`.
+//!
+//! One-off evaluation without a session: load a CSV, apply a list of
+//! `A1=EXPR` edits, and report the values of some cells in a machine-readable
+//! format. This is what `spreadsheet eval` runs.
+//!
+//! The sheet is sized to fit the CSV and every cell named by an edit or a
+//! requested cell, so `E1=SUM(A1:A100)` works on a 3-row CSV.
+
+use serde_json::{Map, Value};
+
+use crate::command::{parse_cells, print_cells};
+use crate::csv::{from_csv, value_text};
+use crate::display::column_index_to_name;
+use crate::edit::assign;
+use crate::json::{cell_value, split_cell_name};
+use crate::spreadsheet::{Spreadsheet, MAX_COLS, MAX_ROWS};
+
+/// Output format of [`report`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Values as `print` shows them: one block per requested cell or range.
+    Text,
+    /// One `NAME,VALUE` line per requested cell, ranges expanded row by row.
+    Csv,
+    /// An object mapping each request to a number (or `null` for `ERR`), or
+    /// an array of rows for a range.
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format {} (expected text, csv or json)", s)),
+        }
+    }
+}
+
+/// Smallest `(rows, cols)` that contains every cell name mentioned in `texts`.
+///
+/// Text in string literals and `'` labels is skipped, and so are names
+/// followed by `(` (functions such as `LOG10`) and names outside the largest
+/// sheet, which are left for the edit or print that uses them to reject.
+fn extent<'a>(texts: impl IntoIterator<Item = &'a str>) -> (usize, usize) {
+    let mut rows = 0;
+    let mut cols = 0;
+    for text in texts {
+        // `A1='label` is text from the quote on
+        let text = match text.split_once('=') {
+            Some((lhs, rhs)) if rhs.trim_start().starts_with('\'') => lhs,
+            _ => text,
+        };
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'"' {
+                // a doubled quote inside a literal closes and reopens it
+                i += 1 + bytes[i + 1..]
+                    .iter()
+                    .position(|&b| b == b'"')
+                    .map_or(bytes.len(), |n| n + 1);
+                continue;
+            }
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_uppercase() {
+                i += 1;
+            }
+            let letters = i;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let preceded = start > 0 && bytes[start - 1].is_ascii_alphanumeric();
+            let call = bytes.get(i) == Some(&b'(');
+            if letters > start && i > letters && !preceded && !call {
+                if let Some((r, c)) = split_cell_name(&text[start..i]) {
+                    if r < MAX_ROWS && c < MAX_COLS {
+                        rows = rows.max(r + 1);
+                        cols = cols.max(c + 1);
+                    }
+                }
+            }
+            if i == start {
+                i += 1;
+            }
+        }
+    }
+    (rows, cols)
+}
+
+/// Build the sheet for a query: `csv` (if any) plus every edit in `sets`,
+/// applied in order.
+///
+/// # Errors
+///
+/// Returns a message if the CSV cannot be imported or an edit is rejected.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::query::evaluate;
+///
+/// let sheet = evaluate(Some("1\n2\n3\n"), &["E1=SUM(A1:A100)"], &["E1"]).unwrap();
+/// assert_eq!(sheet.rows, 100);
+/// assert_eq!(sheet.arr[4], 6);
+/// ```
+pub fn evaluate(csv: Option<&str>, sets: &[&str], prints: &[&str]) -> Result<Spreadsheet, String> {
+    let (rows, cols) = extent(sets.iter().chain(prints).copied());
+    let mut sheet = from_csv(csv.unwrap_or(""), rows, cols)?;
+    for set in sets {
+        assign(&mut sheet, set).map_err(|e| format!("{}: {}", set.trim(), e))?;
+    }
+    Ok(sheet)
+}
+
+/// Format the values of `prints` (cells or ranges) in `format`.
+///
+/// # Errors
+///
+/// Returns a message for an invalid cell or range.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::query::{evaluate, report, Format};
+///
+/// let sheet = evaluate(Some("4,5\n"), &["C1=A1*B1"], &[]).unwrap();
+/// assert_eq!(report(&sheet, &["C1", "A1:B1"], Format::Json).unwrap(),
+///            r#"{"A1:B1":[[4,5]],"C1":20}"#);
+/// assert_eq!(report(&sheet, &["A1:C1"], Format::Csv).unwrap(), "A1,4\nB1,5\nC1,20");
+/// ```
+pub fn report(sheet: &Spreadsheet, prints: &[&str], format: Format) -> Result<String, String> {
+    match format {
+        Format::Text => {
+            let blocks: Result<Vec<String>, String> =
+                prints.iter().map(|spec| print_cells(sheet, spec)).collect();
+            Ok(blocks?.join("\n"))
+        }
+        Format::Csv => {
+            let mut lines = Vec::new();
+            for spec in prints {
+                let (r1, c1, r2, c2) = parse_cells(spec, sheet)?;
+                for r in r1..=r2 {
+                    for c in c1..=c2 {
+                        lines.push(format!(
+                            "{}{},{}",
+                            column_index_to_name(c),
+                            r + 1,
+                            value_text(&sheet.arr[r * sheet.cols + c])
+                        ));
+                    }
+                }
+            }
+            Ok(lines.join("\n"))
+        }
+        Format::Json => {
+            let mut out = Map::new();
+            for spec in prints {
+                let (r1, c1, r2, c2) = parse_cells(spec, sheet)?;
+                let value = if spec.contains(':') {
+                    Value::Array(
+                        (r1..=r2)
+                            .map(|r| {
+                                Value::Array(
+                                    (c1..=c2)
+                                        .map(|c| cell_value(&sheet.arr[r * sheet.cols + c]))
+                                        .collect(),
+                                )
+                            })
+                            .collect(),
+                    )
+                } else {
+                    cell_value(&sheet.arr[r1 * sheet.cols + c1])
+                };
+                out.insert(spec.trim().to_string(), value);
+            }
+            Ok(Value::Object(out).to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extent() {
+        assert_eq!(extent(["E1=SUM(A1:A100)", "B3"]), (100, 5));
+        assert_eq!(extent(["MAX(B2:C4)", "x1"]), (4, 3));
+        assert_eq!(extent([""]), (0, 0));
+        assert_eq!(extent([r#"A1=LEN("QQQ999999")"#, "B2=LOG10(A1)"]), (2, 2));
+        assert_eq!(extent(["C3='ZZZ9 and \"x\"", r#"A1="a""B5""#]), (3, 3));
+        assert_eq!(extent(["ZZZZZZ1", "A1001", "B2"]), (2, 2));
+    }
+
+    #[test]
+    fn test_evaluate_without_input() {
+        let sheet = evaluate(None, &["A1=2", "B1=A1*3"], &["B1"]).unwrap();
+        assert_eq!((sheet.rows, sheet.cols), (1, 2));
+        assert_eq!(report(&sheet, &["B1"], Format::Text).unwrap(), "6");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(evaluate(None, &["A1=A1+1"], &[]).is_err());
+        let sheet = evaluate(None, &["B1=A1/0"], &[]).unwrap();
+        assert_eq!(
+            report(&sheet, &["B1"], Format::Json).unwrap(),
+            r#"{"B1":null}"#
+        );
+        assert!(report(&sheet, &["C9"], Format::Csv).is_err());
+        // cells past the largest sheet are rejected, not allocated
+        let sheet = evaluate(None, &[], &["ZZZZZZ1"]).unwrap();
+        assert!(report(&sheet, &["ZZZZZZ1"], Format::Text).is_err());
+        assert!(evaluate(None, &["A1=ZZZZ1"], &[]).is_err());
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,370 @@
+//! Module `rpc`.
+//!
+//! Newline-delimited JSON requests and responses, so editor plugins and test
+//! harnesses can drive the engine without scraping the terminal grid. Each
+//! input line is one request and produces exactly one response line:
+//!
+//! ```text
+//! → {"id": 1, "method": "set", "params": {"cell": "B1", "formula": "A1+2"}}
+//! ← {"id":1,"result":{"cell":"B1","changed":{"B1":2},"formula":"A1+2","value":2}}
+//! → {"id": 2, "method": "get", "params": {"cell": "Z99"}}
+//! ← {"error":{"code":-32602,"message":"invalid cell Z99"},"id":2}
+//! ```
+//!
+//! Methods:
+//! - `set` `{cell, formula}` (or `{input: "B1=A1+2"}`): apply an edit; the
+//!   result lists every cell whose value changed
+//! - `get` `{cell}`: value, formula text and error marker of one cell
+//! - `range` `{range: "A1:C3"}`: values as an array of rows
+//! - `undo` / `redo`: revert or re-apply an edit; `cell` is `null` when
+//!   there was nothing to do
+//! - `export` `{format: "csv" | "json" | "values"}`: the whole sheet
+//! - `new` `{rows, cols}`: replace the sheet with an empty one
+//!
+//! Values are typed as in the [JSON export](crate::json): numbers, booleans,
+//! strings for text and dates (e.g. `"2024-05-01"`), and `null` for error
+//! cells (which also carry the error, e.g. `"error": "ERR"` or
+//! `"error": "#NUM!"`). Errors use JSON-RPC
+//! codes: `-32700` unparsable line, `-32600` malformed request, `-32601`
+//! unknown method, `-32602` bad parameters, and `1` for an edit the parser
+//! rejected (with the offending input in `data`).
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Map, Value};
+
+use crate::command::{export, parse_cells};
+use crate::edit::{assign, redo, undo};
+use crate::expr::cell_index;
+use crate::json::{cell_name, cell_value};
+use crate::spreadsheet::{initialize_spreadsheet, Spreadsheet, MAX_COLS, MAX_ROWS};
+use crate::trace::{trace_dependents, Depth};
+
+/// JSON-RPC error codes used in responses.
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The parser rejected an edit (invalid formula or a cycle).
+pub const EDIT_REJECTED: i64 = 1;
+
+/// A failed request: code, message and optional structured details.
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn params(message: impl Into<String>) -> RpcError {
+        RpcError::new(INVALID_PARAMS, message)
+    }
+}
+
+/// Value, formula text and error marker of the cell at `idx`.
+fn describe(sheet: &Spreadsheet, idx: usize) -> Value {
+    let value = &sheet.arr[idx];
+    let mut cell = json!({ "cell": cell_name(idx, sheet.cols), "value": cell_value(value) });
+    if let Some((_, rhs)) = sheet.formula(idx).split_once('=') {
+        cell["formula"] = json!(rhs);
+    }
+    if value.is_error() {
+        cell["error"] = json!(value.to_string());
+    }
+    cell
+}
+
+fn str_param<'a>(params: &'a Value, key: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::params(format!("missing string parameter \"{}\"", key)))
+}
+
+fn cell_param(sheet: &Spreadsheet, params: &Value, key: &str) -> Result<usize, RpcError> {
+    let name = str_param(params, key)?;
+    if name.contains(':') {
+        return Err(RpcError::params(format!(
+            "expected a single cell, got {}",
+            name
+        )));
+    }
+    let (r, c, _, _) = parse_cells(name, sheet).map_err(RpcError::params)?;
+    Ok(r * sheet.cols + c)
+}
+
+fn size_param(params: &Value, key: &str, max: u64) -> Result<usize, RpcError> {
+    match params.get(key).and_then(Value::as_u64) {
+        Some(n) if (1..=max).contains(&n) => Ok(n as usize),
+        _ => Err(RpcError::params(format!(
+            "\"{}\" must be an integer between 1 and {}",
+            key, max
+        ))),
+    }
+}
+
+/// Result of an undo/redo: the affected cell, or `null`.
+fn history_result(sheet: &Spreadsheet, idx: Option<usize>) -> Value {
+    idx.map(|idx| describe(sheet, idx))
+        .unwrap_or(json!({ "cell": null }))
+}
+
+fn call(sheet: &mut Spreadsheet, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "set" => {
+            let input = match params.get("input").and_then(Value::as_str) {
+                Some(input) => input.to_string(),
+                None => format!(
+                    "{}={}",
+                    str_param(params, "cell")?,
+                    str_param(params, "formula")?
+                ),
+            };
+            // only the edited cell and what depends on it can change
+            let mut affected: Vec<usize> = input
+                .split_once('=')
+                .and_then(|(name, _)| cell_index(name.trim(), sheet.cols, sheet.rows))
+                .map_or_else(Vec::new, |cell| {
+                    let mut cells = trace_dependents(sheet, &[cell], Depth::Transitive);
+                    cells.push(cell);
+                    cells
+                });
+            affected.sort_unstable();
+            let before: Vec<_> = affected.iter().map(|&c| sheet.arr[c].clone()).collect();
+            let idx = assign(sheet, &input).map_err(|e| RpcError {
+                code: EDIT_REJECTED,
+                message: e,
+                data: Some(json!({ "input": input })),
+            })?;
+            let changed: Map<String, Value> = affected
+                .iter()
+                .zip(before)
+                .filter(|&(&c, ref old)| sheet.arr[c] != *old)
+                .map(|(&c, _)| (cell_name(c, sheet.cols), cell_value(&sheet.arr[c])))
+                .collect();
+            let mut result = describe(sheet, idx);
+            result["changed"] = Value::Object(changed);
+            Ok(result)
+        }
+        "get" => Ok(describe(sheet, cell_param(sheet, params, "cell")?)),
+        "range" => {
+            let spec = str_param(params, "range")?;
+            let (r1, c1, r2, c2) = parse_cells(spec, sheet).map_err(RpcError::params)?;
+            let values: Vec<Value> = (r1..=r2)
+                .map(|r| {
+                    (c1..=c2)
+                        .map(|c| cell_value(&sheet.arr[r * sheet.cols + c]))
+                        .collect()
+                })
+                .collect();
+            Ok(json!({ "range": spec, "values": values }))
+        }
+        "undo" => {
+            let idx = undo(sheet);
+            Ok(history_result(sheet, idx))
+        }
+        "redo" => {
+            let idx = redo(sheet);
+            Ok(history_result(sheet, idx))
+        }
+        "export" => {
+            let format = str_param(params, "format")?;
+            let data = export(sheet, format).map_err(RpcError::params)?;
+            let data = match format {
+                "csv" => json!(data),
+                _ => serde_json::from_str(&data).unwrap_or(Value::Null),
+            };
+            Ok(json!({ "format": format, "data": data }))
+        }
+        "new" => {
+            let rows = size_param(params, "rows", MAX_ROWS as u64)?;
+            let cols = size_param(params, "cols", MAX_COLS as u64)?;
+            *sheet = initialize_spreadsheet(rows, cols);
+            Ok(json!({ "rows": rows, "cols": cols }))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {}", method),
+        )),
+    }
+}
+
+/// Handle one request line and return the response line (without newline).
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::rpc::handle;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// let reply = handle(&mut sheet, r#"{"id":7,"method":"set","params":{"input":"A1=5"}}"#);
+/// assert_eq!(reply, r#"{"id":7,"result":{"cell":"A1","changed":{"A1":5},"formula":"5","value":5}}"#);
+/// ```
+pub fn handle(sheet: &mut Spreadsheet, line: &str) -> String {
+    let (id, outcome) = match serde_json::from_str::<Value>(line) {
+        Err(e) => (
+            Value::Null,
+            Err(RpcError::new(PARSE_ERROR, format!("invalid JSON: {}", e))),
+        ),
+        Ok(request) => {
+            let id = request.get("id").cloned().unwrap_or(Value::Null);
+            let params = request.get("params").cloned().unwrap_or(json!({}));
+            let outcome = match request.get("method").and_then(Value::as_str) {
+                None => Err(RpcError::new(
+                    INVALID_REQUEST,
+                    "request must be an object with a string \"method\"",
+                )),
+                Some(_) if !params.is_object() => Err(RpcError::new(
+                    INVALID_REQUEST,
+                    "\"params\" must be an object",
+                )),
+                Some(method) => call(sheet, method, &params),
+            };
+            (id, outcome)
+        }
+    };
+    match outcome {
+        Ok(result) => json!({ "id": id, "result": result }),
+        Err(e) => {
+            let mut error = json!({ "code": e.code, "message": e.message });
+            if let Some(data) = e.data {
+                error["data"] = data;
+            }
+            json!({ "id": id, "error": error })
+        }
+    }
+    .to_string()
+}
+
+/// Answer every non-blank request line from `input` on `out` until EOF,
+/// flushing after each response.
+pub fn serve<R: BufRead, W: Write>(
+    sheet: &mut Spreadsheet,
+    input: R,
+    out: &mut W,
+) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(out, "{}", handle(sheet, &line))?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(sheet: &mut Spreadsheet, line: &str) -> Value {
+        serde_json::from_str(&handle(sheet, line)).unwrap()
+    }
+
+    #[test]
+    fn test_set_reports_changed_cells() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        request(
+            &mut sheet,
+            r#"{"method":"set","params":{"cell":"B1","formula":"A1*2"}}"#,
+        );
+        let reply = request(
+            &mut sheet,
+            r#"{"id":"x","method":"set","params":{"cell":"A1","formula":"4"}}"#,
+        );
+        assert_eq!(reply["id"], "x");
+        assert_eq!(reply["result"]["changed"], json!({"A1": 4, "B1": 8}));
+        let reply = request(
+            &mut sheet,
+            r#"{"id":2,"method":"get","params":{"cell":"B1"}}"#,
+        );
+        assert_eq!(reply["result"]["formula"], "A1*2");
+        assert_eq!(reply["result"]["value"], 8);
+    }
+
+    #[test]
+    fn test_set_follows_dependents_only() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        for input in ["B1=A1*2", "C1=B1+1", "A2=MIN(A1, 0)", "C3=7"] {
+            assign(&mut sheet, input).unwrap();
+        }
+        let reply = request(&mut sheet, r#"{"method":"set","params":{"input":"A1=5"}}"#);
+        assert_eq!(
+            reply["result"]["changed"],
+            json!({"A1": 5, "B1": 10, "C1": 11})
+        );
+    }
+
+    #[test]
+    fn test_range_undo_redo_export() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        request(
+            &mut sheet,
+            r#"{"method":"set","params":{"input":"B2=A1/0"}}"#,
+        );
+        let reply = request(
+            &mut sheet,
+            r#"{"method":"range","params":{"range":"A1:B2"}}"#,
+        );
+        assert_eq!(reply["result"]["values"], json!([[0, 0], [0, null]]));
+        let reply = request(&mut sheet, r#"{"method":"undo"}"#);
+        assert_eq!(reply["result"]["cell"], "B2");
+        assert_eq!(reply["result"]["value"], 0);
+        let reply = request(&mut sheet, r#"{"method":"redo"}"#);
+        assert_eq!(reply["result"]["error"], "ERR");
+        let reply = request(&mut sheet, r#"{"method":"redo"}"#);
+        assert_eq!(reply["result"]["cell"], Value::Null);
+        let reply = request(
+            &mut sheet,
+            r#"{"method":"export","params":{"format":"values"}}"#,
+        );
+        assert_eq!(reply["result"]["data"], json!([[0, 0], [0, null]]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        let code = |reply: Value| reply["error"]["code"].as_i64().unwrap();
+        assert_eq!(code(request(&mut sheet, "{")), PARSE_ERROR);
+        assert_eq!(code(request(&mut sheet, r#"{"id":1}"#)), INVALID_REQUEST);
+        assert_eq!(
+            code(request(&mut sheet, r#"{"method":"fly"}"#)),
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            code(request(
+                &mut sheet,
+                r#"{"method":"get","params":{"cell":"C3"}}"#
+            )),
+            INVALID_PARAMS
+        );
+        let reply = request(
+            &mut sheet,
+            r#"{"id":5,"method":"set","params":{"input":"A1=A1+1"}}"#,
+        );
+        assert_eq!(reply["id"], 5);
+        assert_eq!(code(reply.clone()), EDIT_REJECTED);
+        assert_eq!(reply["error"]["data"]["input"], "A1=A1+1");
+    }
+
+    #[test]
+    fn test_serve_and_new() {
+        let mut sheet = initialize_spreadsheet(1, 1);
+        let input = "{\"method\":\"new\",\"params\":{\"rows\":4,\"cols\":5}}\n\n{\"method\":\"get\",\"params\":{\"cell\":\"E4\"}}\n";
+        let mut out = Vec::new();
+        serve(&mut sheet, input.as_bytes(), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert_eq!((sheet.rows, sheet.cols), (4, 5));
+    }
+}
@@ -0,0 +1,384 @@
+//! Module `schedule`.
+//!
+//! Time and cancellation for evaluation. Every sheet's graph carries a
+//! [`Scheduler`] that `SLEEP` waits through and that
+//! [`recalculate`](crate::graph::recalculate) consults between cells, so a
+//! long edit can be stopped either by a [`CancelToken`] (set from another
+//! thread or a Ctrl-C handler) or by a per-edit time budget. An interrupted
+//! edit is rolled back completely: the edited cell keeps its old formula and
+//! every dependent keeps its old value.
+//!
+//! Time comes from a [`Clock`]. [`SystemClock`] is the real one;
+//! [`ManualClock`] only moves when something sleeps on it or it is advanced
+//! by hand, which keeps tests instant; the WebAssembly build sleeps on one
+//! too (a browser tab cannot block) but reads the date from the browser. The clock's calendar time is also what
+//! `TODAY()` and `NOW()` read, so a test can fix the date.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest single wait, so cancellation is noticed promptly.
+const SLICE: Duration = Duration::from_millis(20);
+
+/// A source of time that can also wait.
+pub trait Clock: Send + Sync {
+    /// Time elapsed since an arbitrary fixed origin.
+    fn now(&self) -> Duration;
+    /// Calendar time: time elapsed since the Unix epoch, in UTC.
+    fn wall(&self) -> Duration;
+    /// Block for `d`.
+    fn sleep(&self, d: Duration);
+}
+
+/// Wall-clock time with real sleeping.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct SystemClock(std::time::Instant);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock(std::time::Instant::now())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+
+    fn wall(&self) -> Duration {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+
+    fn sleep(&self, d: Duration) {
+        std::thread::sleep(d);
+    }
+}
+
+/// A clock that never blocks: sleeping moves it forward instantly. Clones
+/// share the same time, so a test can keep one and hand another to a sheet.
+/// Its origin is the Unix epoch, so advancing it also sets its calendar
+/// time.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use lab1::schedule::{Clock, ManualClock};
+///
+/// let clock = ManualClock::default();
+/// clock.sleep(Duration::from_secs(3));
+/// clock.advance(Duration::from_millis(500));
+/// assert_eq!(clock.now(), Duration::from_millis(3500));
+/// assert_eq!(clock.wall(), clock.now());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock(Arc<AtomicU64>);
+
+impl ManualClock {
+    /// Move the clock forward by `d`.
+    pub fn advance(&self, d: Duration) {
+        self.0.fetch_add(d.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::SeqCst))
+    }
+
+    fn wall(&self) -> Duration {
+        self.now()
+    }
+
+    fn sleep(&self, d: Duration) {
+        self.advance(d);
+    }
+}
+
+/// A shareable flag asking the running evaluation to stop.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Ask the evaluation in progress to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether [`cancel`](CancelToken::cancel) was called since the last
+    /// reset.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// The underlying flag, e.g. for a signal handler to set.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.0)
+    }
+
+    fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Why an evaluation stopped early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    /// The [`CancelToken`] was set.
+    Cancelled,
+    /// The edit ran past its time budget.
+    TimedOut(Duration),
+}
+
+impl fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interrupt::Cancelled => write!(f, "Evaluation cancelled; edit rolled back"),
+            Interrupt::TimedOut(budget) => write!(
+                f,
+                "Evaluation exceeded the {:.1}s time budget; edit rolled back",
+                budget.as_secs_f64()
+            ),
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+#[wasm_bindgen::prelude::wasm_bindgen]
+extern "C" {
+    /// Milliseconds since the Unix epoch, from JavaScript's `Date.now()`.
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn browser_now() -> f64;
+}
+
+/// The WebAssembly clock: a [`ManualClock`] for sleeping and time budgets
+/// (a browser tab cannot block), with calendar time read from the browser
+/// on every call so `TODAY()` and `NOW()` keep up after the page loads.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Default)]
+struct BrowserClock(ManualClock);
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for BrowserClock {
+    fn now(&self) -> Duration {
+        self.0.now()
+    }
+
+    #[cfg(feature = "wasm")]
+    fn wall(&self) -> Duration {
+        Duration::from_millis(browser_now() as u64)
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn wall(&self) -> Duration {
+        self.0.wall()
+    }
+
+    fn sleep(&self, d: Duration) {
+        self.0.sleep(d);
+    }
+}
+
+/// Clock, cancel token and time budget for one sheet.
+#[derive(Clone)]
+pub struct Scheduler {
+    clock: Arc<dyn Clock>,
+    cancel: CancelToken,
+    budget: Option<Duration>,
+    deadline: Option<Duration>,
+    interrupted: Option<Interrupt>,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("budget", &self.budget)
+            .field("deadline", &self.deadline)
+            .field("interrupted", &self.interrupted)
+            .finish()
+    }
+}
+
+impl Default for Scheduler {
+    /// The system clock, or under WebAssembly a [`ManualClock`] for sleeping
+    /// whose calendar time is the browser's; no budget.
+    fn default() -> Scheduler {
+        #[cfg(not(target_arch = "wasm32"))]
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock::default());
+        #[cfg(target_arch = "wasm32")]
+        let clock: Arc<dyn Clock> = Arc::new(BrowserClock::default());
+        Scheduler::new(clock)
+    }
+}
+
+impl Scheduler {
+    /// A scheduler reading time from `clock`, with no budget.
+    pub fn new(clock: Arc<dyn Clock>) -> Scheduler {
+        Scheduler {
+            clock,
+            cancel: CancelToken::default(),
+            budget: None,
+            deadline: None,
+            interrupted: None,
+        }
+    }
+
+    /// Use `clock` from now on.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// The clock's calendar time (see [`Clock::wall`]).
+    pub fn wall(&self) -> Duration {
+        self.clock.wall()
+    }
+
+    /// Limit each edit to `budget` of evaluation time, or lift the limit.
+    pub fn set_budget(&mut self, budget: Option<Duration>) {
+        self.budget = budget;
+    }
+
+    /// The per-edit time budget, if any.
+    pub fn budget(&self) -> Option<Duration> {
+        self.budget
+    }
+
+    /// A handle that cancels the edit in progress.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Start timing an edit: arm the budget and forget earlier cancel
+    /// requests.
+    pub fn begin_edit(&mut self) {
+        self.cancel.reset();
+        self.interrupted = None;
+        self.deadline = self.budget.map(|b| self.clock.now() + b);
+    }
+
+    /// Stop timing the current edit and report whether it was interrupted.
+    pub fn end_edit(&mut self) -> Option<Interrupt> {
+        self.cancel.reset();
+        self.deadline = None;
+        self.interrupted.take()
+    }
+
+    /// `true` if evaluation may go on; otherwise records why not.
+    pub fn check(&mut self) -> bool {
+        if self.interrupted.is_some() {
+            return false;
+        }
+        if self.cancel.is_cancelled() {
+            self.interrupted = Some(Interrupt::Cancelled);
+        } else if let (Some(deadline), Some(budget)) = (self.deadline, self.budget) {
+            if self.clock.now() >= deadline {
+                self.interrupted = Some(Interrupt::TimedOut(budget));
+            }
+        }
+        self.interrupted.is_none()
+    }
+
+    /// Wait for `d`, giving up early (and returning `false`) if the edit is
+    /// cancelled or its budget runs out first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use lab1::schedule::{Clock, Interrupt, ManualClock, Scheduler};
+    ///
+    /// let clock = ManualClock::default();
+    /// let mut scheduler = Scheduler::new(Arc::new(clock.clone()));
+    /// scheduler.set_budget(Some(Duration::from_secs(2)));
+    /// scheduler.begin_edit();
+    /// assert!(!scheduler.sleep(Duration::from_secs(5)));
+    /// assert_eq!(clock.now(), Duration::from_secs(2));
+    /// assert_eq!(scheduler.end_edit(), Some(Interrupt::TimedOut(Duration::from_secs(2))));
+    /// ```
+    pub fn sleep(&mut self, d: Duration) -> bool {
+        let until = self.clock.now() + d;
+        loop {
+            if !self.check() {
+                return false;
+            }
+            let now = self.clock.now();
+            if now >= until {
+                return true;
+            }
+            let mut step = (until - now).min(SLICE);
+            if let Some(deadline) = self.deadline {
+                step = step.min(deadline.saturating_sub(now));
+            }
+            self.clock.sleep(step);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manual() -> (ManualClock, Scheduler) {
+        let clock = ManualClock::default();
+        let scheduler = Scheduler::new(Arc::new(clock.clone()));
+        (clock, scheduler)
+    }
+
+    #[test]
+    fn test_sleep_without_budget_runs_to_completion() {
+        let (clock, mut scheduler) = manual();
+        scheduler.begin_edit();
+        assert!(scheduler.sleep(Duration::from_secs(90)));
+        assert_eq!(clock.now(), Duration::from_secs(90));
+        assert_eq!(scheduler.end_edit(), None);
+    }
+
+    #[test]
+    fn test_cancel_is_reset_per_edit() {
+        let (_, mut scheduler) = manual();
+        let token = scheduler.cancel_token();
+        token.cancel();
+        scheduler.begin_edit();
+        assert!(scheduler.check());
+        token.cancel();
+        assert!(!scheduler.sleep(Duration::from_secs(1)));
+        assert_eq!(scheduler.end_edit(), Some(Interrupt::Cancelled));
+        assert_eq!(scheduler.end_edit(), None);
+        assert!(!token.is_cancelled());
+    }
+
+    #[test]
+    fn test_budget_counts_from_begin_edit() {
+        let (clock, mut scheduler) = manual();
+        scheduler.set_budget(Some(Duration::from_secs(3)));
+        clock.advance(Duration::from_secs(100));
+        scheduler.begin_edit();
+        assert!(scheduler.sleep(Duration::from_secs(2)));
+        assert!(scheduler.check());
+        clock.advance(Duration::from_secs(1));
+        assert!(!scheduler.check());
+    }
+
+    #[test]
+    fn test_system_clock_sleep_can_be_cancelled() {
+        let mut scheduler = Scheduler::default();
+        let token = scheduler.cancel_token();
+        scheduler.begin_edit();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+        let start = std::time::Instant::now();
+        assert!(!scheduler.sleep(Duration::from_secs(30)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        canceller.join().unwrap();
+    }
+}
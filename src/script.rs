@@ -0,0 +1,88 @@
+//! Module `script`.
+//!
+//! Non-interactive execution of a file (or stream) of terminal commands, for
+//! using the engine in shell pipelines. Each line is run through
+//! [`execute`]; text produced by `print`/`export` lines is written to the
+//! output as it happens, and nothing else is printed.
+
+use std::io::{self, BufRead, Write};
+
+use crate::command::{execute, Outcome};
+use crate::spreadsheet::Spreadsheet;
+
+/// Run every command read from `input` against `sheet`.
+///
+/// Stops at the first failing line unless `keep_going` is set. Returns the
+/// error messages (prefixed with their line number); an empty list means the
+/// whole script succeeded.
+///
+/// # Errors
+///
+/// Only I/O errors while reading `input` or writing `out` are returned as
+/// `Err`.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::script::run_script;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(5, 5);
+/// let script = "A1=2\nA2=A1*21\nprint A2\n";
+/// let mut out = Vec::new();
+/// let errors = run_script(&mut sheet, script.as_bytes(), &mut out, false).unwrap();
+/// assert!(errors.is_empty());
+/// assert_eq!(String::from_utf8(out).unwrap(), "42\n");
+/// ```
+pub fn run_script<R: BufRead, W: Write>(
+    sheet: &mut Spreadsheet,
+    input: R,
+    out: &mut W,
+    keep_going: bool,
+) -> io::Result<Vec<String>> {
+    let mut errors = Vec::new();
+    for (n, line) in input.lines().enumerate() {
+        let line = line?;
+        match execute(sheet, &line) {
+            Ok(Outcome::Output(text)) => writeln!(out, "{}", text)?,
+            Ok(_) => {}
+            Err(e) => {
+                errors.push(format!("line {}: {} ({})", n + 1, e, line.trim()));
+                if !keep_going {
+                    break;
+                }
+            }
+        }
+    }
+    out.flush()?;
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spreadsheet::initialize_spreadsheet;
+
+    const SCRIPT: &str = "# totals\nA1=1\nA2=A1+\nA3=5\nprint A3\n";
+
+    #[test]
+    fn test_stops_at_first_error() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        let mut out = Vec::new();
+        let errors = run_script(&mut sheet, SCRIPT.as_bytes(), &mut out, false).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("line 3:"));
+        assert!(out.is_empty());
+        assert_eq!(sheet.arr[0], 1);
+        assert_eq!(sheet.arr[10], 0);
+    }
+
+    #[test]
+    fn test_keep_going() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        let mut out = Vec::new();
+        let errors = run_script(&mut sheet, SCRIPT.as_bytes(), &mut out, true).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(String::from_utf8(out).unwrap(), "5\n");
+    }
+}
@@ -55,7 +55,12 @@ pub fn scroller(cmd: &str, spreadsheet: &mut Spreadsheet) {
         return;
     }
 
-    // Otherwise, fallback to existing scroll logic
+    // w/a/s/d honour the sheet's view settings; anything else goes to the
+    // fixed-size legacy handler
+    if matches!(cmd, "w" | "a" | "s" | "d") {
+        step(spreadsheet, cmd);
+        return;
+    }
     scroller_display(
         cmd,
         &spreadsheet.arr,
@@ -67,37 +72,39 @@ pub fn scroller(cmd: &str, spreadsheet: &mut Spreadsheet) {
     );
 }
 
-/// Scroll up by a page (10 rows)
+/// Apply one `w`/`a`/`s`/`d` scroll using the sheet's view settings.
+fn step(spreadsheet: &mut Spreadsheet, cmd: &str) {
+    spreadsheet.view.scroll(
+        cmd,
+        &mut spreadsheet.curr_x,
+        &mut spreadsheet.curry,
+        spreadsheet.cols,
+        spreadsheet.rows,
+    );
+}
+
+/// Scroll up by a page (`view.scroll_step` rows, 10 by default)
 pub fn scroll_up(spreadsheet: &mut Spreadsheet) {
-    if spreadsheet.curry < 10 {
-        spreadsheet.curry = 0;
-    } else {
-        spreadsheet.curry -= 10;
-    }
+    step(spreadsheet, "w");
 }
 
-/// Scroll down by a page (10 rows)
+/// Scroll down by a page (`view.scroll_step` rows, 10 by default)
 pub fn scroll_down(spreadsheet: &mut Spreadsheet) {
-    let remaining = spreadsheet.rows.saturating_sub(spreadsheet.curry + 10);
-    spreadsheet.curry += remaining.min(10);
+    step(spreadsheet, "s");
 }
 
-/// Scroll left by a page (10 columns)
+/// Scroll left by a page (`view.scroll_step` columns, 10 by default)
 pub fn scroll_left(spreadsheet: &mut Spreadsheet) {
-    if spreadsheet.curr_x < 10 {
-        spreadsheet.curr_x = 0;
-    } else {
-        spreadsheet.curr_x -= 10;
-    }
+    step(spreadsheet, "a");
 }
 
-/// Scroll right by a page (10 columns)
+/// Scroll right by a page (`view.scroll_step` columns, 10 by default)
 pub fn scroll_right(spreadsheet: &mut Spreadsheet) {
-    let remaining = spreadsheet.cols.saturating_sub(spreadsheet.curr_x + 10);
-    spreadsheet.curr_x += remaining.min(10);
+    step(spreadsheet, "d");
 }
 
 #[cfg(test)]
+#[allow(unused_imports)]
 mod tests {
     use super::*;
     use crate::spreadsheet::{initialize_spreadsheet, Spreadsheet};
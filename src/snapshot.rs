@@ -0,0 +1,757 @@
+//! Module `snapshot`.
+//!
+//! A compact, versioned binary image of a [`Spreadsheet`] for fast startup of
+//! big sheets. Only non-empty cells are written, formula texts are interned,
+//! and loading restores values and formulas directly instead of re-running
+//! every formula through the parser.
+//!
+//! Layout (all integers little-endian):
+//!
+//! ```text
+//! magic    b"LSNP"
+//! version  u8        (currently 3)
+//! flags    u8        bit 0 = payload is deflate-compressed
+//! rows     u32
+//! cols     u32
+//! crc32    u32       checksum of the uncompressed payload
+//! numbers  u8        0 = float, 1 = int, 2 = decimal rounding half-even,
+//!                    3 = decimal rounding half-up
+//! scale    u8        decimals of a decimal sheet
+//! max_iter u32       iteration limit, 0 = circular references rejected
+//! tolerance f64      iteration tolerance
+//! payload:
+//!   varint n_texts, then n_texts × (varint len, UTF-8 bytes)
+//!   varint n_cells, then n_cells × (
+//!     varint gap      cells skipped since the previous entry
+//!     value           u8 kind: 0 = whole number, then zigzag;
+//!                     1 = other number, then f64; 2 = ERR;
+//!                     3 = exact integer, then zigzag i64; 4 = #NUM!;
+//!                     5 = decimal, then u8 scale and zigzag i64 units;
+//!                     6 = boolean, then u8 0 or 1; 7 = #N/A;
+//!                     8 = text, then varint len and UTF-8 bytes;
+//!                     9 = date, then f64 LE serial
+//!     varint op_type, zigzag p1, zigzag p2
+//!     varint text     0 = no formula text, else 1 + index into the texts
+//!   )
+//! ```
+//!
+//! Version 1 snapshots, which stored every value as a zigzag integer with
+//! `i32::MIN` for `ERR`, and version 2 snapshots, which had no settings and
+//! load as a float sheet without iteration, can still be loaded.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+
+use crate::decimal::{Decimal, DecimalMode, Rounding, MAX_SCALE};
+use crate::display::column_index_to_name;
+use crate::expr::{parse, Expr, EXPR};
+use crate::graph::{recalculate_all, Formula, Graph};
+use crate::iteration::Iteration;
+use crate::spreadsheet::{initialize_spreadsheet, Spreadsheet, MAX_COLS, MAX_ROWS};
+use crate::trace::formula_precedents;
+use crate::value::{CellError, NumberMode, Value, ERR, MAX_TEXT, NA, NUM};
+
+const MAGIC: &[u8; 4] = b"LSNP";
+const VERSION: u8 = 3;
+const VALUE_WHOLE: u8 = 0;
+const VALUE_FLOAT: u8 = 1;
+const VALUE_ERR: u8 = 2;
+const VALUE_INT: u8 = 3;
+const VALUE_NUM: u8 = 4;
+const VALUE_DECIMAL: u8 = 5;
+const VALUE_BOOL: u8 = 6;
+const VALUE_NA: u8 = 7;
+const VALUE_TEXT: u8 = 8;
+const VALUE_DATE: u8 = 9;
+const FLAG_DEFLATE: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 1 + 4 + 4 + 4;
+const SETTINGS_LEN: usize = 1 + 1 + 4 + 8;
+/// Fewest bytes a stored cell takes: index gap, value, formula type, two
+/// operands and text index, one byte each.
+const MIN_CELL_LEN: usize = 6;
+/// Most bytes a stored cell can take: its fixed fields as ten-byte varints,
+/// plus a text value and a formula text of up to [`MAX_TEXT`] four-byte
+/// characters each with their lengths. Bounds how far a compressed payload
+/// is inflated.
+const MAX_CELL_LEN: u64 = 6 * 10 + 1 + 2 * (10 + 4 * MAX_TEXT as u64);
+
+fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn put_zigzag(out: &mut Vec<u8>, v: i32) {
+    put_varint(out, ((v << 1) ^ (v >> 31)) as u32 as u64);
+}
+
+fn put_zigzag64(out: &mut Vec<u8>, v: i64) {
+    put_varint(out, ((v << 1) ^ (v >> 63)) as u64);
+}
+
+fn put_value(out: &mut Vec<u8>, v: &Value) {
+    match *v {
+        Value::Number(x) if x.fract() == 0.0 && x >= i32::MIN as f64 && x <= i32::MAX as f64 => {
+            out.push(VALUE_WHOLE);
+            put_zigzag(out, x as i32);
+        }
+        Value::Number(x) => {
+            out.push(VALUE_FLOAT);
+            out.extend_from_slice(&x.to_le_bytes());
+        }
+        Value::Int(i) => {
+            out.push(VALUE_INT);
+            put_zigzag64(out, i);
+        }
+        Value::Decimal(d) => {
+            out.push(VALUE_DECIMAL);
+            out.push(d.scale as u8);
+            put_zigzag64(out, d.units);
+        }
+        Value::Bool(b) => {
+            out.push(VALUE_BOOL);
+            out.push(b as u8);
+        }
+        Value::Text(ref t) => {
+            out.push(VALUE_TEXT);
+            put_varint(out, t.len() as u64);
+            out.extend_from_slice(t.as_bytes());
+        }
+        Value::Date(x) => {
+            out.push(VALUE_DATE);
+            out.extend_from_slice(&x.to_le_bytes());
+        }
+        Value::Error(CellError::Err) => out.push(VALUE_ERR),
+        Value::Error(CellError::Num) => out.push(VALUE_NUM),
+        Value::Error(CellError::Na) => out.push(VALUE_NA),
+    }
+}
+
+/// The sheet's number mode and iteration settings, [`SETTINGS_LEN`] bytes.
+fn put_settings(out: &mut Vec<u8>, graph: &Graph) {
+    let (numbers, scale) = match graph.numbers {
+        NumberMode::Float => (0, 0),
+        NumberMode::Int => (1, 0),
+        NumberMode::Decimal(mode) if mode.rounding == Rounding::HalfEven => (2, mode.scale),
+        NumberMode::Decimal(mode) => (3, mode.scale),
+    };
+    out.push(numbers);
+    out.push(scale as u8);
+    let (max, tolerance) = graph
+        .iteration
+        .map_or((0, 0.0), |it| (it.max_iterations as u32, it.tolerance));
+    out.extend_from_slice(&max.to_le_bytes());
+    out.extend_from_slice(&tolerance.to_le_bytes());
+}
+
+/// Decode what [`put_settings`] wrote.
+fn settings(bytes: &[u8]) -> Result<(NumberMode, Option<Iteration>), String> {
+    let corrupt = |e: String| format!("Corrupt snapshot settings: {}", e);
+    let numbers = match bytes[0] {
+        0 => NumberMode::Float,
+        1 => NumberMode::Int,
+        n @ (2 | 3) => {
+            let rounding = if n == 2 {
+                Rounding::HalfEven
+            } else {
+                Rounding::HalfUp
+            };
+            DecimalMode::new(bytes[1] as u32, rounding)
+                .map_err(corrupt)?
+                .into()
+        }
+        n => return Err(corrupt(format!("unknown number mode {}", n))),
+    };
+    let max = u32::from_le_bytes(bytes[2..6].try_into().unwrap());
+    let tolerance = f64::from_le_bytes(bytes[6..14].try_into().unwrap());
+    let iteration = match max {
+        0 => None,
+        max => Some(Iteration::new(max as usize, tolerance).map_err(corrupt)?),
+    };
+    Ok((numbers, iteration))
+}
+
+/// Cursor over the payload bytes.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.buf.get(self.pos).ok_or("Truncated snapshot")?;
+            self.pos += 1;
+            v |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err("Malformed varint in snapshot".to_string())
+    }
+
+    fn zigzag(&mut self) -> Result<i32, String> {
+        let v = self.varint()? as u32;
+        Ok(((v >> 1) as i32) ^ -((v & 1) as i32))
+    }
+
+    fn zigzag64(&mut self) -> Result<i64, String> {
+        let v = self.varint()?;
+        Ok(((v >> 1) as i64) ^ -((v & 1) as i64))
+    }
+
+    fn value(&mut self, version: u8) -> Result<Value, String> {
+        if version == 1 {
+            return Ok(match self.zigzag()? {
+                i32::MIN => ERR,
+                v => Value::from(v),
+            });
+        }
+        match self.bytes(1)?[0] {
+            VALUE_WHOLE => Ok(Value::from(self.zigzag()?)),
+            VALUE_FLOAT => {
+                let mut raw = [0u8; 8];
+                raw.copy_from_slice(self.bytes(8)?);
+                Ok(Value::Number(f64::from_le_bytes(raw)))
+            }
+            VALUE_ERR => Ok(ERR),
+            VALUE_INT => Ok(Value::Int(self.zigzag64()?)),
+            VALUE_NUM => Ok(NUM),
+            VALUE_NA => Ok(NA),
+            VALUE_DECIMAL => {
+                let scale = self.bytes(1)?[0] as u32;
+                if scale > MAX_SCALE {
+                    return Err(format!("Decimal scale {} out of range in snapshot", scale));
+                }
+                let units = self.zigzag64()?;
+                Ok(Value::Decimal(Decimal { units, scale }))
+            }
+            VALUE_BOOL => Ok(Value::Bool(self.bytes(1)?[0] != 0)),
+            VALUE_TEXT => {
+                let len = self.varint()? as usize;
+                let text = std::str::from_utf8(self.bytes(len)?)
+                    .map_err(|_| "Invalid UTF-8 in snapshot")?;
+                Ok(Value::text(text))
+            }
+            VALUE_DATE => {
+                let mut raw = [0u8; 8];
+                raw.copy_from_slice(self.bytes(8)?);
+                Ok(Value::Date(f64::from_le_bytes(raw)))
+            }
+            kind => Err(format!("Unknown value kind {} in snapshot", kind)),
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.pos.checked_add(len).ok_or("Truncated snapshot")?;
+        let slice = self.buf.get(self.pos..end).ok_or("Truncated snapshot")?;
+        self.pos = end;
+        Ok(slice)
+    }
+}
+
+/// Hook up the dependency edges the parser would have installed for `f`.
+fn link(graph: &mut Graph, cell: usize, f: Formula, cols: usize) {
+    let srcs = match f.op_type {
+        EXPR => graph.exprs.get(&cell).map(|e| e.refs(cols)),
+        _ => Some(formula_precedents(&f, cell, cols)),
+    };
+    for src in srcs.unwrap_or_default() {
+        graph.adj.entry(src).or_default().push(cell);
+    }
+}
+
+/// Encode `sheet` as a snapshot, optionally deflate-compressing the payload.
+///
+/// Undo/redo history and the viewport position are not part of a snapshot.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::snapshot::{load, save};
+/// use lab1::spreadsheet::initialize_spreadsheet;
+///
+/// let mut sheet = initialize_spreadsheet(100, 100);
+/// assign(&mut sheet, "A1=5").unwrap();
+/// assign(&mut sheet, "B1=A1*2").unwrap();
+///
+/// let bytes = save(&sheet, true);
+/// let mut copy = load(&bytes).unwrap();
+/// assert_eq!(copy.arr[1], 10);
+/// assert_eq!(copy.formula(1), "B1=A1*2");
+///
+/// // dependencies are live again after loading
+/// assign(&mut copy, "A1=7").unwrap();
+/// assert_eq!(copy.arr[1], 14);
+/// ```
+pub fn save(sheet: &Spreadsheet, compress: bool) -> Vec<u8> {
+    let mut texts: Vec<&str> = Vec::new();
+    let mut text_ids = std::collections::HashMap::new();
+    let mut cells = Vec::new();
+    let mut count = 0u64;
+    let mut next = 0usize;
+
+    for idx in 0..sheet.rows * sheet.cols {
+        let f = sheet.formula_array[idx];
+        let value = &sheet.arr[idx];
+        // formula texts are stored without their "A1=" prefix so that
+        // identical right-hand sides share one entry
+        let rhs = sheet
+            .formula(idx)
+            .split_once('=')
+            .map(|(_, rhs)| rhs)
+            .unwrap_or("");
+        if *value == 0 && f.op_type == 0 && f.p1 == 0 && rhs.is_empty() {
+            continue;
+        }
+        let text = if sheet.formula(idx).is_empty() {
+            0
+        } else {
+            1 + *text_ids.entry(rhs).or_insert_with(|| {
+                texts.push(rhs);
+                texts.len() - 1
+            }) as u64
+        };
+        put_varint(&mut cells, (idx - next) as u64);
+        put_value(&mut cells, value);
+        put_varint(&mut cells, f.op_type as u32 as u64);
+        put_zigzag(&mut cells, f.p1);
+        put_zigzag(&mut cells, f.p2);
+        put_varint(&mut cells, text);
+        count += 1;
+        next = idx + 1;
+    }
+
+    let mut payload = Vec::new();
+    put_varint(&mut payload, texts.len() as u64);
+    for text in &texts {
+        put_varint(&mut payload, text.len() as u64);
+        payload.extend_from_slice(text.as_bytes());
+    }
+    put_varint(&mut payload, count);
+    payload.extend_from_slice(&cells);
+
+    let mut crc = Crc::new();
+    crc.update(&payload);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + SETTINGS_LEN + payload.len());
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.push(if compress { FLAG_DEFLATE } else { 0 });
+    out.extend_from_slice(&(sheet.rows as u32).to_le_bytes());
+    out.extend_from_slice(&(sheet.cols as u32).to_le_bytes());
+    out.extend_from_slice(&crc.sum().to_le_bytes());
+    put_settings(&mut out, &sheet.graph);
+    if compress {
+        let mut encoder = DeflateEncoder::new(out, Compression::fast());
+        // writing into a Vec cannot fail
+        encoder.write_all(&payload).unwrap();
+        encoder.finish().unwrap()
+    } else {
+        out.extend_from_slice(&payload);
+        out
+    }
+}
+
+/// Decode a snapshot produced by [`save`].
+///
+/// # Errors
+///
+/// Returns a message if the magic bytes, version or checksum do not match, if
+/// the sheet is larger than [`MAX_ROWS`] × [`MAX_COLS`], or if the payload is
+/// truncated or refers to cells outside the sheet.
+pub fn load(bytes: &[u8]) -> Result<Spreadsheet, String> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err("Not a spreadsheet snapshot".to_string());
+    }
+    let version = bytes[4];
+    if !(1..=VERSION).contains(&version) {
+        return Err(format!("Unsupported snapshot version {}", version));
+    }
+    let header_len = match version {
+        1 | 2 => HEADER_LEN,
+        _ => HEADER_LEN + SETTINGS_LEN,
+    };
+    if bytes.len() < header_len {
+        return Err("Truncated snapshot".to_string());
+    }
+    let (numbers, iteration) = match version {
+        1 | 2 => (NumberMode::Float, None),
+        _ => settings(&bytes[HEADER_LEN..header_len])?,
+    };
+    let flags = bytes[5];
+    let word =
+        |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    let rows = word(6) as usize;
+    let cols = word(10) as usize;
+    let checksum = word(14);
+    if !(1..=MAX_ROWS).contains(&rows) || !(1..=MAX_COLS).contains(&cols) {
+        return Err(format!(
+            "Snapshot sheet of {} × {} cells is outside {} × {}",
+            rows, cols, MAX_ROWS, MAX_COLS
+        ));
+    }
+
+    let body = &bytes[header_len..];
+    let inflated;
+    let payload = if flags & FLAG_DEFLATE != 0 {
+        // the two counts, then at most every cell of the sheet
+        let limit = 2 * 10 + (rows * cols) as u64 * MAX_CELL_LEN;
+        let mut buf = Vec::new();
+        DeflateDecoder::new(body)
+            .take(limit + 1)
+            .read_to_end(&mut buf)
+            .map_err(|e| format!("Corrupt snapshot: {}", e))?;
+        if buf.len() as u64 > limit {
+            return Err("Snapshot payload is larger than its sheet".to_string());
+        }
+        inflated = buf;
+        &inflated[..]
+    } else {
+        body
+    };
+    let mut crc = Crc::new();
+    crc.update(payload);
+    if crc.sum() != checksum {
+        return Err("Snapshot checksum mismatch".to_string());
+    }
+
+    let mut rd = Reader {
+        buf: payload,
+        pos: 0,
+    };
+    let n_texts = rd.varint()? as usize;
+    let mut texts = Vec::with_capacity(n_texts.min(payload.len()));
+    for _ in 0..n_texts {
+        let len = rd.varint()? as usize;
+        let text = std::str::from_utf8(rd.bytes(len)?).map_err(|_| "Invalid UTF-8 in snapshot")?;
+        texts.push(text.to_string());
+    }
+
+    // check the cell count against the sheet and the bytes left before
+    // allocating, so a corrupt header cannot ask for more than the file holds
+    let total = rows
+        .checked_mul(cols)
+        .ok_or("Snapshot sheet is too large")?;
+    let n_cells = rd.varint()?;
+    let room = (payload.len() - rd.pos) / MIN_CELL_LEN;
+    if n_cells > total as u64 || n_cells > room as u64 {
+        return Err("Truncated snapshot".to_string());
+    }
+
+    let mut sheet = initialize_spreadsheet(rows, cols);
+    sheet.graph.numbers = numbers;
+    sheet.graph.iteration = iteration;
+    let mut next = 0usize;
+    for _ in 0..n_cells {
+        let idx = next
+            .checked_add(rd.varint()? as usize)
+            .filter(|&i| i < total)
+            .ok_or("Snapshot cell out of range")?;
+        sheet.arr[idx] = rd.value(version)?;
+        let op_type = rd.varint()? as u32 as i32;
+        let p1 = rd.zigzag()?;
+        let p2 = rd.zigzag()?;
+        let f = Formula { op_type, p1, p2 };
+        let refs_ok = match op_type {
+            1..=14 => {
+                (p1 as u32 as usize) < total
+                    && (!(5..=13).contains(&op_type) || (p2 as u32 as usize) < total)
+            }
+            _ => true,
+        };
+        if !refs_ok {
+            return Err("Snapshot formula refers outside the sheet".to_string());
+        }
+        sheet.formula_array[idx] = f;
+        let text = rd.varint()? as usize;
+        if text > 0 {
+            let rhs = texts
+                .get(text - 1)
+                .ok_or("Snapshot text index out of range")?;
+            if op_type == EXPR {
+                let expr =
+                    parse(rhs, cols, rows).map_err(|e| format!("Snapshot formula: {}", e))?;
+                sheet.graph.exprs.insert(idx, expr);
+            }
+            let text = format!(
+                "{}{}={}",
+                column_index_to_name(idx % cols),
+                idx / cols + 1,
+                rhs
+            );
+            sheet.set_formula(idx, text);
+        } else if op_type == EXPR {
+            return Err("Snapshot formula has no text".to_string());
+        }
+        link(&mut sheet.graph, idx, f, cols);
+        next = idx + 1;
+    }
+    // what INDIRECT and OFFSET read is only found by evaluating them
+    if sheet.graph.exprs.values().any(Expr::is_dynamic) {
+        recalculate_all(
+            &mut sheet.graph,
+            cols as i32,
+            &mut sheet.arr,
+            &sheet.formula_array,
+        );
+    }
+    Ok(sheet)
+}
+
+/// Write a snapshot of `sheet` to `path`.
+pub fn save_file(sheet: &Spreadsheet, path: &Path, compress: bool) -> std::io::Result<()> {
+    fs::write(path, save(sheet, compress))
+}
+
+/// Read a snapshot from `path`.
+pub fn load_file(path: &Path) -> Result<Spreadsheet, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    load(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::execute;
+    use crate::edit::assign;
+
+    fn sample() -> Spreadsheet {
+        let mut sheet = initialize_spreadsheet(10, 10);
+        for input in [
+            "A1=5",
+            "A2=-3",
+            "B1=A1*2",
+            "C1=A1+A2",
+            "D1=SUM(A1:A3)",
+            "E1=B1/0",
+            "F1=A1/4",
+            "G1=0.125",
+            "H1=IF(A1>A2, B1, SUM(A1:A2))",
+            r#"I1=INDIRECT("A"&2)"#,
+        ] {
+            assign(&mut sheet, input).unwrap();
+        }
+        sheet
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        let mut buf = Vec::new();
+        for v in [0, 1, -1, 300, i32::MIN, i32::MAX] {
+            put_zigzag(&mut buf, v);
+        }
+        let mut rd = Reader { buf: &buf, pos: 0 };
+        for v in [0, 1, -1, 300, i32::MIN, i32::MAX] {
+            assert_eq!(rd.zigzag().unwrap(), v);
+        }
+        assert!(rd.varint().is_err());
+    }
+
+    #[test]
+    fn test_round_trip_plain_and_compressed() {
+        let sheet = sample();
+        for compress in [false, true] {
+            let copy = load(&save(&sheet, compress)).unwrap();
+            assert_eq!((copy.rows, copy.cols), (10, 10));
+            assert_eq!(copy.arr, sheet.arr);
+            assert_eq!(copy.formula_array, sheet.formula_array);
+            assert_eq!(copy.formula_strings, sheet.formula_strings);
+            assert_eq!(copy.graph.exprs, sheet.graph.exprs);
+        }
+        // an expression cell is wired up again after loading
+        let mut copy = load(&save(&sheet, false)).unwrap();
+        assert_eq!(copy.arr[7], 10);
+        assign(&mut copy, "A2=9").unwrap();
+        assert_eq!(copy.arr[7], 14);
+        assert_eq!(copy.arr[8], 9);
+    }
+
+    #[test]
+    fn test_values_keep_their_kind() {
+        let mut buf = Vec::new();
+        let values = [
+            Value::from(-7),
+            Value::from(2.5),
+            Value::from(1e12),
+            ERR,
+            Value::Int(i64::MIN),
+            NUM,
+            Value::Decimal(Decimal {
+                units: -150,
+                scale: 2,
+            }),
+            Value::Bool(true),
+            NA,
+            Value::Date(45413.5),
+        ];
+        for v in &values {
+            put_value(&mut buf, v);
+        }
+        assert_eq!(buf.len(), 2 + 9 + 9 + 1 + 11 + 1 + 4 + 2 + 1 + 9);
+        let mut rd = Reader { buf: &buf, pos: 0 };
+        for v in values {
+            assert_eq!(rd.value(VERSION).unwrap(), v);
+        }
+        let mut rd = Reader {
+            buf: &buf[buf.len() - 9..],
+            pos: 0,
+        };
+        assert_eq!(rd.value(VERSION).unwrap().to_string(), "2024-05-01 12:00");
+        assert!(matches!(
+            Reader {
+                buf: &buf[21..],
+                pos: 0
+            }
+            .value(VERSION),
+            Ok(Value::Int(i64::MIN))
+        ));
+        assert_eq!(load(&save(&sample(), true)).unwrap().arr[5], 1.25);
+    }
+
+    #[test]
+    fn test_loads_version_1() {
+        // one cell, B1 = 7, and the error cell C1, with no formulas
+        let mut payload = vec![0, 2];
+        payload.extend([1, 14, 0, 0, 0, 0]);
+        payload.push(0);
+        put_zigzag(&mut payload, i32::MIN);
+        payload.extend([0, 0, 0, 0]);
+        let mut crc = Crc::new();
+        crc.update(&payload);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([1, 0]);
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(3u32.to_le_bytes());
+        bytes.extend(crc.sum().to_le_bytes());
+        bytes.extend(payload);
+        let sheet = load(&bytes).unwrap();
+        assert_eq!(sheet.arr, vec![Value::from(0), Value::from(7), ERR]);
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut sheet = initialize_spreadsheet(2, 2);
+        execute(&mut sheet, "numbers decimal 2 half-up").unwrap();
+        execute(&mut sheet, "iterate on 50 0.01").unwrap();
+        assign(&mut sheet, "A1=B1/2+1").unwrap();
+        assign(&mut sheet, "B1=A1").unwrap();
+        let mut copy = load(&save(&sheet, true)).unwrap();
+        assert_eq!(copy.graph.numbers, sheet.graph.numbers);
+        assert_eq!(copy.graph.iteration, sheet.graph.iteration);
+        assert_eq!(copy.arr, sheet.arr);
+        // the loop was saved as allowed and is still solved after loading
+        assign(&mut copy, "A2=B1*2").unwrap();
+        assert_eq!(copy.arr[2].to_string(), "4.00");
+
+        let mut bytes = save(&sheet, false);
+        bytes[HEADER_LEN] = 9;
+        assert!(load(&bytes).err().unwrap().contains("number mode"));
+    }
+
+    #[test]
+    fn test_loaded_graph_propagates() {
+        let mut copy = load(&save(&sample(), false)).unwrap();
+        assign(&mut copy, "A2=10").unwrap();
+        assert_eq!(copy.arr[2], 15); // C1 = A1 + A2
+        assert_eq!(copy.arr[3], 15); // D1 = SUM(A1:A3)
+        assert!(assign(&mut copy, "A1=B1").is_err()); // cycle still detected
+    }
+
+    #[test]
+    fn test_interned_texts() {
+        let mut sheet = initialize_spreadsheet(1, 50);
+        for c in 1..50 {
+            let name = column_index_to_name(c);
+            assign(&mut sheet, &format!("{}1=SUM(A1:A1)", name)).unwrap();
+        }
+        let bytes = save(&sheet, false);
+        assert_eq!(bytes.windows(10).filter(|w| w == b"SUM(A1:A1)").count(), 1);
+    }
+
+    #[test]
+    fn test_rejects_corruption() {
+        let mut bytes = save(&sample(), false);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert_eq!(load(&bytes).err().unwrap(), "Snapshot checksum mismatch");
+
+        let mut bytes = save(&sample(), false);
+        bytes[4] = 99;
+        assert!(load(&bytes).err().unwrap().contains("version"));
+
+        assert!(load(b"nope").is_err());
+        let bytes = save(&sample(), true);
+        assert!(load(&bytes[..bytes.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_oversized_sheets() {
+        let sized = |rows: u32, cols: u32, payload: &[u8]| {
+            let mut crc = Crc::new();
+            crc.update(payload);
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend([2, 0]);
+            bytes.extend(rows.to_le_bytes());
+            bytes.extend(cols.to_le_bytes());
+            bytes.extend(crc.sum().to_le_bytes());
+            bytes.extend(payload);
+            load(&bytes)
+        };
+        assert!(sized(u32::MAX, u32::MAX, &[0, 0]).is_err());
+        assert!(sized(1001, 1, &[0, 0]).is_err());
+        assert!(sized(1, 18279, &[0, 0]).is_err());
+        assert!(sized(0, 5, &[0, 0]).is_err());
+        // more cells than the sheet or the remaining bytes can hold
+        let mut payload = vec![0];
+        put_varint(&mut payload, 1 << 40);
+        assert_eq!(
+            sized(1000, 18278, &payload).err().unwrap(),
+            "Truncated snapshot"
+        );
+        assert_eq!(
+            sized(2, 2, &[0, 5, 0, 0, 0, 0, 0, 0]).err().unwrap(),
+            "Truncated snapshot"
+        );
+        assert!(sized(1000, 1, &[0, 0]).is_ok());
+        assert!(sized(1, 18278, &[0, 0]).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_inflating_past_the_sheet() {
+        // a few hundred bytes that inflate to more than a 1 × 1 sheet holds
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0; 1 << 20]).unwrap();
+        let body = encoder.finish().unwrap();
+        assert!(body.len() < 2048);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([2, FLAG_DEFLATE]);
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(body);
+        assert_eq!(
+            load(&bytes).err().unwrap(),
+            "Snapshot payload is larger than its sheet"
+        );
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("lab1-snapshot-{}.bin", std::process::id()));
+        save_file(&sample(), &path, true).unwrap();
+        let copy = load_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(copy.arr[3], 2);
+        assert!(load_file(&path).is_err());
+    }
+}
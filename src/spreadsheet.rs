@@ -1,16 +1,25 @@
-use crate::display::printer;
 use crate::graph::{Formula, Graph};
-use std::collections::VecDeque;
-/// The core spreadsheet model: a 2D grid of `i32` cells with
+use crate::value::Value;
+use crate::view::Viewport;
+use std::collections::{HashMap, VecDeque};
+
+/// Most rows a sheet may have.
+pub const MAX_ROWS: usize = 1000;
+/// Most columns a sheet may have (`A` to `ZZZ`).
+pub const MAX_COLS: usize = 18278;
+
+/// The core spreadsheet model: a 2D grid of [`Value`] cells with
 /// dependency tracking and undo/redo history.
 ///
 /// - `rows`, `cols`: dimensions of the sheet
 /// - `arr`: flat row-major storage of cell values
 /// - `graph`: dependency graph for formula recalculation
 /// - `formula_array`: parsed formulas for each cell
-/// - `formula_strings`: the original text of each cell’s formula
+/// - `formula_strings`: the original text of each cell’s formula, kept only
+///   for cells that have one (see [`Spreadsheet::formula`])
 /// - `undo_stack` / `redo_stack`: history for undo/redo operations
 /// - `curr_x`, `curry`: viewport origin for on-screen printing
+/// - `view`: viewport size, column widths and scroll step (see [`Viewport`])
 /// - `output_disabled`: if `true`, suppress output on updates
 ///
 /// # Examples
@@ -21,12 +30,13 @@ use std::collections::VecDeque;
 /// assert_eq!(sheet.rows, 3);
 /// assert_eq!(sheet.cols, 4);
 /// assert_eq!(sheet.arr.len(), 12);
-/// assert!(sheet.formula_strings.iter().all(|s| s.is_empty()));
+/// assert!(sheet.formula_strings.is_empty());
+/// assert_eq!(sheet.formula(0), "");
 /// ```
 pub struct Spreadsheet {
     pub rows: usize,
     pub cols: usize,
-    pub arr: Vec<i32>,
+    pub arr: Vec<Value>,
     pub graph: Graph,
     pub formula_array: Vec<Formula>,
     pub output_disabled: bool,
@@ -34,20 +44,18 @@ pub struct Spreadsheet {
     pub time: f64,
     pub curr_x: usize,
     pub curry: usize,
-    pub formula_strings: Vec<String>, // Store formulas as strings
-    pub undo_stack: VecDeque<(usize, String)>, // Store previous formulas for undo
+    pub formula_strings: HashMap<usize, String>, // Formula text of each cell that has one
+    pub undo_stack: VecDeque<(usize, String)>,   // Store previous formulas for undo
     pub redo_stack: VecDeque<(usize, String)>,
+    pub view: Viewport, // Viewport size, column widths and scroll step
 }
 
 impl Spreadsheet {
-    /// Print the current 10×10 viewport of this spreadsheet to stdout.
-    ///
-    /// This calls into the [`printer`] function with this sheet’s
-    /// `curr_x`, `curry`, `arr`, `cols` and `rows`.
-    ///
-    /// # Panics
+    /// Print the current viewport of this spreadsheet to stdout.
     ///
-    /// Will panic if the underlying `printer` panics (it normally does not).
+    /// The window starts at `curr_x`/`curry` and is laid out by
+    /// [`Viewport::render`] using this sheet's `view` settings (10×10 with
+    /// 10-character columns by default).
     ///
     /// # Examples
     ///
@@ -57,7 +65,25 @@ impl Spreadsheet {
     /// sheet.print(); // prints a 2×2 sheet
     /// ```
     pub fn print(&self) {
-        printer(self.curr_x, self.curry, &self.arr, self.cols, self.rows);
+        print!(
+            "{}",
+            self.view
+                .render(self.curr_x, self.curry, &self.arr, self.cols, self.rows)
+        );
+    }
+
+    /// The formula text of `cell` (e.g. `"B1=A1*2"`), or `""` if it has none.
+    pub fn formula(&self, cell: usize) -> &str {
+        self.formula_strings.get(&cell).map_or("", String::as_str)
+    }
+
+    /// Set the formula text of `cell`; an empty `text` removes it.
+    pub fn set_formula(&mut self, cell: usize, text: String) {
+        if text.is_empty() {
+            self.formula_strings.remove(&cell);
+        } else {
+            self.formula_strings.insert(cell, text);
+        }
     }
 }
 /// Create a new `Spreadsheet` of the given dimensions, initialized to all zeros.
@@ -70,7 +96,7 @@ impl Spreadsheet {
 /// A `Spreadsheet` with:
 /// - `arr` filled with `rows * cols` zeros
 /// - `formula_array` filled with empty formulas (type `0`)
-/// - `formula_strings` all empty
+/// - no `formula_strings`
 /// - empty undo/redo stacks
 ///
 /// # Examples
@@ -80,7 +106,7 @@ impl Spreadsheet {
 /// let sheet = initialize_spreadsheet(4, 5);
 /// assert_eq!(sheet.arr, vec![0; 20]);
 /// assert_eq!(sheet.formula_array.len(), 20);
-/// assert_eq!(sheet.formula_strings.len(), 20);
+/// assert!(sheet.formula_strings.is_empty());
 /// assert!(sheet.undo_stack.is_empty());
 /// assert!(sheet.redo_stack.is_empty());
 /// ```
@@ -89,7 +115,7 @@ pub fn initialize_spreadsheet(rows: usize, cols: usize) -> Spreadsheet {
     Spreadsheet {
         rows,
         cols,
-        arr: vec![0; total_cells],
+        arr: vec![Value::default(); total_cells],
         graph: Graph::new(),
         formula_array: vec![
             Formula {
@@ -104,9 +130,10 @@ pub fn initialize_spreadsheet(rows: usize, cols: usize) -> Spreadsheet {
         time: 0.0,
         curr_x: 0,
         curry: 0,
-        formula_strings: vec!["".to_string(); rows * cols],
+        formula_strings: HashMap::new(),
         undo_stack: VecDeque::new(),
         redo_stack: VecDeque::new(),
+        view: Viewport::default(),
     }
 }
 /// Convenience wrapper around [`Spreadsheet::print`].
@@ -123,6 +150,7 @@ pub fn print_spreadsheet(spreadsheet: &Spreadsheet) {
 }
 
 #[cfg(test)]
+#[allow(unused_imports)]
 mod tests {
     use super::*;
     use crate::display::printer;
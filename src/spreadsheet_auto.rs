@@ -1,11 +1,20 @@
-use crate::display::printer;
 use crate::graph::{Formula, Graph};
+use crate::value::Value;
+use crate::view::Viewport;
+use std::collections::{HashMap, VecDeque};
+
+/// Most rows a sheet may have.
+#[allow(dead_code)]
+pub const MAX_ROWS: usize = 1000;
+/// Most columns a sheet may have (`A` to `ZZZ`).
+#[allow(dead_code)]
+pub const MAX_COLS: usize = 18278;
 
 #[allow(dead_code)]
 pub struct Spreadsheet {
     pub rows: usize,
     pub cols: usize,
-    pub arr: Vec<i32>,
+    pub arr: Vec<Value>,
     pub graph: Graph,
     pub formula_array: Vec<Formula>,
     pub output_disabled: bool,
@@ -13,11 +22,35 @@ pub struct Spreadsheet {
     pub time: f64,
     pub curr_x: usize,
     pub curry: usize,
+    pub formula_strings: HashMap<usize, String>, // Formula text of each cell that has one
+    pub undo_stack: VecDeque<(usize, String)>,   // Store previous formulas for undo
+    pub redo_stack: VecDeque<(usize, String)>,
+    pub view: Viewport, // Viewport size, column widths and scroll step
 }
 
 impl Spreadsheet {
     pub fn print(&self) {
-        printer(self.curr_x, self.curry, &self.arr, self.cols, self.rows);
+        print!(
+            "{}",
+            self.view
+                .render(self.curr_x, self.curry, &self.arr, self.cols, self.rows)
+        );
+    }
+
+    #[allow(dead_code)]
+    /// The formula text of `cell` (e.g. `"B1=A1*2"`), or `""` if it has none.
+    pub fn formula(&self, cell: usize) -> &str {
+        self.formula_strings.get(&cell).map_or("", String::as_str)
+    }
+
+    #[allow(dead_code)]
+    /// Set the formula text of `cell`; an empty `text` removes it.
+    pub fn set_formula(&mut self, cell: usize, text: String) {
+        if text.is_empty() {
+            self.formula_strings.remove(&cell);
+        } else {
+            self.formula_strings.insert(cell, text);
+        }
     }
 }
 
@@ -45,7 +78,7 @@ pub fn initialize_spreadsheet(rows: usize, cols: usize) -> Spreadsheet {
     Spreadsheet {
         rows,
         cols,
-        arr: vec![0; total_cells],
+        arr: vec![Value::default(); total_cells],
         graph: Graph::new(),
         formula_array: vec![
             Formula {
@@ -60,6 +93,10 @@ pub fn initialize_spreadsheet(rows: usize, cols: usize) -> Spreadsheet {
         time: 0.0,
         curr_x: 0,
         curry: 0,
+        formula_strings: HashMap::new(),
+        undo_stack: VecDeque::new(),
+        redo_stack: VecDeque::new(),
+        view: Viewport::default(),
     }
 }
 
@@ -81,7 +118,7 @@ mod tests {
         assert_eq!(spreadsheet.rows, rows);
         assert_eq!(spreadsheet.cols, cols);
         assert_eq!(spreadsheet.arr.len(), rows * cols);
-        assert!(spreadsheet.arr.iter().all(|&x| x == 0)); // All cells initialized to 0
+        assert!(spreadsheet.arr.iter().all(|x| *x == 0)); // All cells initialized to 0
         assert_eq!(spreadsheet.curr_x, 0);
         assert_eq!(spreadsheet.curry, 0);
         assert!(spreadsheet.formula_array.iter().all(|f| f.op_type == 0)); // All formulas initialized
@@ -0,0 +1,403 @@
+//! Module `text`.
+//!
+//! The text functions of expressions: `CONCAT`, `LEN`, `LEFT`, `RIGHT`,
+//! `MID`, `UPPER`, `LOWER`, `TRIM`, `SUBSTITUTE`, `FIND`, `TEXT` and
+//! `VALUE`. Positions and lengths count characters, not bytes, and start at
+//! 1.
+//!
+//! A number used as text is written with its plain digits (`2.5`, `7`), a
+//! date as `2024-05-01`, a boolean as `TRUE` or `FALSE`; an empty cell is
+//! the empty text. Text used
+//! as a count, as in `LEFT(A1, "3")`, is read like `VALUE` reads it. A count
+//! or position out of range, a search that finds nothing, or a `VALUE` of
+//! text that is not a number gives `ERR`.
+
+use crate::date;
+use crate::math::{self, Math};
+use crate::value::{NumberMode, Value, ERR};
+
+/// A text function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    /// `CONCAT(value1, ...)`, which also takes ranges.
+    Concat,
+    Len,
+    /// `LEFT(text, [count])`, the first `count` (1) characters.
+    Left,
+    /// `RIGHT(text, [count])`.
+    Right,
+    /// `MID(text, start, count)`.
+    Mid,
+    Upper,
+    Lower,
+    /// Spaces removed from both ends, and runs of spaces inside made one.
+    Trim,
+    /// `SUBSTITUTE(text, old, new, [instance])`: every `old` replaced, or
+    /// only the `instance`th.
+    Substitute,
+    /// `FIND(needle, text, [start])`: where `needle` first occurs in `text`
+    /// from position `start` on, matching case.
+    Find,
+    /// `TEXT(value, format)`: a number written with a format such as
+    /// `"0.00"`, `"#,##0"` or `"0%"`, or a date with a pattern such as
+    /// `"dd mmm yyyy"` (see [`date::format_with`]).
+    Format,
+    /// `VALUE(text)`: the number or date text spells.
+    Value,
+}
+
+/// `v` as text, the way `&` joins it: the text itself, `TRUE` or `FALSE`,
+/// or the plain digits of a number.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::text::as_text;
+/// use lab1::value::Value;
+///
+/// assert_eq!(as_text(&Value::from(2.5)), "2.5");
+/// assert_eq!(as_text(&Value::Bool(true)), "TRUE");
+/// assert_eq!(as_text(&Value::text("a")), "a");
+/// ```
+pub fn as_text(v: &Value) -> String {
+    match *v {
+        Value::Decimal(d) if d.scale > 0 => {
+            let digits = d.to_string();
+            digits
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string()
+        }
+        _ => v.to_string(),
+    }
+}
+
+/// The number `text` spells, such as `"42"`, `" -1.5 "`, `"1,250"` or
+/// `"15%"`; the empty text is 0.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::text::parse_number;
+///
+/// assert_eq!(parse_number("1,250.5"), Some(1250.5));
+/// assert_eq!(parse_number("15%"), Some(0.15));
+/// assert_eq!(parse_number("inf"), None);
+/// assert_eq!(parse_number("12 apples"), None);
+/// ```
+pub fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(0.0);
+    }
+    let (digits, scale) = match text.strip_suffix('%') {
+        Some(rest) => (rest.trim_end(), 100.0),
+        None => (text, 1.0),
+    };
+    let digits = digits.replace(',', "");
+    let plain = digits
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | '+' | '-' | 'e' | 'E'));
+    if !plain || !digits.contains(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    digits
+        .parse::<f64>()
+        .ok()
+        .map(|x| x / scale)
+        .filter(|x| x.is_finite())
+}
+
+/// `f` applied to `args`, whose count the parser has checked, on a sheet
+/// of mode `numbers`. The first error among the arguments is passed on.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::text::{apply, Text};
+/// use lab1::value::{NumberMode, Value, ERR};
+///
+/// let float = NumberMode::Float;
+/// let word = Value::text("spreadsheet");
+/// assert_eq!(apply(Text::Left, &[word.clone(), Value::from(6)], float).to_string(), "spread");
+/// assert_eq!(apply(Text::Find, &[Value::text("sheet"), word.clone()], float), 7);
+/// assert_eq!(apply(Text::Find, &[Value::text("x"), word], float), ERR);
+/// ```
+pub fn apply(f: Text, args: &[Value], numbers: NumberMode) -> Value {
+    if let Some(e) = args.iter().find(|v| v.is_error()) {
+        return e.clone();
+    }
+    compute(f, args, numbers).unwrap_or(ERR)
+}
+
+fn compute(f: Text, args: &[Value], numbers: NumberMode) -> Option<Value> {
+    let text = |i: usize| args.get(i).map_or(String::new(), as_text);
+    // a missing count takes its default; a negative one is an error
+    let count = |i: usize, default: i64| match args.get(i) {
+        None => Some(default),
+        Some(v) => whole(v).filter(|&n| n >= 0),
+    };
+    let chars: Vec<char> = text(0).chars().collect();
+    let slice = |from: usize, n: i64| -> String {
+        let from = from.min(chars.len());
+        let to = from.saturating_add(n as usize).min(chars.len());
+        chars[from..to].iter().collect()
+    };
+    let result = match f {
+        Text::Concat => args.iter().map(as_text).collect(),
+        Text::Len => return Some(numbers.whole(chars.len() as i64)),
+        Text::Left => slice(0, count(1, 1)?),
+        Text::Right => {
+            let n = count(1, 1)?;
+            slice(chars.len().saturating_sub(n as usize), n)
+        }
+        Text::Mid => {
+            let start = count(1, 0)?.checked_sub(1).filter(|&s| s >= 0)?;
+            slice(start as usize, count(2, 0)?)
+        }
+        Text::Upper => text(0).to_uppercase(),
+        Text::Lower => text(0).to_lowercase(),
+        Text::Trim => text(0)
+            .split(' ')
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+        Text::Substitute => {
+            let (whole_text, old, new) = (text(0), text(1), text(2));
+            match args.get(3) {
+                _ if old.is_empty() => whole_text,
+                None => whole_text.replace(&old, &new),
+                Some(v) => {
+                    let instance = whole(v).filter(|&n| n >= 1)?;
+                    match whole_text.match_indices(&old).nth(instance as usize - 1) {
+                        Some((at, _)) => {
+                            let end = at + old.len();
+                            format!("{}{}{}", &whole_text[..at], new, &whole_text[end..])
+                        }
+                        None => whole_text,
+                    }
+                }
+            }
+        }
+        Text::Find => {
+            let (needle, haystack) = (text(0), text(1));
+            let start = count(2, 1)?.checked_sub(1).filter(|&s| s >= 0)? as usize;
+            let len = haystack.chars().count();
+            if start > len {
+                return None;
+            }
+            let from = haystack
+                .char_indices()
+                .nth(start)
+                .map_or(haystack.len(), |(at, _)| at);
+            let at = from + haystack[from..].find(&needle)?;
+            return Some(numbers.whole(haystack[..at].chars().count() as i64 + 1));
+        }
+        // a format without digits is a date pattern
+        Text::Format => match whole_number(&args[0]) {
+            Some(x) if text(1).contains(['0', '#']) => format_number(x, &text(1))?,
+            Some(x) => date::format_with(x, &text(1))?,
+            None => text(0),
+        },
+        Text::Value => {
+            return match &args[0] {
+                Value::Text(t) => parse_number(t)
+                    .map(|x| numbers.float(x))
+                    .or_else(|| date::parse(t).map(Value::Date)),
+                Value::Bool(_) => None,
+                v => Some(v.clone()),
+            }
+        }
+    };
+    Some(Value::text(&result))
+}
+
+/// `v` as a number, reading text like [`parse_number`]; booleans are not
+/// numbers here.
+fn whole_number(v: &Value) -> Option<f64> {
+    match v {
+        Value::Text(t) => parse_number(t),
+        Value::Bool(_) => None,
+        v => v.number(),
+    }
+}
+
+/// `v` as a count, with any fraction cut off.
+fn whole(v: &Value) -> Option<i64> {
+    match v {
+        Value::Text(t) => parse_number(t),
+        v => v.number(),
+    }
+    .map(|x| x.trunc() as i64)
+}
+
+/// `x` written with `format`: the digits of its `0`s and `#`s, grouped by
+/// thousands if it holds a `,` and as a percentage if it ends in `%`, with
+/// any other text before or after kept as it is.
+fn format_number(x: f64, format: &str) -> Option<String> {
+    let is_pattern = |c: char| matches!(c, '0' | '#' | '.' | ',');
+    let first = format.find(['0', '#'])?;
+    let start = format[..first]
+        .rfind(|c| !is_pattern(c))
+        .map_or(0, |i| i + 1);
+    let end = format[first..]
+        .find(|c| !is_pattern(c))
+        .map_or(format.len(), |i| first + i);
+    let (prefix, pattern, suffix) = (&format[..start], &format[start..end], &format[end..]);
+    let (int_pattern, frac_pattern) = pattern.split_once('.').unwrap_or((pattern, ""));
+    let decimals = frac_pattern.matches(['0', '#']).count();
+    let min_digits = int_pattern.matches('0').count();
+    let x = if suffix.contains('%') { x * 100.0 } else { x };
+    let rounded = math::apply(
+        Math::Round,
+        &[Value::Number(x), Value::from(decimals as i32)],
+        NumberMode::Float,
+    )
+    .number()?;
+    let digits = format!("{:.*}", decimals, rounded.abs());
+    let (int_digits, frac_digits) = digits.split_once('.').unwrap_or((&digits, ""));
+    let int_digits = match int_digits.trim_start_matches('0') {
+        short if short.len() < min_digits => format!("{:0>width$}", short, width = min_digits),
+        short => short.to_string(),
+    };
+    let int_digits = if int_pattern.contains(',') {
+        group(&int_digits)
+    } else {
+        int_digits
+    };
+    let sign = if rounded < 0.0 { "-" } else { "" };
+    let point = if decimals > 0 { "." } else { "" };
+    Some(format!(
+        "{}{}{}{}{}{}",
+        sign, prefix, int_digits, point, frac_digits, suffix
+    ))
+}
+
+/// `digits` with a comma between each group of three from the right.
+fn group(digits: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(f: Text, args: &[Value]) -> String {
+        apply(f, args, NumberMode::Float).to_string()
+    }
+
+    #[test]
+    fn test_slicing_counts_characters() {
+        let t = Value::text;
+        let word = t("héllo wörld");
+        assert_eq!(call(Text::Len, std::slice::from_ref(&word)), "11");
+        assert_eq!(call(Text::Left, std::slice::from_ref(&word)), "h");
+        assert_eq!(call(Text::Right, &[word.clone(), Value::from(5)]), "wörld");
+        assert_eq!(
+            call(Text::Mid, &[word.clone(), Value::from(2), Value::from(4)]),
+            "éllo"
+        );
+        assert_eq!(
+            call(Text::Mid, &[word.clone(), Value::from(10), Value::from(9)]),
+            "ld"
+        );
+        assert_eq!(
+            call(Text::Left, &[word.clone(), Value::from(99)]),
+            "héllo wörld"
+        );
+        assert_eq!(call(Text::Left, &[word.clone(), t("2")]), "hé");
+        assert_eq!(call(Text::Left, &[word.clone(), Value::from(-1)]), "ERR");
+        assert_eq!(
+            call(Text::Mid, &[word.clone(), Value::from(0), Value::from(1)]),
+            "ERR"
+        );
+        assert_eq!(call(Text::Find, &[t("ö"), word.clone()]), "8");
+        assert_eq!(
+            call(Text::Find, &[t("l"), word.clone(), Value::from(5)]),
+            "10"
+        );
+        assert_eq!(call(Text::Find, &[t("L"), word.clone()]), "ERR");
+        assert_eq!(
+            call(Text::Find, &[t(""), word.clone(), Value::from(12)]),
+            "12"
+        );
+        assert_eq!(
+            call(Text::Find, &[t(""), word.clone(), Value::from(13)]),
+            "ERR"
+        );
+    }
+
+    #[test]
+    fn test_case_spaces_and_substitution() {
+        let t = Value::text;
+        assert_eq!(call(Text::Trim, &[t("  a   b  ")]), "a b");
+        assert_eq!(call(Text::Upper, &[t("straße")]), "STRASSE");
+        assert_eq!(call(Text::Lower, &[t("ABC")]), "abc");
+        let s = t("a-b-c");
+        assert_eq!(
+            call(Text::Substitute, &[s.clone(), t("-"), t("+")]),
+            "a+b+c"
+        );
+        assert_eq!(
+            call(
+                Text::Substitute,
+                &[s.clone(), t("-"), t(""), Value::from(2)]
+            ),
+            "a-bc"
+        );
+        assert_eq!(
+            call(Text::Substitute, &[s, t("-"), t(""), Value::from(3)]),
+            "a-b-c"
+        );
+        assert_eq!(
+            call(
+                Text::Concat,
+                &[t("x"), Value::from(1.5), Value::Bool(false)]
+            ),
+            "x1.5FALSE"
+        );
+        assert_eq!(call(Text::Concat, &[t("x"), crate::value::NA]), "#N/A");
+    }
+
+    #[test]
+    fn test_text_and_value() {
+        let t = Value::text;
+        let n = Value::from;
+        assert_eq!(call(Text::Format, &[n(1234.5), t("#,##0.00")]), "1,234.50");
+        assert_eq!(call(Text::Format, &[n(-2.675), t("0.00")]), "-2.68");
+        assert_eq!(call(Text::Format, &[n(0.256), t("0.0%")]), "25.6%");
+        assert_eq!(call(Text::Format, &[n(7.0), t("000")]), "007");
+        assert_eq!(call(Text::Format, &[n(0.5), t("#.#")]), ".5");
+        assert_eq!(call(Text::Format, &[n(42.0), t("$#,##0 each")]), "$42 each");
+        assert_eq!(call(Text::Format, &[n(1e6), t("#,###")]), "1,000,000");
+        assert_eq!(call(Text::Format, &[t("abc"), t("0.0")]), "abc");
+        assert_eq!(call(Text::Format, &[n(1.0), t("abc")]), "ERR");
+        assert_eq!(
+            apply(Text::Value, &[t(" 1,250.5 ")], NumberMode::Float),
+            1250.5
+        );
+        assert!(matches!(
+            apply(Text::Value, &[t("12")], NumberMode::Int),
+            Value::Int(12)
+        ));
+        assert_eq!(call(Text::Value, &[t("12 apples")]), "ERR");
+        let may_day = Value::Date(45413.0);
+        assert_eq!(
+            call(Text::Format, &[may_day.clone(), t("d mmm yyyy")]),
+            "1 May 2024"
+        );
+        assert_eq!(call(Text::Format, &[may_day, t("0")]), "45413");
+        assert!(matches!(
+            apply(Text::Value, &[t("2024-05-01")], NumberMode::Float),
+            Value::Date(45413.0)
+        ));
+        assert_eq!(call(Text::Value, &[Value::Bool(true)]), "ERR");
+    }
+}
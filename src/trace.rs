@@ -0,0 +1,319 @@
+//! Module `trace`.
+//!
+//! Which cells a cell reads (its *precedents*) and which cells read it (its
+//! *dependents*). Dependents come straight from the forward edges in
+//! [`Graph::adj`](crate::graph::Graph); precedents are recovered from the
+//! compact [`Formula`] stored for the cell.
+//!
+//! [`trace_precedents`] and [`trace_dependents`] extend both to a set of
+//! cells (such as a range) and, with [`Depth::Transitive`], to everything
+//! reachable through further references.
+//!
+//! [`find_cycle`] names the loop a rejected formula would have closed.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::expr::{cell_index, EXPR};
+use crate::graph::Formula;
+use crate::spreadsheet::Spreadsheet;
+
+/// Cells read by formula `f` installed in `cell` of a sheet `cols` wide,
+/// in ascending order.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::graph::Formula;
+/// use lab1::trace::formula_precedents;
+///
+/// // C1 = SUM(A1:B2) on a 3-wide sheet
+/// let f = Formula { op_type: 12, p1: 0, p2: 4 };
+/// assert_eq!(formula_precedents(&f, 2, 3), vec![0, 1, 3, 4]);
+/// ```
+pub fn formula_precedents(f: &Formula, cell: usize, cols: usize) -> Vec<usize> {
+    let mut cells = match f.op_type {
+        1..=4 => vec![f.p1 as usize],
+        5..=8 => vec![f.p1 as usize, f.p2 as usize],
+        9..=13 => {
+            let (start, end) = (f.p1 as usize, f.p2 as usize);
+            (start / cols..=end / cols)
+                .flat_map(|r| (start % cols..=end % cols).map(move |c| r * cols + c))
+                .filter(|&src| src != cell)
+                .collect()
+        }
+        14 if f.p1 as usize != cell => vec![f.p1 as usize],
+        _ => Vec::new(),
+    };
+    cells.sort_unstable();
+    cells.dedup();
+    cells
+}
+
+/// Cells that `cell`'s formula reads directly, in ascending order; for
+/// `INDIRECT` and `OFFSET`, the cells they read when last evaluated.
+pub fn precedents(sheet: &Spreadsheet, cell: usize) -> Vec<usize> {
+    match sheet.formula_array[cell].op_type {
+        EXPR => {
+            let graph = &sheet.graph;
+            let mut cells = graph
+                .exprs
+                .get(&cell)
+                .map_or_else(Vec::new, |e| e.refs(sheet.cols));
+            cells.extend(graph.dynamic.get(&cell).into_iter().flatten());
+            cells.sort_unstable();
+            cells.dedup();
+            cells
+        }
+        _ => formula_precedents(&sheet.formula_array[cell], cell, sheet.cols),
+    }
+}
+
+/// Cells whose formulas read `cell` directly, in ascending order.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+/// use lab1::trace::{dependents, precedents};
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// assign(&mut sheet, "B1=A1+1").unwrap();
+/// assign(&mut sheet, "C1=SUM(A1:B1)").unwrap();
+/// assert_eq!(dependents(&sheet, 0), vec![1, 2]);
+/// assert_eq!(precedents(&sheet, 2), vec![0, 1]);
+/// ```
+pub fn dependents(sheet: &Spreadsheet, cell: usize) -> Vec<usize> {
+    let mut cells = sheet.graph.adj.get(&cell).cloned().unwrap_or_default();
+    cells.sort_unstable();
+    cells.dedup();
+    cells
+}
+
+/// How far a trace follows references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// Only cells referenced directly.
+    Direct,
+    /// Cells reachable through any chain of references.
+    Transitive,
+}
+
+/// Breadth-first walk from `cells` along `next`, returning the cells reached
+/// that are not in `cells`, in ascending order.
+fn walk(cells: &[usize], depth: Depth, next: impl Fn(usize) -> Vec<usize>) -> Vec<usize> {
+    let seeds: BTreeSet<usize> = cells.iter().copied().collect();
+    let mut seen = seeds.clone();
+    let mut found = BTreeSet::new();
+    let mut queue: VecDeque<usize> = seeds.iter().copied().collect();
+    while let Some(cell) = queue.pop_front() {
+        for n in next(cell) {
+            if seen.insert(n) {
+                found.insert(n);
+                if depth == Depth::Transitive {
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+    found.into_iter().collect()
+}
+
+/// Cells that the formulas in `cells` read, excluding `cells` themselves.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+/// use lab1::trace::{trace_precedents, Depth};
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// assign(&mut sheet, "B1=A1+1").unwrap();
+/// assign(&mut sheet, "C1=B1*2").unwrap();
+/// assert_eq!(trace_precedents(&sheet, &[2], Depth::Direct), vec![1]);
+/// assert_eq!(trace_precedents(&sheet, &[2], Depth::Transitive), vec![0, 1]);
+/// ```
+pub fn trace_precedents(sheet: &Spreadsheet, cells: &[usize], depth: Depth) -> Vec<usize> {
+    walk(cells, depth, |c| precedents(sheet, c))
+}
+
+/// Cells whose formulas read `cells`, excluding `cells` themselves: what
+/// changes (or breaks) when their values do.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+/// use lab1::trace::{trace_dependents, Depth};
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// assign(&mut sheet, "B1=A1+1").unwrap();
+/// assign(&mut sheet, "C1=B1*2").unwrap();
+/// assert_eq!(trace_dependents(&sheet, &[0], Depth::Transitive), vec![1, 2]);
+/// ```
+pub fn trace_dependents(sheet: &Spreadsheet, cells: &[usize], depth: Depth) -> Vec<usize> {
+    walk(cells, depth, |c| dependents(sheet, c))
+}
+
+/// Cells named in the formula text `expr` (the part after `=`), with ranges
+/// such as `A1:B3` expanded. Names outside the sheet are ignored, and so are
+/// numbers and function names that happen to look like cells (`LOG10(`).
+pub fn formula_refs(sheet: &Spreadsheet, expr: &str) -> Vec<usize> {
+    let index = |name: &str| {
+        if name.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        cell_index(name, sheet.cols, sheet.rows)
+    };
+    let mut refs = Vec::new();
+    let mut rest = expr;
+    while !rest.is_empty() {
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == ':'))
+            .unwrap_or(rest.len());
+        let (token, tail) = rest.split_at(len);
+        if !tail.starts_with('(') {
+            match token.split_once(':') {
+                Some((from, to)) => {
+                    if let (Some(a), Some(b)) = (index(from), index(to)) {
+                        let f = Formula {
+                            op_type: 12,
+                            p1: a as i32,
+                            p2: b as i32,
+                        };
+                        refs.extend(formula_precedents(&f, usize::MAX, sheet.cols));
+                    }
+                }
+                None => refs.extend(index(token)),
+            }
+        }
+        let mut chars = tail.chars();
+        chars.next();
+        rest = chars.as_str();
+    }
+    refs.sort_unstable();
+    refs.dedup();
+    refs
+}
+
+/// The loop that making `cell` read `refs` would close, as the chain of
+/// cells it runs through, starting and ending at `cell` (`A1 → B1 → A1`
+/// reads as "B1 reads A1, A1 would read B1"). `None` if there is none.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::edit::assign;
+/// use lab1::spreadsheet::initialize_spreadsheet;
+/// use lab1::trace::find_cycle;
+///
+/// let mut sheet = initialize_spreadsheet(3, 3);
+/// assign(&mut sheet, "B1=A1+1").unwrap();
+/// assign(&mut sheet, "C1=B1*2").unwrap();
+/// // A1=C1 would close A1 → B1 → C1 → A1
+/// assert_eq!(find_cycle(&sheet, 0, &[2]), Some(vec![0, 1, 2, 0]));
+/// assert_eq!(find_cycle(&sheet, 2, &[0]), None);
+/// ```
+pub fn find_cycle(sheet: &Spreadsheet, cell: usize, refs: &[usize]) -> Option<Vec<usize>> {
+    if refs.contains(&cell) {
+        return Some(vec![cell, cell]);
+    }
+    // breadth-first along dependents, so the reported loop is a shortest one
+    let mut parent: HashMap<usize, usize> = HashMap::new();
+    let mut queue = VecDeque::from([cell]);
+    while let Some(c) = queue.pop_front() {
+        for d in dependents(sheet, c) {
+            if d == cell || parent.contains_key(&d) {
+                continue;
+            }
+            parent.insert(d, c);
+            if refs.contains(&d) {
+                let mut path = vec![cell, d];
+                while path[path.len() - 1] != cell {
+                    path.push(parent[&path[path.len() - 1]]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            queue.push_back(d);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::assign;
+    use crate::spreadsheet::initialize_spreadsheet;
+
+    #[test]
+    fn test_formula_precedents_by_kind() {
+        let f = |op_type, p1, p2| Formula { op_type, p1, p2 };
+        assert_eq!(formula_precedents(&f(0, 5, 0), 1, 4), Vec::<usize>::new());
+        assert_eq!(formula_precedents(&f(3, 2, 7), 1, 4), vec![2]);
+        assert_eq!(formula_precedents(&f(8, 6, 2), 1, 4), vec![2, 6]);
+        assert_eq!(formula_precedents(&f(5, 3, 3), 1, 4), vec![3]);
+        // the range skips the cell itself
+        assert_eq!(formula_precedents(&f(9, 0, 2), 1, 4), vec![0, 2]);
+        assert_eq!(formula_precedents(&f(14, 1, 3), 1, 4), Vec::<usize>::new());
+        assert_eq!(formula_precedents(&f(14, 0, 0), 1, 4), vec![0]);
+    }
+
+    #[test]
+    fn test_dependents_follow_edits() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        assign(&mut sheet, "B1=A1*A1").unwrap();
+        assert_eq!(dependents(&sheet, 0), vec![1]);
+        assign(&mut sheet, "B1=7").unwrap();
+        assert!(dependents(&sheet, 0).is_empty());
+        assert!(precedents(&sheet, 1).is_empty());
+    }
+
+    #[test]
+    fn test_trace_over_range() {
+        let mut sheet = initialize_spreadsheet(4, 4);
+        for input in ["A2=A1+1", "B1=SUM(A1:A3)", "C1=B1", "D4=C1*C1", "D1=9"] {
+            assign(&mut sheet, input).unwrap();
+        }
+        // A2 reads A1 but lies inside the traced range itself
+        let range = [0, 4, 8];
+        assert_eq!(trace_dependents(&sheet, &range, Depth::Direct), vec![1]);
+        assert_eq!(
+            trace_dependents(&sheet, &range, Depth::Transitive),
+            vec![1, 2, 15]
+        );
+        assert_eq!(
+            trace_precedents(&sheet, &[15], Depth::Transitive),
+            vec![0, 1, 2, 4, 8]
+        );
+        assert!(trace_precedents(&sheet, &[3], Depth::Transitive).is_empty());
+    }
+
+    #[test]
+    fn test_formula_refs() {
+        let sheet = initialize_spreadsheet(12, 3);
+        assert_eq!(formula_refs(&sheet, "A1+B2"), vec![0, 4]);
+        assert_eq!(formula_refs(&sheet, "SUM(A1:B2)"), vec![0, 1, 3, 4]);
+        assert_eq!(formula_refs(&sheet, "SLEEP(C3)"), vec![8]);
+        // out-of-sheet names and cell-like function names are skipped
+        assert_eq!(formula_refs(&sheet, "LOG10(A1)+Z9"), vec![0]);
+        assert!(formula_refs(&sheet, "42").is_empty());
+        assert!(formula_refs(&sheet, "99999999999999999999+AAAAAAAAAAAAAAAAAAAA1").is_empty());
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        assign(&mut sheet, "B1=A1").unwrap();
+        assign(&mut sheet, "C1=A1+B1").unwrap();
+        assert_eq!(find_cycle(&sheet, 0, &[0]), Some(vec![0, 0]));
+        // the shorter of the two loops through A1
+        assert_eq!(find_cycle(&sheet, 0, &[1, 2]), Some(vec![0, 1, 0]));
+        assert_eq!(find_cycle(&sheet, 0, &[2]), Some(vec![0, 2, 0]));
+        assert_eq!(find_cycle(&sheet, 1, &[2]), Some(vec![1, 2, 1]));
+        assert_eq!(find_cycle(&sheet, 2, &[0, 1]), None);
+    }
+}
@@ -0,0 +1,540 @@
+//! Module `tui`.
+//!
+//! Full-screen terminal front end: a cell cursor moved with the arrow keys, an
+//! editable formula bar showing the selected cell's formula text, and a status
+//! line. Edits go through [`assign`], so the sheet behaves exactly like the
+//! REPL and the web version.
+//!
+//! [`App`] holds the UI state and turns [`Key`]s and terminal sizes into
+//! screen lines without touching the terminal, so it can be tested; the
+//! crossterm event loop in [`run`] is only built with the `tui` feature.
+//!
+//! Keys: arrows / PageUp / PageDown / Home move the cursor; Enter, F2 or any
+//! printable character starts editing; in the formula bar Enter (or Tab)
+//! commits and Esc cancels. Delete clears a cell, Ctrl-Z / Ctrl-Y undo and
+//! redo, Esc or Ctrl-Q quits.
+
+use crate::csv::value_text;
+use crate::display::column_index_to_name;
+use crate::edit::{assign, redo, undo};
+use crate::spreadsheet::Spreadsheet;
+use crate::value::Value;
+
+/// Width of one grid column in characters.
+pub const CELL_WIDTH: usize = 10;
+/// Width of the row-number gutter.
+const GUTTER: usize = 5;
+/// Screen lines that are not grid rows: formula bar, column header, status.
+const CHROME: usize = 3;
+
+/// A key press, independent of the terminal library.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Tab,
+    Esc,
+    Backspace,
+    Delete,
+    F2,
+    Char(char),
+    Ctrl(char),
+}
+
+/// Full-screen UI state.
+#[derive(Debug)]
+pub struct App {
+    /// Selected cell (zero-based).
+    pub row: usize,
+    pub col: usize,
+    /// Top-left cell of the visible grid.
+    pub top: usize,
+    pub left: usize,
+    /// Formula being edited, or `None` while navigating.
+    pub editing: Option<String>,
+    /// Byte offset of the text cursor in `editing`.
+    pub cursor: usize,
+    /// Message shown in the status line.
+    pub status: String,
+    /// Set once the user asked to quit.
+    pub quit: bool,
+    width: usize,
+    height: usize,
+}
+
+impl App {
+    /// A new UI for a terminal of `width` × `height` characters.
+    pub fn new(width: usize, height: usize) -> App {
+        App {
+            row: 0,
+            col: 0,
+            top: 0,
+            left: 0,
+            editing: None,
+            cursor: 0,
+            status: "Ready".to_string(),
+            quit: false,
+            width,
+            height,
+        }
+    }
+
+    /// Adapt to a new terminal size, keeping the cursor on screen.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.follow_cursor();
+    }
+
+    /// Number of grid rows and columns that fit on screen.
+    pub fn visible(&self) -> (usize, usize) {
+        (
+            self.height.saturating_sub(CHROME).max(1),
+            (self.width.saturating_sub(GUTTER) / CELL_WIDTH).max(1),
+        )
+    }
+
+    fn selected(&self, sheet: &Spreadsheet) -> usize {
+        self.row * sheet.cols + self.col
+    }
+
+    fn name(&self) -> String {
+        format!("{}{}", column_index_to_name(self.col), self.row + 1)
+    }
+
+    /// Scroll just enough for the selected cell to be visible.
+    fn follow_cursor(&mut self) {
+        let (rows, cols) = self.visible();
+        self.top = self
+            .top
+            .min(self.row)
+            .max((self.row + 1).saturating_sub(rows));
+        self.left = self
+            .left
+            .min(self.col)
+            .max((self.col + 1).saturating_sub(cols));
+    }
+
+    fn move_by(&mut self, sheet: &Spreadsheet, dr: isize, dc: isize) {
+        self.row = self.row.saturating_add_signed(dr).min(sheet.rows - 1);
+        self.col = self.col.saturating_add_signed(dc).min(sheet.cols - 1);
+        self.follow_cursor();
+    }
+
+    fn start_edit(&mut self, sheet: &Spreadsheet, text: Option<String>) {
+        let text = text.unwrap_or_else(|| {
+            sheet
+                .formula(self.selected(sheet))
+                .split_once('=')
+                .map(|(_, rhs)| rhs.to_string())
+                .unwrap_or_default()
+        });
+        self.cursor = text.len();
+        self.editing = Some(text);
+        self.status = "Editing: Enter to commit, Esc to cancel".to_string();
+    }
+
+    /// Apply `input` and report the outcome in the status line.
+    fn commit(&mut self, sheet: &mut Spreadsheet, input: String) -> Option<String> {
+        match assign(sheet, &input) {
+            Ok(_) => {
+                self.status = format!(
+                    "{} = {}",
+                    self.name(),
+                    value_text(&sheet.arr[self.selected(sheet)])
+                );
+                Some(input)
+            }
+            Err(e) => {
+                self.status = format!("{}: {}", self.name(), e);
+                None
+            }
+        }
+    }
+
+    /// Handle one key press. Returns the accepted edit (as `A1=EXPR` text) when
+    /// the key changed a cell, so callers can journal it.
+    pub fn handle_key(&mut self, sheet: &mut Spreadsheet, key: Key) -> Option<String> {
+        let (rows, _) = self.visible();
+        let Some(text) = self.editing.as_mut() else {
+            match key {
+                Key::Up => self.move_by(sheet, -1, 0),
+                Key::Down => self.move_by(sheet, 1, 0),
+                Key::Left => self.move_by(sheet, 0, -1),
+                Key::Right | Key::Tab => self.move_by(sheet, 0, 1),
+                Key::PageUp => self.move_by(sheet, -(rows as isize), 0),
+                Key::PageDown => self.move_by(sheet, rows as isize, 0),
+                Key::Home => self.move_by(sheet, 0, -(self.col as isize)),
+                Key::Enter | Key::F2 => self.start_edit(sheet, None),
+                Key::Char(c) => self.start_edit(sheet, Some(c.to_string())),
+                Key::Delete => return self.commit(sheet, format!("{}=0", self.name())),
+                Key::Ctrl('z') | Key::Ctrl('y') => {
+                    let idx = if key == Key::Ctrl('z') {
+                        undo(sheet)
+                    } else {
+                        redo(sheet)
+                    };
+                    let Some(idx) = idx else {
+                        self.status = "Nothing to do".to_string();
+                        return None;
+                    };
+                    self.row = idx / sheet.cols;
+                    self.col = idx % sheet.cols;
+                    self.follow_cursor();
+                    self.status = format!("{} = {}", self.name(), value_text(&sheet.arr[idx]));
+                    return Some(sheet.formula(idx).to_string());
+                }
+                Key::Esc | Key::Ctrl('q') | Key::Ctrl('c') => self.quit = true,
+                _ => {}
+            }
+            return None;
+        };
+
+        let prev = |text: &str, at: usize| {
+            text[..at]
+                .chars()
+                .next_back()
+                .map_or(0, |c| at - c.len_utf8())
+        };
+        let next =
+            |text: &str, at: usize| text[at..].chars().next().map_or(at, |c| at + c.len_utf8());
+        match key {
+            Key::Char(c) => {
+                text.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor = prev(text, self.cursor);
+                text.remove(self.cursor);
+            }
+            Key::Delete if self.cursor < text.len() => {
+                text.remove(self.cursor);
+            }
+            Key::Left => self.cursor = prev(text, self.cursor),
+            Key::Right => self.cursor = next(text, self.cursor),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = text.len(),
+            Key::Esc => {
+                self.editing = None;
+                self.status = "Edit cancelled".to_string();
+            }
+            Key::Enter | Key::Tab => {
+                let input = format!(
+                    "{}{}={}",
+                    column_index_to_name(self.col),
+                    self.row + 1,
+                    text.trim()
+                );
+                let accepted = self.commit(sheet, input);
+                if accepted.is_some() {
+                    self.editing = None;
+                    if key == Key::Tab {
+                        self.move_by(sheet, 0, 1);
+                    } else {
+                        self.move_by(sheet, 1, 0);
+                    }
+                }
+                return accepted;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// The screen as `height` lines of exactly `width` characters.
+    pub fn render(&self, sheet: &Spreadsheet) -> Vec<String> {
+        let (rows, cols) = self.visible();
+        let fit = |line: String| -> String {
+            let mut line: String = line.chars().take(self.width).collect();
+            let len = line.chars().count();
+            line.extend(std::iter::repeat_n(' ', self.width - len));
+            line
+        };
+
+        let mut lines = Vec::with_capacity(self.height);
+        lines.push(fit(self.formula_bar(sheet)));
+
+        let last_col = (self.left + cols).min(sheet.cols);
+        let mut header = " ".repeat(GUTTER);
+        for c in self.left..last_col {
+            header.push_str(&format!("{:^w$}", column_index_to_name(c), w = CELL_WIDTH));
+        }
+        lines.push(fit(header));
+
+        for r in self.top..(self.top + rows).min(sheet.rows) {
+            let mut line = format!("{:>w$} ", r + 1, w = GUTTER - 1);
+            for c in self.left..last_col {
+                line.push_str(&cell_text(
+                    &sheet.arr[r * sheet.cols + c],
+                    sheet.view.precision,
+                ));
+            }
+            lines.push(fit(line));
+        }
+        while lines.len() < self.height.saturating_sub(1) {
+            lines.push(fit(String::new()));
+        }
+
+        let mode = if self.editing.is_some() {
+            "EDIT"
+        } else {
+            "NAV"
+        };
+        lines.push(fit(format!(
+            "[{}] {}  {}x{}  | {}",
+            mode,
+            self.name(),
+            sheet.rows,
+            sheet.cols,
+            self.status
+        )));
+        lines.truncate(self.height.max(1));
+        lines
+    }
+
+    fn formula_bar(&self, sheet: &Spreadsheet) -> String {
+        match &self.editing {
+            Some(text) => format!("{}={}", self.name(), text),
+            None => {
+                let formula = sheet.formula(self.selected(sheet));
+                if formula.is_empty() {
+                    format!("{}=", self.name())
+                } else {
+                    formula.to_string()
+                }
+            }
+        }
+    }
+
+    /// Screen position `(x, y)` of the selected cell in the grid, for
+    /// highlighting it.
+    pub fn highlight(&self) -> (usize, usize) {
+        (
+            GUTTER + (self.col - self.left) * CELL_WIDTH,
+            2 + self.row - self.top,
+        )
+    }
+
+    /// Screen position of the text cursor while editing.
+    pub fn text_cursor(&self) -> Option<(usize, usize)> {
+        let text = self.editing.as_ref()?;
+        let x = self.name().len() + 1 + text[..self.cursor].chars().count();
+        Some((x.min(self.width.saturating_sub(1)), 0))
+    }
+}
+
+/// One right-aligned grid cell with `precision` decimals (see
+/// [`Value::format`]); values too wide for the column show as `#`.
+pub fn cell_text(value: &Value, precision: Option<usize>) -> String {
+    let text = value.format(precision);
+    if text.len() >= CELL_WIDTH {
+        format!(" {}", "#".repeat(CELL_WIDTH - 1))
+    } else {
+        format!("{:>w$}", text, w = CELL_WIDTH)
+    }
+}
+
+/// Run the full-screen UI on the controlling terminal until the user quits.
+/// `on_edit` is called with every accepted edit (e.g. to journal it).
+#[cfg(feature = "tui")]
+pub fn run(
+    sheet: &mut Spreadsheet,
+    mut on_edit: impl FnMut(&Spreadsheet, &str),
+) -> std::io::Result<()> {
+    use crossterm::{cursor, execute, terminal};
+    use std::io::stdout;
+
+    let (width, height) = terminal::size()?;
+    let mut app = App::new(width as usize, height as usize);
+    terminal::enable_raw_mode()?;
+    execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = event_loop(sheet, &mut app, &mut on_edit);
+    execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+#[cfg(feature = "tui")]
+fn event_loop(
+    sheet: &mut Spreadsheet,
+    app: &mut App,
+    on_edit: &mut impl FnMut(&Spreadsheet, &str),
+) -> std::io::Result<()> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    while !app.quit {
+        draw(sheet, app)?;
+        let key = match event::read()? {
+            Event::Resize(w, h) => {
+                app.resize(w as usize, h as usize);
+                continue;
+            }
+            Event::Key(k) if k.kind != KeyEventKind::Release => match k.code {
+                KeyCode::Char(c) if k.modifiers.contains(KeyModifiers::CONTROL) => {
+                    Key::Ctrl(c.to_ascii_lowercase())
+                }
+                KeyCode::Char(c) => Key::Char(c),
+                KeyCode::Up => Key::Up,
+                KeyCode::Down => Key::Down,
+                KeyCode::Left => Key::Left,
+                KeyCode::Right => Key::Right,
+                KeyCode::PageUp => Key::PageUp,
+                KeyCode::PageDown => Key::PageDown,
+                KeyCode::Home => Key::Home,
+                KeyCode::End => Key::End,
+                KeyCode::Enter => Key::Enter,
+                KeyCode::Tab => Key::Tab,
+                KeyCode::Esc => Key::Esc,
+                KeyCode::Backspace => Key::Backspace,
+                KeyCode::Delete => Key::Delete,
+                KeyCode::F(2) => Key::F2,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if let Some(input) = app.handle_key(sheet, key) {
+            on_edit(sheet, &input);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn draw(sheet: &Spreadsheet, app: &App) -> std::io::Result<()> {
+    use crossterm::style::{Attribute, Print, SetAttribute};
+    use crossterm::{cursor, queue};
+    use std::io::{stdout, Write};
+
+    let mut out = stdout().lock();
+    for (y, line) in app.render(sheet).iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, y as u16), Print(line))?;
+    }
+    let (x, y) = app.highlight();
+    queue!(
+        out,
+        cursor::MoveTo(x as u16, y as u16),
+        SetAttribute(Attribute::Reverse),
+        Print(cell_text(
+            &sheet.arr[app.row * sheet.cols + app.col],
+            sheet.view.precision,
+        )),
+        SetAttribute(Attribute::Reset)
+    )?;
+    match app.text_cursor() {
+        Some((x, y)) => queue!(out, cursor::MoveTo(x as u16, y as u16), cursor::Show)?,
+        None => queue!(out, cursor::Hide)?,
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spreadsheet::initialize_spreadsheet;
+
+    fn type_text(app: &mut App, sheet: &mut Spreadsheet, text: &str) {
+        for c in text.chars() {
+            app.handle_key(sheet, Key::Char(c));
+        }
+    }
+
+    #[test]
+    fn test_navigation_scrolls_viewport() {
+        let mut sheet = initialize_spreadsheet(50, 50);
+        // 3 chrome lines + 5 rows, gutter + 2 columns
+        let mut app = App::new(GUTTER + 2 * CELL_WIDTH, 8);
+        assert_eq!(app.visible(), (5, 2));
+        for _ in 0..6 {
+            app.handle_key(&mut sheet, Key::Down);
+        }
+        app.handle_key(&mut sheet, Key::Right);
+        app.handle_key(&mut sheet, Key::Right);
+        assert_eq!((app.row, app.col, app.top, app.left), (6, 2, 2, 1));
+        app.handle_key(&mut sheet, Key::PageUp);
+        assert_eq!((app.row, app.top), (1, 1));
+        app.handle_key(&mut sheet, Key::Up);
+        app.handle_key(&mut sheet, Key::Up);
+        assert_eq!((app.row, app.top), (0, 0));
+        app.resize(GUTTER + CELL_WIDTH, 4);
+        assert_eq!(app.visible(), (1, 1));
+        assert_eq!((app.top, app.left), (0, 2));
+    }
+
+    #[test]
+    fn test_edit_commit_and_cancel() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        let mut app = App::new(80, 24);
+        type_text(&mut app, &mut sheet, "42");
+        assert_eq!(
+            app.handle_key(&mut sheet, Key::Enter),
+            Some("A1=42".to_string())
+        );
+        assert_eq!((app.row, &sheet.arr[0]), (1, &Value::from(42)));
+
+        app.handle_key(&mut sheet, Key::Up);
+        app.handle_key(&mut sheet, Key::Enter);
+        assert_eq!(app.editing.as_deref(), Some("42"));
+        app.handle_key(&mut sheet, Key::Backspace);
+        app.handle_key(&mut sheet, Key::Esc);
+        assert_eq!(app.editing, None);
+        assert_eq!(sheet.formula(0), "A1=42");
+        assert!(!app.quit);
+    }
+
+    #[test]
+    fn test_rejected_edit_stays_in_formula_bar() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        let mut app = App::new(80, 24);
+        type_text(&mut app, &mut sheet, "A1+1");
+        assert_eq!(app.handle_key(&mut sheet, Key::Enter), None);
+        assert_eq!(app.editing.as_deref(), Some("A1+1"));
+        assert!(app.status.starts_with("A1:"));
+    }
+
+    #[test]
+    fn test_cursor_editing_and_undo() {
+        let mut sheet = initialize_spreadsheet(5, 5);
+        let mut app = App::new(80, 24);
+        type_text(&mut app, &mut sheet, "1+2");
+        app.handle_key(&mut sheet, Key::Home);
+        app.handle_key(&mut sheet, Key::Delete);
+        type_text(&mut app, &mut sheet, "5");
+        assert_eq!(app.text_cursor(), Some((4, 0)));
+        app.handle_key(&mut sheet, Key::Tab);
+        assert_eq!((&sheet.arr[0], app.col), (&Value::from(7), 1));
+        assert_eq!(
+            app.handle_key(&mut sheet, Key::Ctrl('z')),
+            Some("A1=0".to_string())
+        );
+        assert_eq!((&sheet.arr[0], app.col), (&Value::default(), 0));
+        app.handle_key(&mut sheet, Key::Esc);
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn test_render_layout() {
+        let mut sheet = initialize_spreadsheet(3, 3);
+        assign(&mut sheet, "B1=A1+1234567890").ok();
+        assign(&mut sheet, "A2=A3/0").unwrap();
+        assign(&mut sheet, "B2=7").unwrap();
+        let mut app = App::new(30, 6);
+        app.handle_key(&mut sheet, Key::Down);
+        app.handle_key(&mut sheet, Key::Right);
+        let lines = app.render(&sheet);
+        assert_eq!(lines.len(), 6);
+        assert!(lines.iter().all(|l| l.chars().count() == 30));
+        assert_eq!(lines[0].trim_end(), "B2=7");
+        assert_eq!(lines[1].trim_end(), "         A         B");
+        assert_eq!(lines[2].trim_end(), "   1          0 #########");
+        assert_eq!(lines[3].trim_end(), "   2        ERR         7");
+        assert!(lines[5].starts_with("[NAV] B2  3x3  | "));
+        assert_eq!(app.highlight(), (15, 3));
+    }
+}
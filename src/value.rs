@@ -0,0 +1,474 @@
+//! Module `value`.
+//!
+//! What an evaluated cell holds. Numbers are `f64`, so division, averages and
+//! standard deviations keep their fractions; whole numbers are still shown
+//! without a decimal point. A sheet in [`NumberMode::Int`] keeps whole
+//! numbers as exact 64-bit integers instead, and one in
+//! [`NumberMode::Decimal`] keeps fixed-point decimals (see
+//! [`crate::decimal`]).
+//!
+//! A comparison gives a boolean, shown as `TRUE` or `FALSE`; in arithmetic
+//! it counts as 1 or 0.
+//!
+//! A cell may also hold text, such as a label or the result of `&`. Text
+//! is not a number: arithmetic on it is [`ERR`], and range functions skip
+//! it. Formulas compare texts ignoring case (see [`Value::collate`]).
+//!
+//! A date is a number of days (see [`crate::date`]) that is shown as a
+//! date, such as `2024-05-01`.
+//!
+//! A cell whose formula failed holds an error, which every formula reading
+//! it passes on: [`ERR`] for a failure such as dividing by zero, [`NUM`]
+//! (`#NUM!`) for a result too large to represent, [`NA`] (`#N/A`) for a lookup
+//! that found nothing. Numbers never wrap around.
+//!
+//! How many decimals are shown is a display setting (see
+//! [`Value::format`]); the stored number is never rounded.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::decimal::{Decimal, DecimalMode, Rounding};
+
+/// Decimals shown for a fractional number when no precision is set.
+pub const AUTO_DECIMALS: usize = 6;
+/// Largest display precision.
+pub const MAX_PRECISION: usize = 15;
+/// Most characters a text value may hold.
+pub const MAX_TEXT: usize = 32767;
+
+/// Why a formula has no value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellError {
+    /// The formula failed, e.g. by dividing by zero; shown as `ERR`.
+    Err,
+    /// The result is too large for the sheet's numbers; shown as `#NUM!`.
+    Num,
+    /// A lookup found no match; shown as `#N/A`.
+    Na,
+}
+
+impl CellError {
+    /// The text shown for this error.
+    pub fn name(self) -> &'static str {
+        match self {
+            CellError::Err => "ERR",
+            CellError::Num => "#NUM!",
+            CellError::Na => "#N/A",
+        }
+    }
+}
+
+/// The value of one cell.
+///
+/// Numbers compare by value whichever way they are held, so
+/// `Value::Int(2) == Value::Number(2.0)`.
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// A number.
+    Number(f64),
+    /// An exact whole number (see [`NumberMode::Int`]).
+    Int(i64),
+    /// An exact fixed-point number (see [`NumberMode::Decimal`]).
+    Decimal(Decimal),
+    /// The result of a comparison or logical function.
+    Bool(bool),
+    /// A date and time as a serial number of days (see [`crate::date`]).
+    Date(f64),
+    /// Text (see [`Value::text`]).
+    Text(Arc<String>),
+    /// The formula could not be evaluated.
+    Error(CellError),
+}
+
+/// The error value of a failed formula.
+pub const ERR: Value = Value::Error(CellError::Err);
+/// The error value of a result that overflowed.
+pub const NUM: Value = Value::Error(CellError::Num);
+/// The error value of a lookup without a match.
+pub const NA: Value = Value::Error(CellError::Na);
+
+/// How a sheet computes with numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberMode {
+    /// Every number is an `f64`; a result beyond its range is `#NUM!`.
+    #[default]
+    Float,
+    /// Whole numbers are exact `i64`s: `+`, `-`, `*`, SUM, MIN and MAX stay
+    /// exact and give `#NUM!` when they leave the `i64` range. A division
+    /// with a remainder, AVG and STDEV fall back to `f64`.
+    Int,
+    /// Every number is a decimal with a fixed number of decimals; results
+    /// are rounded to it with the mode's rule.
+    Decimal(DecimalMode),
+}
+
+impl NumberMode {
+    /// The literal `n` as a number of this mode.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::value::{NumberMode, Value};
+    ///
+    /// assert!(matches!(NumberMode::Int.whole(3), Value::Int(3)));
+    /// assert!(matches!(NumberMode::Float.whole(3), Value::Number(_)));
+    /// ```
+    pub fn whole(self, n: i64) -> Value {
+        match self {
+            NumberMode::Float => Value::Number(n as f64),
+            NumberMode::Int => Value::Int(n),
+            NumberMode::Decimal(mode) => mode.convert(Value::Int(n)),
+        }
+    }
+
+    /// The floating-point result `x` as a number of this mode: whole
+    /// numbers as [`NumberMode::whole`] gives them, fractions rounded to a
+    /// decimal sheet's scale. NaN is [`ERR`] and an infinity [`NUM`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::value::{NumberMode, Value, ERR, NUM};
+    ///
+    /// assert!(matches!(NumberMode::Int.float(4.0), Value::Int(4)));
+    /// assert_eq!(NumberMode::Int.float(0.5), 0.5);
+    /// assert_eq!(NumberMode::Float.float(f64::NAN), ERR);
+    /// assert_eq!(NumberMode::Float.float(f64::INFINITY), NUM);
+    /// ```
+    pub fn float(self, x: f64) -> Value {
+        match self {
+            _ if x.is_nan() => ERR,
+            _ if x.fract() == 0.0 && x.abs() < 9e15 => self.whole(x as i64),
+            NumberMode::Decimal(mode) => mode.convert(Value::Number(x)),
+            _ => Value::finite(x),
+        }
+    }
+}
+
+impl Default for Value {
+    fn default() -> Value {
+        Value::Number(0.0)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Value {
+        Value::Number(x)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(x: i32) -> Value {
+        Value::Number(x as f64)
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Error(a), Value::Error(b)) => a == b,
+            (Value::Decimal(a), Value::Decimal(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Text(a), Value::Text(b)) => a == b,
+            (a, b) => a.number().is_some() && a.number() == b.number(),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    /// Numbers (and booleans, as 1 and 0) by value and texts by their
+    /// characters; errors, and texts against anything else, are unordered.
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            (Value::Decimal(a), Value::Decimal(b)) => a.partial_cmp(b),
+            (a, b) => a.number()?.partial_cmp(&b.number()?),
+        }
+    }
+}
+
+impl PartialEq<f64> for Value {
+    fn eq(&self, other: &f64) -> bool {
+        self.number() == Some(*other)
+    }
+}
+
+impl PartialEq<i32> for Value {
+    fn eq(&self, other: &i32) -> bool {
+        *self == *other as f64
+    }
+}
+
+impl Value {
+    /// `x`, or [`NUM`] if it is infinite or not a number.
+    pub fn finite(x: f64) -> Value {
+        if x.is_finite() {
+            Value::Number(x)
+        } else {
+            NUM
+        }
+    }
+
+    /// The text `text` as a value, or [`ERR`] if it is longer than
+    /// [`MAX_TEXT`]. Copies of the value share one string, held behind a
+    /// thin pointer so the value stays 16 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::value::{Value, ERR, MAX_TEXT};
+    ///
+    /// assert_eq!(Value::text("Total").to_string(), "Total");
+    /// assert_eq!(Value::text("Total").number(), None);
+    /// assert_eq!(Value::text(&"x".repeat(MAX_TEXT + 1)), ERR);
+    /// ```
+    pub fn text(text: &str) -> Value {
+        if text.chars().count() > MAX_TEXT {
+            return ERR;
+        }
+        Value::Text(Arc::new(text.to_string()))
+    }
+
+    /// The number held, or `None` for an error or text.
+    pub fn number(&self) -> Option<f64> {
+        match *self {
+            Value::Number(x) => Some(x),
+            Value::Int(i) => Some(i as f64),
+            Value::Decimal(d) => Some(d.to_f64()),
+            Value::Bool(b) => Some(if b { 1.0 } else { 0.0 }),
+            Value::Date(x) => Some(x),
+            Value::Error(_) | Value::Text(_) => None,
+        }
+    }
+
+    /// Whether this is an error of any kind.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Value::Error(_))
+    }
+
+    /// Whether `self` and `other` are of one kind: both numbers, however
+    /// held (dates included), both booleans, both texts or both errors.
+    pub fn same_kind(&self, other: &Value) -> bool {
+        let kind = |v: &Value| match v {
+            Value::Number(_) | Value::Int(_) | Value::Decimal(_) | Value::Date(_) => 0,
+            Value::Bool(_) => 1,
+            Value::Text(_) => 2,
+            Value::Error(_) => 3,
+        };
+        kind(self) == kind(other)
+    }
+
+    /// How a formula orders `self` and `other`: like `partial_cmp`, except
+    /// that texts compare ignoring case.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cmp::Ordering;
+    /// use lab1::value::Value;
+    ///
+    /// let (apple, pear) = (Value::text("apple"), Value::text("Pear"));
+    /// assert_eq!(apple.collate(&Value::text("APPLE")), Some(Ordering::Equal));
+    /// assert_eq!(apple.collate(&pear), Some(Ordering::Less));
+    /// assert_eq!(apple.collate(&Value::from(1)), None);
+    /// ```
+    pub fn collate(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Text(a), Value::Text(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+            _ => self.partial_cmp(other),
+        }
+    }
+
+    /// The text shown for this value: exactly `precision` decimals when
+    /// given, otherwise whole numbers without a decimal point and fractions
+    /// rounded to [`AUTO_DECIMALS`] with trailing zeros dropped. A decimal
+    /// shows all its decimals unless a precision is given, and is rounded
+    /// half-even to fewer. A date is shown as [`crate::date::format`] writes
+    /// it, whatever the precision.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::value::{Value, ERR};
+    ///
+    /// assert_eq!(Value::from(7).format(None), "7");
+    /// assert_eq!(Value::from(1.0 / 3.0).format(None), "0.333333");
+    /// assert_eq!(Value::from(0.1 + 0.2).format(None), "0.3");
+    /// assert_eq!(Value::from(2.5).format(Some(2)), "2.50");
+    /// assert_eq!(Value::from(-0.001).format(Some(1)), "0.0");
+    /// assert_eq!(ERR.format(Some(2)), "ERR");
+    /// assert_eq!(Value::Int(i64::MAX).format(Some(1)), "9223372036854775807.0");
+    ///
+    /// let price = lab1::decimal::Decimal { units: 1005, scale: 3 };
+    /// assert_eq!(Value::Decimal(price).format(None), "1.005");
+    /// assert_eq!(Value::Decimal(price).format(Some(2)), "1.00");
+    /// assert_eq!(Value::Decimal(price).format(Some(4)), "1.0050");
+    /// ```
+    pub fn format(&self, precision: Option<usize>) -> String {
+        let x = match *self {
+            Value::Number(x) => x,
+            Value::Int(i) => {
+                return match precision {
+                    Some(p) if p > 0 => format!("{}.{}", i, "0".repeat(p)),
+                    _ => i.to_string(),
+                }
+            }
+            Value::Decimal(d) => return format_decimal(d, precision),
+            Value::Bool(_) | Value::Text(_) | Value::Date(_) => return self.to_string(),
+            Value::Error(e) => return e.name().to_string(),
+        };
+        let text = match precision {
+            Some(p) => format!("{:.*}", p, x),
+            None if x.fract() == 0.0 || x.abs() >= 1e15 => x.to_string(),
+            None => {
+                let scale = 10f64.powi(AUTO_DECIMALS as i32);
+                ((x * scale).round() / scale).to_string()
+            }
+        };
+        // never show a minus sign on a value that displays as zero
+        if text.starts_with('-') && text[1..].chars().all(|c| c == '0' || c == '.') {
+            text[1..].to_string()
+        } else {
+            text
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// The value at full precision, as exported: `2.5`, `7`, `2024-05-01`,
+    /// `ERR`, `#NUM!` or the text itself.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(x) => write!(f, "{}", x),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Bool(b) => f.write_str(if *b { "TRUE" } else { "FALSE" }),
+            Value::Text(t) => f.write_str(t),
+            Value::Date(x) => f.write_str(&crate::date::format(*x)),
+            Value::Error(e) => f.write_str(e.name()),
+        }
+    }
+}
+
+/// `d` with `precision` decimals, padded with zeros or rounded half-even.
+fn format_decimal(d: Decimal, precision: Option<usize>) -> String {
+    let p = match precision {
+        Some(p) => p as u32,
+        None => return d.to_string(),
+    };
+    if p <= d.scale {
+        // fewer units always fit
+        return d.rescale(p, Rounding::HalfEven).unwrap_or(d).to_string();
+    }
+    let pad = "0".repeat((p - d.scale) as usize);
+    if d.scale == 0 {
+        format!("{}.{}", d, pad)
+    } else {
+        format!("{}{}", d, pad)
+    }
+}
+
+/// Split a decimal literal such as `"12.75"` into a numerator and a power of
+/// ten (`(1275, 100)`), both fitting an `i32`, so it can be stored in a
+/// [`Formula`](crate::graph::Formula). Signs are not accepted.
+///
+/// # Examples
+///
+/// ```rust
+/// use lab1::value::decimal_parts;
+///
+/// assert_eq!(decimal_parts("12.75"), Some((1275, 100)));
+/// assert_eq!(decimal_parts("3"), Some((3, 1)));
+/// assert_eq!(decimal_parts("0.5"), Some((5, 10)));
+/// assert_eq!(decimal_parts("1."), None);
+/// assert_eq!(decimal_parts("-1.5"), None);
+/// ```
+pub fn decimal_parts(text: &str) -> Option<(i32, i32)> {
+    let (whole, frac) = text.split_once('.').unwrap_or((text, ""));
+    let digits_only = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !digits_only(whole) || !digits_only(frac) {
+        return None;
+    }
+    if text.contains('.') && frac.is_empty() {
+        return None;
+    }
+    let num = format!("{}{}", whole, frac).parse::<i32>().ok()?;
+    let den = 10i32.checked_pow(frac.len() as u32)?;
+    Some((num, den))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_is_compact() {
+        // every cell of a sheet holds one, so keep it at two words
+        assert_eq!(std::mem::size_of::<Value>(), 16);
+    }
+
+    #[test]
+    fn test_comparisons_with_plain_numbers() {
+        assert_eq!(Value::from(3), 3);
+        assert_eq!(Value::from(2.5), 2.5);
+        assert_ne!(ERR, 0);
+        assert!(ERR.is_error());
+        assert_eq!(Value::default().number(), Some(0.0));
+        assert_eq!(Value::Int(2), Value::Number(2.0));
+        assert_ne!(ERR, NUM);
+        assert!(NUM.is_error());
+        assert_eq!(Value::finite(f64::INFINITY), NUM);
+        assert_eq!(Value::Bool(true), 1);
+        assert!(Value::Int(3) > Value::Number(2.5));
+        assert!(ERR.partial_cmp(&ERR).is_none());
+    }
+
+    #[test]
+    fn test_texts() {
+        let label = Value::text("Q1 sales");
+        assert!(
+            matches!((label.clone(), label.clone()), (Value::Text(a), Value::Text(b)) if Arc::ptr_eq(&a, &b))
+        );
+        assert_eq!(label, Value::text("Q1 sales"));
+        assert_ne!(label, Value::text("Q1 SALES"));
+        assert_eq!(
+            label.collate(&Value::text("Q1 SALES")),
+            Some(Ordering::Equal)
+        );
+        assert_ne!(Value::text("1"), 1);
+        assert!(label.partial_cmp(&Value::from(1)).is_none());
+        assert!(label.same_kind(&Value::text("")));
+        assert!(!label.same_kind(&Value::Bool(true)));
+        assert!(Value::Int(1).same_kind(&Value::from(2.5)));
+        assert_eq!(label.format(Some(2)), "Q1 sales");
+    }
+
+    #[test]
+    fn test_format_whole_and_large_numbers() {
+        assert_eq!(Value::from(-12).format(None), "-12");
+        assert_eq!(Value::from(1e20).format(None), "100000000000000000000");
+        assert_eq!(Value::from(3).format(Some(2)), "3.00");
+        assert_eq!(Value::from(2.0 / 3.0).format(Some(0)), "1");
+        assert_eq!(Value::from(1.0 / 3.0).to_string(), "0.3333333333333333");
+        assert_eq!(Value::Int(-5).to_string(), "-5");
+        assert_eq!(NUM.format(None), "#NUM!");
+        assert_eq!(Value::Bool(false).format(Some(2)), "FALSE");
+    }
+
+    #[test]
+    fn test_decimal_parts_limits() {
+        assert_eq!(decimal_parts("2147483647"), Some((i32::MAX, 1)));
+        assert_eq!(decimal_parts("2147483648"), None);
+        assert_eq!(
+            decimal_parts("0.123456789"),
+            Some((123456789, 1_000_000_000))
+        );
+        assert_eq!(decimal_parts("0.1234567890"), None);
+        assert_eq!(decimal_parts(".5"), None);
+        assert_eq!(decimal_parts("1.2.3"), None);
+    }
+}
@@ -0,0 +1,276 @@
+//! Module `view`.
+//!
+//! Viewport settings for the terminal grid: how many rows and columns are
+//! shown, how wide each column is, and how far `w`/`a`/`s`/`d` move. Every
+//! [`Spreadsheet`](crate::spreadsheet::Spreadsheet) carries one in its
+//! `view` field; the defaults reproduce the classic 10×10 window with
+//! 10-character columns and 10-cell page jumps.
+//!
+//! Values wider than their column are cut and end in [`TRUNCATION_MARKER`].
+//! Numbers are shown with the viewport's `precision` (see
+//! [`Value::format`]).
+
+use std::collections::HashMap;
+
+use crate::display::column_index_to_name;
+use crate::value::{Value, MAX_PRECISION};
+
+/// Largest number of rows or columns a viewport may show.
+pub const MAX_VIEW: usize = 100;
+/// Largest column width in characters.
+pub const MAX_WIDTH: usize = 100;
+/// Last character of a value that did not fit its column.
+pub const TRUNCATION_MARKER: char = '…';
+
+/// What part of the sheet the terminal shows and how.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    /// Rows shown at once.
+    pub rows: usize,
+    /// Columns shown at once.
+    pub cols: usize,
+    /// Cells moved by one `w`/`a`/`s`/`d`.
+    pub scroll_step: usize,
+    /// Width of columns without an explicit width.
+    pub default_width: usize,
+    /// Explicit widths by zero-based column index.
+    pub widths: HashMap<usize, usize>,
+    /// Decimals shown for numbers; `None` shows whole numbers as integers
+    /// and rounds fractions to a few places.
+    pub precision: Option<usize>,
+}
+
+impl Default for Viewport {
+    fn default() -> Viewport {
+        Viewport {
+            rows: 10,
+            cols: 10,
+            scroll_step: 10,
+            default_width: 10,
+            widths: HashMap::new(),
+            precision: None,
+        }
+    }
+}
+
+fn check(what: &str, n: usize, max: usize) -> Result<usize, String> {
+    if (1..=max).contains(&n) {
+        Ok(n)
+    } else {
+        Err(format!("{} must be between 1 and {}", what, max))
+    }
+}
+
+impl Viewport {
+    /// Width of column `col` in characters.
+    pub fn width(&self, col: usize) -> usize {
+        self.widths.get(&col).copied().unwrap_or(self.default_width)
+    }
+
+    /// Show `rows` × `cols` cells at once.
+    pub fn set_size(&mut self, rows: usize, cols: usize) -> Result<(), String> {
+        self.rows = check("view rows", rows, MAX_VIEW)?;
+        self.cols = check("view cols", cols, MAX_VIEW)?;
+        Ok(())
+    }
+
+    /// Give column `col` its own width.
+    pub fn set_width(&mut self, col: usize, width: usize) -> Result<(), String> {
+        self.widths
+            .insert(col, check("column width", width, MAX_WIDTH)?);
+        Ok(())
+    }
+
+    /// Move `w`/`a`/`s`/`d` by `step` cells.
+    pub fn set_scroll_step(&mut self, step: usize) -> Result<(), String> {
+        self.scroll_step = check("scroll step", step, MAX_VIEW)?;
+        Ok(())
+    }
+
+    /// Show numbers with exactly `precision` decimals, or automatically.
+    pub fn set_precision(&mut self, precision: Option<usize>) -> Result<(), String> {
+        if precision.is_some_and(|p| p > MAX_PRECISION) {
+            return Err(format!("precision must be at most {}", MAX_PRECISION));
+        }
+        self.precision = precision;
+        Ok(())
+    }
+
+    /// Apply a `w`/`a`/`s`/`d` scroll to the origin `(curr_x, curry)` of a
+    /// `rows` × `cols` sheet. Scrolling stops once the last row or column is
+    /// in view. Returns `false` for any other command.
+    pub fn scroll(
+        &self,
+        cmd: &str,
+        curr_x: &mut usize,
+        curry: &mut usize,
+        cols: usize,
+        rows: usize,
+    ) -> bool {
+        let step = self.scroll_step;
+        match cmd {
+            "w" => *curry = curry.saturating_sub(step),
+            "a" => *curr_x = curr_x.saturating_sub(step),
+            "s" => *curry += rows.saturating_sub(*curry + self.rows).min(step),
+            "d" => *curr_x += cols.saturating_sub(*curr_x + self.cols).min(step),
+            _ => return false,
+        }
+        true
+    }
+
+    /// The visible window with top-left cell `(curr_x, curry)` as the text
+    /// the terminal printer shows: a header line of column names, then one
+    /// line per row, every line ending in a newline.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use lab1::value::Value;
+    /// use lab1::view::Viewport;
+    ///
+    /// let mut view = Viewport::default();
+    /// view.set_size(1, 2).unwrap();
+    /// view.set_width(0, 4).unwrap();
+    /// let arr = [Value::from(123456), Value::from(7), Value::from(8)];
+    /// let text = view.render(0, 0, &arr, 3, 1);
+    /// assert_eq!(text, "      A   B         \n1     123…7         \n");
+    /// ```
+    pub fn render(
+        &self,
+        curr_x: usize,
+        curry: usize,
+        arr: &[Value],
+        cols: usize,
+        rows: usize,
+    ) -> String {
+        let last_col = cols.min(curr_x + self.cols);
+        let mut out = String::from("      ");
+        for c in curr_x..last_col {
+            out.push_str(&fit(&column_index_to_name(c), self.width(c)));
+        }
+        out.push('\n');
+        for r in curry..rows.min(curry + self.rows) {
+            out.push_str(&format!("{:<3}   ", r + 1));
+            for c in curr_x..last_col {
+                out.push_str(&fit(
+                    &arr[r * cols + c].format(self.precision),
+                    self.width(c),
+                ));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Zero-based index of a column name such as `"B"` or `"AA"`.
+pub fn column_index(name: &str) -> Option<usize> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    name.chars()
+        .try_fold(0usize, |acc, c| {
+            acc.checked_mul(26)?
+                .checked_add(c as usize - 'A' as usize + 1)
+        })
+        .map(|n| n - 1)
+}
+
+/// Left-align `text` in `width` characters, cutting it and ending it in
+/// [`TRUNCATION_MARKER`] if it is longer.
+pub fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        format!("{:<width$}", text)
+    } else {
+        let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+        cut.push(TRUNCATION_MARKER);
+        cut
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_render_matches_classic_layout() {
+        let mut arr = vec![Value::default(); 12 * 12];
+        arr[13] = crate::value::ERR;
+        arr[14] = Value::from(-1234567890);
+        let text = Viewport::default().render(0, 0, &arr, 12, 12);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(
+            lines[0],
+            format!(
+                "      {}",
+                "ABCDEFGHIJ"
+                    .chars()
+                    .map(|c| format!("{:<10}", c))
+                    .collect::<String>()
+            )
+        );
+        assert!(lines[2].starts_with("2     0         ERR       -12345678…0"));
+        assert_eq!(lines[10].len(), 6 + 100);
+    }
+
+    #[test]
+    fn test_column_index() {
+        assert_eq!(column_index("A"), Some(0));
+        assert_eq!(column_index("AA"), Some(26));
+        assert_eq!(column_index("b"), None);
+        assert_eq!(column_index(""), None);
+    }
+
+    #[test]
+    fn test_fit() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abcdef", 5), "abcd…");
+        assert_eq!(fit("ab", 1), "…");
+    }
+
+    #[test]
+    fn test_scroll_uses_step_and_view_size() {
+        let mut view = Viewport::default();
+        view.set_size(5, 3).unwrap();
+        view.set_scroll_step(4).unwrap();
+        let (mut x, mut y) = (0, 0);
+        for _ in 0..3 {
+            view.scroll("s", &mut x, &mut y, 10, 10);
+        }
+        assert_eq!(y, 5);
+        view.scroll("d", &mut x, &mut y, 10, 10);
+        view.scroll("w", &mut x, &mut y, 10, 10);
+        assert_eq!((x, y), (4, 1));
+        view.scroll("a", &mut x, &mut y, 10, 10);
+        view.scroll("a", &mut x, &mut y, 10, 10);
+        assert_eq!(x, 0);
+        assert!(!view.scroll("q", &mut x, &mut y, 10, 10));
+    }
+
+    #[test]
+    fn test_settings_are_validated() {
+        let mut view = Viewport::default();
+        assert!(view.set_size(0, 5).is_err());
+        assert!(view.set_width(1, MAX_WIDTH + 1).is_err());
+        assert!(view.set_scroll_step(0).is_err());
+        assert!(view.set_precision(Some(MAX_PRECISION + 1)).is_err());
+        assert_eq!(view, Viewport::default());
+        view.set_width(1, 20).unwrap();
+        assert_eq!((view.width(0), view.width(1)), (10, 20));
+    }
+
+    #[test]
+    fn test_render_uses_precision() {
+        let mut view = Viewport::default();
+        view.set_size(1, 3).unwrap();
+        let arr = [Value::from(2.5), Value::from(1.0 / 3.0), Value::from(4)];
+        assert!(view
+            .render(0, 0, &arr, 3, 1)
+            .ends_with("2.5       0.333333  4         \n"));
+        view.set_precision(Some(2)).unwrap();
+        assert!(view
+            .render(0, 0, &arr, 3, 1)
+            .ends_with("2.50      0.33      4.00      \n"));
+    }
+}